@@ -0,0 +1,136 @@
+//! Writes the Groth16 keys and `manifest.json` entry the backend's `CircuitRegistry` loads
+//! for a circuit circom has already compiled, see `build/circuits/README.md`.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::Groth16;
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, LinearCombination, SynthesisError, Variable};
+use ark_serialize::CanonicalSerialize;
+use ark_snark::SNARK;
+use circom_witness::R1csFile;
+use rand::rngs::OsRng;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+const USAGE: &str = "Usage: circuit_setup <circuits-dir> <name>...
+
+  circuits-dir  circom's output directory, e.g. build/circuits
+  name          circuit to set up, e.g. InsertLeaf; reads <name>.r1cs and writes <name>.pk,
+                <name>.vk and the circuit's entry in manifest.json
+
+The keys come from a single-party setup: whoever ran it can forge proofs, so it is only
+fit for development and testing.";
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+// Allocates wires the way ark-circom's `CircomCircuit` does, so the keys fit the circuits the
+// backend proves with: wire 0 is the constant one, then the public outputs and inputs, then
+// every other wire as a witness.
+struct SetupCircuit {
+    r1cs: R1csFile,
+}
+
+impl ConstraintSynthesizer<Fr> for SetupCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let num_inputs = 1 + self.r1cs.n_pub_out + self.r1cs.n_pub_in;
+        for _ in 1..num_inputs {
+            cs.new_input_variable(|| Ok(Fr::from(1u32)))?;
+        }
+        for _ in num_inputs..self.r1cs.n_wires {
+            cs.new_witness_variable(|| Ok(Fr::from(1u32)))?;
+        }
+
+        let variable = |wire: usize| {
+            if wire < num_inputs {
+                Variable::Instance(wire)
+            } else {
+                Variable::Witness(wire - num_inputs)
+            }
+        };
+        let lc = |terms: &[(usize, Fr)]| {
+            terms
+                .iter()
+                .fold(LinearCombination::zero(), |lc, (wire, coeff)| lc + (*coeff, variable(*wire)))
+        };
+        for constraint in &self.r1cs.constraints {
+            cs.enforce_constraint(lc(&constraint.a), lc(&constraint.b), lc(&constraint.c))?;
+        }
+        Ok(())
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some((dir, names)) = args.split_first().filter(|(_, names)| !names.is_empty()) else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+
+    let dir = PathBuf::from(dir);
+    for name in names {
+        if let Err(err) = setup(&dir, name) {
+            eprintln!("Error: {}", err);
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn setup(dir: &Path, name: &str) -> Result<(), String> {
+    let r1cs_path = dir.join(format!("{name}.r1cs"));
+    let r1cs_bytes = fs::read(&r1cs_path).map_err(|e| format!("Failed to read {}: {}", r1cs_path.display(), e))?;
+    let r1cs = R1csFile::read(r1cs_bytes.as_slice()).map_err(|e| format!("{}: {}", r1cs_path.display(), e))?;
+    println!("Setting up {} ({} constraints)", name, r1cs.constraints.len());
+
+    let (proving_key, verifying_key) = Groth16::<Bn254>::circuit_specific_setup(SetupCircuit { r1cs }, &mut OsRng)
+        .map_err(|e| format!("Setup of {} failed: {}", name, e))?;
+
+    let mut pk_bytes = Vec::new();
+    proving_key.serialize_uncompressed(&mut pk_bytes).map_err(|e| e.to_string())?;
+    let mut vk_bytes = Vec::new();
+    verifying_key.serialize_uncompressed(&mut vk_bytes).map_err(|e| e.to_string())?;
+    write(&dir.join(format!("{name}.pk")), &pk_bytes)?;
+    write(&dir.join(format!("{name}.vk")), &vk_bytes)?;
+
+    // The registry prefers the sym file and falls back to the wasm generator
+    let mut entry = Map::new();
+    for (field, path) in [
+        ("sym", dir.join(format!("{name}.sym"))),
+        ("wasm", dir.join(format!("{name}_js")).join(format!("{name}.wasm"))),
+    ] {
+        if let Ok(bytes) = fs::read(&path) {
+            entry.insert(field.to_string(), json!(sha256_hex(&bytes)));
+        }
+    }
+    if entry.is_empty() {
+        return Err(format!("{} has neither a sym file nor a wasm generator", name));
+    }
+    entry.insert("r1cs".to_string(), json!(sha256_hex(&r1cs_bytes)));
+    entry.insert("proving_key".to_string(), json!(sha256_hex(&pk_bytes)));
+    entry.insert("verifying_key".to_string(), json!(sha256_hex(&vk_bytes)));
+
+    let manifest_path = dir.join(MANIFEST_FILE);
+    let mut manifest = match fs::read_to_string(&manifest_path) {
+        Ok(contents) => serde_json::from_str::<Map<String, Value>>(&contents)
+            .map_err(|e| format!("Invalid {}: {}", manifest_path.display(), e))?,
+        Err(_) => Map::new(),
+    };
+    manifest.insert(name.to_string(), Value::Object(entry));
+    let contents = serde_json::to_string_pretty(&manifest).map_err(|e| e.to_string())?;
+    write(&manifest_path, contents.as_bytes())?;
+    println!("Wrote {}.pk, {}.vk and its {} entry", name, name, MANIFEST_FILE);
+    Ok(())
+}
+
+fn write(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    fs::write(path, bytes).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
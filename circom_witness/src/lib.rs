@@ -0,0 +1,338 @@
+//! Native witness calculation for the circom circuits in `circuits/`.
+//!
+//! Instead of running the wasm generator, the witness is solved from the circuit's own
+//! constraints: any constraint with a single unknown wire that enters it linearly fixes that
+//! wire. The few signals circom computes with `<--` (bit decompositions, `IsZero` inverses)
+//! cannot be derived that way and are filled in by [`Hint`]s.
+
+mod r1cs;
+mod sym;
+
+use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, Field, PrimeField, Zero};
+use num_bigint::{BigInt, Sign};
+use thiserror::Error;
+
+pub use r1cs::{Constraint, LinearCombination, R1csFile};
+pub use sym::read_sym;
+
+#[derive(Debug, Error)]
+pub enum WitnessError {
+    #[error("Failed to read circuit file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid r1cs file: {0}")]
+    InvalidR1cs(String),
+    #[error("Invalid sym line: {0}")]
+    InvalidSym(String),
+    #[error("Signal {0} is not in the witness")]
+    UnknownSignal(String),
+    #[error("Input {0} expects {1} values, got {2}")]
+    InputLength(String, usize, usize),
+    #[error("Could not solve signal {0}")]
+    Unsolved(String),
+    #[error("Constraint {0} is not satisfied")]
+    ConstraintFailed(usize),
+}
+
+/// Values for signals assigned with `<--`, which the constraints alone do not determine.
+#[derive(Clone, Debug)]
+pub enum Hint {
+    /// `Num2Bits`: `<bits>[i]` is bit `i` of the signal `input`.
+    Bits { input: String, bits: String, n: usize },
+    /// `IsZero`: for every component whose name ends in `component_suffix`,
+    /// `<component>.inv` is `1 / <component>.in`, or zero when the input is zero.
+    InverseOrZero { component_suffix: String },
+}
+
+// A hint resolved to witness wires
+enum WireHint {
+    Bits { input: usize, bits: Vec<usize> },
+    InverseOrZero { input: usize, inv: usize },
+}
+
+impl WireHint {
+    // Values of the hinted wires, once the hint's input is known
+    fn evaluate(&self, witness: &[Option<Fr>]) -> Vec<(usize, Fr)> {
+        match self {
+            WireHint::Bits { input, bits } => match witness[*input] {
+                Some(value) => {
+                    let value = value.into_bigint();
+                    bits.iter()
+                        .enumerate()
+                        .map(|(i, wire)| (*wire, Fr::from(value.get_bit(i) as u64)))
+                        .collect()
+                }
+                None => Vec::new(),
+            },
+            WireHint::InverseOrZero { input, inv } => match witness[*input] {
+                Some(value) => vec![(*inv, value.inverse().unwrap_or_else(Fr::zero))],
+                None => Vec::new(),
+            },
+        }
+    }
+}
+
+/// Computes circom witnesses natively from a circuit's `.r1cs` and `.sym` files.
+pub struct WitnessCalculator {
+    r1cs: R1csFile,
+    signals: HashMap<String, usize>,
+    hints: Vec<WireHint>,
+    // Constraints each wire appears in, without duplicates
+    wire_constraints: Vec<Vec<usize>>,
+}
+
+impl WitnessCalculator {
+    pub fn new(r1cs: R1csFile, signals: HashMap<String, usize>, hints: &[Hint]) -> Result<Self, WitnessError> {
+        let mut wire_constraints = vec![Vec::new(); r1cs.n_wires];
+        for (i, constraint) in r1cs.constraints.iter().enumerate() {
+            for wire in constraint_wires(constraint) {
+                wire_constraints[wire].push(i);
+            }
+        }
+
+        let mut calculator = Self {
+            r1cs,
+            signals,
+            hints: Vec::new(),
+            wire_constraints,
+        };
+        for hint in hints {
+            calculator.resolve_hint(hint)?;
+        }
+        Ok(calculator)
+    }
+
+    pub fn from_files(r1cs_path: impl AsRef<Path>, sym_path: impl AsRef<Path>, hints: &[Hint]) -> Result<Self, WitnessError> {
+        let r1cs = R1csFile::read(BufReader::new(File::open(r1cs_path)?))?;
+        let signals = read_sym(BufReader::new(File::open(sym_path)?))?;
+        Self::new(r1cs, signals, hints)
+    }
+
+    /// Loads `InsertLeaf` or `MerkleTreeUpdater`, which share the `indexBits` decomposition
+    /// of `pathIndices` and use `IsEqual` inside `RawMerkleTree`.
+    pub fn merkle_circuit(
+        r1cs_path: impl AsRef<Path>,
+        sym_path: impl AsRef<Path>,
+        max_depth: usize,
+    ) -> Result<Self, WitnessError> {
+        Self::from_files(r1cs_path, sym_path, &merkle_hints(max_depth))
+    }
+
+    pub fn n_wires(&self) -> usize {
+        self.r1cs.n_wires
+    }
+
+    /// Number of public signals (outputs then public inputs), which follow the constant wire.
+    pub fn n_public(&self) -> usize {
+        self.r1cs.n_pub_out + self.r1cs.n_pub_in
+    }
+
+    /// Calculates the full witness, in the same order and with the same values as circom's
+    /// wasm generator. `inputs` uses the shape of `CircomBuilder::inputs`: arrays are flattened.
+    pub fn calculate_witness(&self, inputs: &HashMap<String, Vec<BigInt>>) -> Result<Vec<Fr>, WitnessError> {
+        let mut witness: Vec<Option<Fr>> = vec![None; self.r1cs.n_wires];
+        witness[0] = Some(Fr::from(1u64));
+        for (name, values) in inputs {
+            for (wire, value) in self.input_wires(name, values.len())?.into_iter().zip(values) {
+                witness[wire] = Some(bigint_to_fr(value));
+            }
+        }
+
+        let mut unknowns: Vec<usize> = self
+            .r1cs
+            .constraints
+            .iter()
+            .map(|constraint| {
+                constraint_wires(constraint)
+                    .into_iter()
+                    .filter(|wire| witness[*wire].is_none())
+                    .count()
+            })
+            .collect();
+        let mut queue: Vec<usize> = (0..unknowns.len()).filter(|i| unknowns[*i] == 1).collect();
+
+        loop {
+            while let Some(i) = queue.pop() {
+                if unknowns[i] != 1 {
+                    continue;
+                }
+                if let Some((wire, value)) = solve(&self.r1cs.constraints[i], &witness) {
+                    self.assign(&mut witness, &mut unknowns, &mut queue, wire, value);
+                }
+            }
+
+            // Constraint propagation is stuck, fill in whatever hints are ready
+            let mut progressed = false;
+            for hint in self.hints.iter() {
+                for (wire, value) in hint.evaluate(&witness) {
+                    if witness[wire].is_none() {
+                        self.assign(&mut witness, &mut unknowns, &mut queue, wire, value);
+                        progressed = true;
+                    }
+                }
+            }
+            if !progressed {
+                break;
+            }
+        }
+
+        if let Some(wire) = witness.iter().position(Option::is_none) {
+            return Err(WitnessError::Unsolved(self.signal_name(wire)));
+        }
+        let witness: Vec<Fr> = witness.into_iter().map(Option::unwrap).collect();
+
+        for (i, constraint) in self.r1cs.constraints.iter().enumerate() {
+            let eval = |lc: &LinearCombination| lc.iter().map(|(wire, coeff)| witness[*wire] * coeff).sum::<Fr>();
+            if eval(&constraint.a) * eval(&constraint.b) != eval(&constraint.c) {
+                return Err(WitnessError::ConstraintFailed(i));
+            }
+        }
+        Ok(witness)
+    }
+
+    fn assign(
+        &self,
+        witness: &mut [Option<Fr>],
+        unknowns: &mut [usize],
+        queue: &mut Vec<usize>,
+        wire: usize,
+        value: Fr,
+    ) {
+        witness[wire] = Some(value);
+        for &i in self.wire_constraints[wire].iter() {
+            unknowns[i] -= 1;
+            if unknowns[i] == 1 {
+                queue.push(i);
+            }
+        }
+    }
+
+    fn input_wires(&self, name: &str, len: usize) -> Result<Vec<usize>, WitnessError> {
+        let qualified = format!("main.{}", name);
+        if let Some(wire) = self.signals.get(&qualified) {
+            if len != 1 {
+                return Err(WitnessError::InputLength(name.to_string(), 1, len));
+            }
+            return Ok(vec![*wire]);
+        }
+        let wires: Vec<usize> = (0..)
+            .map_while(|i| self.signals.get(&format!("{}[{}]", qualified, i)).copied())
+            .collect();
+        if wires.is_empty() {
+            return Err(WitnessError::UnknownSignal(qualified));
+        }
+        if wires.len() != len {
+            return Err(WitnessError::InputLength(name.to_string(), wires.len(), len));
+        }
+        Ok(wires)
+    }
+
+    fn resolve_hint(&mut self, hint: &Hint) -> Result<(), WitnessError> {
+        match hint {
+            Hint::Bits { input, bits, n } => {
+                let input = self.wire(input)?;
+                let bits = (0..*n)
+                    .map(|i| self.wire(&format!("{}[{}]", bits, i)))
+                    .collect::<Result<_, _>>()?;
+                self.hints.push(WireHint::Bits { input, bits });
+            }
+            Hint::InverseOrZero { component_suffix } => {
+                let inv_suffix = format!("{}.inv", component_suffix);
+                let mut resolved = Vec::new();
+                for (name, inv) in self.signals.iter() {
+                    if let Some(component) = name.strip_suffix(".inv").filter(|_| name.ends_with(&inv_suffix)) {
+                        let input = self.wire(&format!("{}.in", component))?;
+                        resolved.push(WireHint::InverseOrZero { input, inv: *inv });
+                    }
+                }
+                self.hints.extend(resolved);
+            }
+        }
+        Ok(())
+    }
+
+    fn wire(&self, name: &str) -> Result<usize, WitnessError> {
+        self.signals
+            .get(name)
+            .copied()
+            .ok_or_else(|| WitnessError::UnknownSignal(name.to_string()))
+    }
+
+    fn signal_name(&self, wire: usize) -> String {
+        self.signals
+            .iter()
+            .find(|(_, index)| **index == wire)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| format!("wire {}", wire))
+    }
+}
+
+/// Hints for the circuits built on `RawMerkleTree` with a `Num2Bits(MAX_DEPTH)` named `indexBits`.
+pub fn merkle_hints(max_depth: usize) -> Vec<Hint> {
+    vec![
+        Hint::Bits {
+            input: "main.pathIndices".to_string(),
+            bits: "main.indexBits.out".to_string(),
+            n: max_depth,
+        },
+        Hint::InverseOrZero {
+            component_suffix: ".isz".to_string(),
+        },
+    ]
+}
+
+fn constraint_wires(constraint: &Constraint) -> Vec<usize> {
+    let mut wires: Vec<usize> = constraint
+        .a
+        .iter()
+        .chain(constraint.b.iter())
+        .chain(constraint.c.iter())
+        .map(|(wire, _)| *wire)
+        .collect();
+    wires.sort_unstable();
+    wires.dedup();
+    wires
+}
+
+// Solves `a * b = c` for its only unknown wire, if that wire enters linearly
+fn solve(constraint: &Constraint, witness: &[Option<Fr>]) -> Option<(usize, Fr)> {
+    let mut unknown = None;
+    // Known part and coefficient of the unknown wire for each side
+    let mut split = |lc: &LinearCombination| {
+        let mut known = Fr::zero();
+        let mut coeff = Fr::zero();
+        for (wire, c) in lc {
+            match witness[*wire] {
+                Some(value) => known += value * c,
+                None => {
+                    unknown = Some(*wire);
+                    coeff += c;
+                }
+            }
+        }
+        (known, coeff)
+    };
+    let (a0, ax) = split(&constraint.a);
+    let (b0, bx) = split(&constraint.b);
+    let (c0, cx) = split(&constraint.c);
+    let wire = unknown?;
+
+    // (a0 + ax x)(b0 + bx x) = c0 + cx x, linear in x only when one of ax, bx is zero
+    if !ax.is_zero() && !bx.is_zero() {
+        return None;
+    }
+    let denominator = ax * b0 + bx * a0 - cx;
+    let value = (c0 - a0 * b0) * denominator.inverse()?;
+    Some((wire, value))
+}
+
+fn bigint_to_fr(value: &BigInt) -> Fr {
+    let (sign, bytes) = value.to_bytes_le();
+    let magnitude = Fr::from_le_bytes_mod_order(&bytes);
+    match sign {
+        Sign::Minus => -magnitude,
+        _ => magnitude,
+    }
+}
@@ -0,0 +1,131 @@
+use std::io::Read;
+
+use ark_bn254::Fr;
+use ark_ff::PrimeField;
+
+use crate::WitnessError;
+
+const HEADER_SECTION: u32 = 1;
+const CONSTRAINTS_SECTION: u32 = 2;
+
+/// Sparse linear combination over witness wires: `(wire, coefficient)` pairs.
+pub type LinearCombination = Vec<(usize, Fr)>;
+
+/// `a * b = c` over the witness.
+pub struct Constraint {
+    pub a: LinearCombination,
+    pub b: LinearCombination,
+    pub c: LinearCombination,
+}
+
+/// The parts of a circom `.r1cs` file the solver needs.
+pub struct R1csFile {
+    pub n_wires: usize,
+    pub n_pub_out: usize,
+    pub n_pub_in: usize,
+    pub n_prv_in: usize,
+    pub constraints: Vec<Constraint>,
+}
+
+impl R1csFile {
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, WitnessError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let mut cursor = Cursor { bytes: &bytes, pos: 0 };
+
+        if cursor.take(4)? != b"r1cs" {
+            return Err(WitnessError::InvalidR1cs("bad magic".to_string()));
+        }
+        let version = cursor.u32()?;
+        if version != 1 {
+            return Err(WitnessError::InvalidR1cs(format!("unsupported version {}", version)));
+        }
+
+        // Sections may come in any order, the header has to be read before the constraints
+        let n_sections = cursor.u32()?;
+        let mut sections = Vec::with_capacity(n_sections as usize);
+        for _ in 0..n_sections {
+            let section_type = cursor.u32()?;
+            let size = cursor.u64()? as usize;
+            sections.push((section_type, cursor.pos, size));
+            cursor.take(size)?;
+        }
+
+        let &(_, header_pos, _) = sections
+            .iter()
+            .find(|(section_type, _, _)| *section_type == HEADER_SECTION)
+            .ok_or_else(|| WitnessError::InvalidR1cs("missing header section".to_string()))?;
+        let mut header = Cursor { bytes: &bytes, pos: header_pos };
+        let field_size = header.u32()? as usize;
+        if field_size != 32 {
+            return Err(WitnessError::InvalidR1cs(format!("unsupported field size {}", field_size)));
+        }
+        if Fr::from_le_bytes_mod_order(header.take(field_size)?) != Fr::from(0u64) {
+            return Err(WitnessError::InvalidR1cs("circuit is not over the bn254 scalar field".to_string()));
+        }
+        let n_wires = header.u32()? as usize;
+        let n_pub_out = header.u32()? as usize;
+        let n_pub_in = header.u32()? as usize;
+        let n_prv_in = header.u32()? as usize;
+        let _n_labels = header.u64()?;
+        let n_constraints = header.u32()? as usize;
+
+        let &(_, constraints_pos, _) = sections
+            .iter()
+            .find(|(section_type, _, _)| *section_type == CONSTRAINTS_SECTION)
+            .ok_or_else(|| WitnessError::InvalidR1cs("missing constraints section".to_string()))?;
+        let mut body = Cursor { bytes: &bytes, pos: constraints_pos };
+        let mut constraints = Vec::with_capacity(n_constraints);
+        for _ in 0..n_constraints {
+            constraints.push(Constraint {
+                a: body.linear_combination(field_size)?,
+                b: body.linear_combination(field_size)?,
+                c: body.linear_combination(field_size)?,
+            });
+        }
+
+        Ok(Self {
+            n_wires,
+            n_pub_out,
+            n_pub_in,
+            n_prv_in,
+            constraints,
+        })
+    }
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], WitnessError> {
+        let end = self.pos + len;
+        let slice = self
+            .bytes
+            .get(self.pos..end)
+            .ok_or_else(|| WitnessError::InvalidR1cs("unexpected end of file".to_string()))?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32, WitnessError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, WitnessError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn linear_combination(&mut self, field_size: usize) -> Result<LinearCombination, WitnessError> {
+        let n_factors = self.u32()? as usize;
+        let mut factors = Vec::with_capacity(n_factors);
+        for _ in 0..n_factors {
+            let wire = self.u32()? as usize;
+            let coefficient = Fr::from_le_bytes_mod_order(self.take(field_size)?);
+            factors.push((wire, coefficient));
+        }
+        Ok(factors)
+    }
+}
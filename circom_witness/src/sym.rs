@@ -0,0 +1,25 @@
+use std::{collections::HashMap, io::BufRead};
+
+use crate::WitnessError;
+
+/// Maps every signal that survived compilation to its witness index, from a circom `.sym`
+/// file (`label,witness_index,component,name` per line, index -1 for removed signals).
+pub fn read_sym<R: BufRead>(reader: R) -> Result<HashMap<String, usize>, WitnessError> {
+    let mut signals = HashMap::new();
+    for line in reader.lines() {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        let mut columns = line.splitn(4, ',');
+        let (_label, index, _component, name) = match (columns.next(), columns.next(), columns.next(), columns.next()) {
+            (Some(label), Some(index), Some(component), Some(name)) => (label, index, component, name),
+            _ => return Err(WitnessError::InvalidSym(line)),
+        };
+        let index: i64 = index.parse().map_err(|_| WitnessError::InvalidSym(line.clone()))?;
+        if index >= 0 {
+            signals.insert(name.to_string(), index as usize);
+        }
+    }
+    Ok(signals)
+}
@@ -0,0 +1,227 @@
+//! Compares the native witness against circom's own wasm generator for `InsertLeaf` and
+//! `MerkleTreeUpdater`.
+//!
+//! Only `InsertLeaf`'s circom output is checked in. The `MerkleTreeUpdater` tests are ignored
+//! by default; compile it into `build/circuits` (see its README) and run them with
+//! `cargo test -- --ignored`.
+
+use std::collections::HashMap;
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use circom_witness::{WitnessCalculator, WitnessError};
+use light_poseidon::{Poseidon, PoseidonHasher};
+use num_bigint::{BigInt, BigUint};
+use wasmi::{core::Trap, Caller, Engine, Instance, Linker, Module, Store, WasmParams, WasmResults};
+
+const MAX_DEPTH: usize = 20;
+const WASM: &str = "../build/circuits/InsertLeaf_js/InsertLeaf.wasm";
+const R1CS: &str = "../build/circuits/InsertLeaf.r1cs";
+const SYM: &str = "../build/circuits/InsertLeaf.sym";
+const UPDATER_WASM: &str = "../build/circuits/MerkleTreeUpdater_js/MerkleTreeUpdater.wasm";
+const UPDATER_R1CS: &str = "../build/circuits/MerkleTreeUpdater.r1cs";
+const UPDATER_SYM: &str = "../build/circuits/MerkleTreeUpdater.sym";
+
+// Minimal runner for the circom 2 wasm witness generator, mirroring witness_calculator.js
+struct WasmWitness {
+    store: Store<()>,
+    instance: Instance,
+    n32: usize,
+}
+
+impl WasmWitness {
+    fn load(path: &str) -> Self {
+        let engine = Engine::default();
+        let module = Module::new(&engine, std::fs::read(path).unwrap().as_slice()).unwrap();
+        let mut store = Store::new(&engine, ());
+        let mut linker = <Linker<()>>::new(&engine);
+        linker
+            .func_wrap("runtime", "exceptionHandler", |_: Caller<'_, ()>, code: i32| -> Result<(), Trap> {
+                Err(Trap::new(format!("witness generator raised error {}", code)))
+            })
+            .unwrap();
+        for name in ["printErrorMessage", "writeBufferMessage", "showSharedRWMemory"] {
+            linker.func_wrap("runtime", name, |_: Caller<'_, ()>| {}).unwrap();
+        }
+        let instance = linker.instantiate(&mut store, &module).unwrap().start(&mut store).unwrap();
+
+        let mut runner = Self { store, instance, n32: 0 };
+        runner.n32 = runner.call::<(), i32>("getFieldNumLen32", ()).unwrap() as usize;
+        runner
+    }
+
+    fn call<P: WasmParams, R: WasmResults>(&mut self, name: &str, params: P) -> Result<R, wasmi::Error> {
+        let func = self.instance.get_typed_func::<P, R>(&self.store, name)?;
+        func.call(&mut self.store, params).map_err(Into::into)
+    }
+
+    fn calculate(&mut self, inputs: &HashMap<String, Vec<BigInt>>) -> Result<Vec<Fr>, wasmi::Error> {
+        self.call::<i32, ()>("init", 1)?;
+        for (name, values) in inputs {
+            let hash = fnv1a(name);
+            let (msb, lsb) = ((hash >> 32) as i32, hash as i32);
+            for (i, value) in values.iter().enumerate() {
+                let limbs = Fr::from(value.to_biguint().unwrap()).into_bigint().0;
+                for j in 0..self.n32 {
+                    let word = (limbs[j / 2] >> (32 * (j % 2))) as u32;
+                    self.call::<(i32, i32), ()>("writeSharedRWMemory", (j as i32, word as i32))?;
+                }
+                self.call::<(i32, i32, i32), ()>("setInputSignal", (msb, lsb, i as i32))?;
+            }
+        }
+
+        let size = self.call::<(), i32>("getWitnessSize", ())?;
+        let mut witness = Vec::with_capacity(size as usize);
+        for i in 0..size {
+            self.call::<i32, ()>("getWitness", i)?;
+            let mut bytes = Vec::with_capacity(self.n32 * 4);
+            for j in 0..self.n32 {
+                let word = self.call::<i32, i32>("readSharedRWMemory", j as i32)? as u32;
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+            witness.push(Fr::from_le_bytes_mod_order(&bytes));
+        }
+        Ok(witness)
+    }
+}
+
+fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn to_bigint(value: Fr) -> BigInt {
+    BigUint::from_bytes_le(&value.into_bigint().to_bytes_le()).into()
+}
+
+fn siblings(seed: u64) -> Vec<Fr> {
+    (0..MAX_DEPTH as u64).map(|i| Fr::from(seed * 1_000 + i)).collect()
+}
+
+// Root of `leaf` at `position` after `depth` levels, as `RawMerkleTree` computes it
+fn root(leaf: Fr, position: u64, depth: usize, siblings: &[Fr]) -> Fr {
+    let mut poseidon = Poseidon::<Fr>::new_circom(2).unwrap();
+    let mut node = leaf;
+    for (level, sibling) in siblings.iter().enumerate().take(depth) {
+        node = if (position >> level) & 1 == 1 {
+            poseidon.hash(&[*sibling, node]).unwrap()
+        } else {
+            poseidon.hash(&[node, *sibling]).unwrap()
+        };
+    }
+    node
+}
+
+// Valid InsertLeaf inputs: the root of `leaf` at `position` after `depth` levels
+fn insert_leaf_inputs(leaf: u64, position: u64, depth: usize, seed: u64) -> HashMap<String, Vec<BigInt>> {
+    let siblings = siblings(seed);
+    let mut inputs = HashMap::new();
+    inputs.insert("newRoot".to_string(), vec![to_bigint(root(Fr::from(leaf), position, depth, &siblings))]);
+    inputs.insert("newLeaf".to_string(), vec![BigInt::from(leaf)]);
+    inputs.insert("pathIndices".to_string(), vec![BigInt::from(position)]);
+    inputs.insert("depth".to_string(), vec![BigInt::from(depth)]);
+    inputs.insert("pathElements".to_string(), siblings.into_iter().map(to_bigint).collect());
+    inputs
+}
+
+// Valid MerkleTreeUpdater inputs: `position` goes from zero to `leaf` under the same siblings
+fn updater_inputs(leaf: u64, position: u64, depth: usize, seed: u64) -> HashMap<String, Vec<BigInt>> {
+    let siblings = siblings(seed);
+    let mut inputs = HashMap::new();
+    inputs.insert("current_root".to_string(), vec![to_bigint(root(Fr::from(0u64), position, depth, &siblings))]);
+    inputs.insert("new_root".to_string(), vec![to_bigint(root(Fr::from(leaf), position, depth, &siblings))]);
+    inputs.insert("new_leaf".to_string(), vec![BigInt::from(leaf)]);
+    inputs.insert("pathIndices".to_string(), vec![BigInt::from(position)]);
+    inputs.insert("depth".to_string(), vec![BigInt::from(depth)]);
+    inputs.insert("pathElements".to_string(), siblings.into_iter().map(to_bigint).collect());
+    inputs
+}
+
+#[test]
+fn native_witness_matches_wasm() {
+    let mut wasm = WasmWitness::load(WASM);
+    let native = WitnessCalculator::merkle_circuit(R1CS, SYM, MAX_DEPTH).unwrap();
+
+    let cases = [
+        (7, 0, 0, 1),
+        (42, 0, 1, 2),
+        (42, 1, 1, 3),
+        (123_456, 5, 3, 4),
+        (987_654_321, 0b1010_1100, 8, 5),
+        (1, (1 << 19) + 3, MAX_DEPTH, 6),
+    ];
+    for (leaf, position, depth, seed) in cases {
+        let inputs = insert_leaf_inputs(leaf, position, depth, seed);
+        let expected = wasm.calculate(&inputs).unwrap();
+        let witness = native.calculate_witness(&inputs).unwrap();
+        assert_eq!(witness.len(), native.n_wires());
+        assert_eq!(witness, expected, "leaf {} at {} with depth {}", leaf, position, depth);
+    }
+}
+
+#[test]
+fn rejects_inconsistent_root() {
+    let mut wasm = WasmWitness::load(WASM);
+    let native = WitnessCalculator::merkle_circuit(R1CS, SYM, MAX_DEPTH).unwrap();
+
+    let mut inputs = insert_leaf_inputs(42, 3, 2, 7);
+    inputs.insert("newRoot".to_string(), vec![BigInt::from(1)]);
+    assert!(wasm.calculate(&inputs).is_err());
+    assert!(matches!(native.calculate_witness(&inputs), Err(WitnessError::ConstraintFailed(_))));
+}
+
+#[test]
+fn rejects_unknown_and_misshaped_inputs() {
+    let native = WitnessCalculator::merkle_circuit(R1CS, SYM, MAX_DEPTH).unwrap();
+
+    let mut inputs = insert_leaf_inputs(42, 3, 2, 7);
+    inputs.insert("oldRoot".to_string(), vec![BigInt::from(1)]);
+    assert!(matches!(native.calculate_witness(&inputs), Err(WitnessError::UnknownSignal(_))));
+
+    let mut inputs = insert_leaf_inputs(42, 3, 2, 7);
+    inputs.insert("pathElements".to_string(), vec![BigInt::from(1)]);
+    assert!(matches!(
+        native.calculate_witness(&inputs),
+        Err(WitnessError::InputLength(_, MAX_DEPTH, 1))
+    ));
+}
+
+#[test]
+#[ignore = "needs MerkleTreeUpdater compiled into build/circuits"]
+fn updater_witness_matches_wasm() {
+    let mut wasm = WasmWitness::load(UPDATER_WASM);
+    let native = WitnessCalculator::merkle_circuit(UPDATER_R1CS, UPDATER_SYM, MAX_DEPTH).unwrap();
+
+    // Every sibling is non-zero, and the positions set bits across the whole path so the index
+    // bits and every level's depth check are worked out from real values
+    let cases = [
+        (7, 0, MAX_DEPTH, 1),
+        (42, 1, MAX_DEPTH, 2),
+        (123_456, 0b1010_1100_0011_0101_1001, MAX_DEPTH, 3),
+        (987_654_321, (1 << MAX_DEPTH) - 1, MAX_DEPTH, 4),
+        (1, (1 << 19) + 3, MAX_DEPTH, 5),
+        (5, 0b110, 3, 6),
+    ];
+    for (leaf, position, depth, seed) in cases {
+        let inputs = updater_inputs(leaf, position, depth, seed);
+        let expected = wasm.calculate(&inputs).unwrap();
+        let witness = native.calculate_witness(&inputs).unwrap();
+        assert_eq!(witness.len(), native.n_wires());
+        assert_eq!(witness, expected, "leaf {} at {} with depth {}", leaf, position, depth);
+    }
+}
+
+#[test]
+#[ignore = "needs MerkleTreeUpdater compiled into build/circuits"]
+fn updater_rejects_a_filled_slot() {
+    let mut wasm = WasmWitness::load(UPDATER_WASM);
+    let native = WitnessCalculator::merkle_circuit(UPDATER_R1CS, UPDATER_SYM, MAX_DEPTH).unwrap();
+
+    // The slot held a leaf before, so the path does not lead to `current_root` from zero
+    let mut inputs = updater_inputs(42, 0b1011, MAX_DEPTH, 7);
+    let filled = root(Fr::from(41u64), 0b1011, MAX_DEPTH, &siblings(7));
+    inputs.insert("current_root".to_string(), vec![to_bigint(filled)]);
+    assert!(wasm.calculate(&inputs).is_err());
+    assert!(matches!(native.calculate_witness(&inputs), Err(WitnessError::ConstraintFailed(_))));
+}
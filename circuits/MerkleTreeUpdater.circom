@@ -1,35 +1,40 @@
+pragma circom  2.2.1;
 include "./MerkleTree.circom";
+include "../node_modules/circomlib/circuits/bitify.circom";
 
 // inserts a leaf into a tree
 // checks that tree previously contained zero in the same position
-template MerkleTreeUpdater(depth, zeroLeaf) {
+template MerkleTreeUpdater(MAX_DEPTH) {
     signal input current_root;
     signal input new_root;
     signal input new_leaf;
-    signal input pathIndices[depth];
-    signal input pathElements[depth];
+    signal input pathIndices;
+    signal input depth;
+    signal input pathElements[MAX_DEPTH];
 
     // Compute indexBits once for both trees
     // Since Num2Bits is non deterministic, 2 duplicate calls to it cannot be
     // optimized by circom compiler
-    component indexBits = Num2Bits(depth);
+    component indexBits = Num2Bits(MAX_DEPTH);
     indexBits.in <== pathIndices;
 
-    component treeBefore = RawMerkleTree(depth);
-    for(var i = 0; i < depth; i++) {
-        treeBefore.pathIndices[i] <== indexBits.out[i];
-        treeBefore.pathElements[i] <== pathElements[i];
+    component treeBefore = RawMerkleTree(MAX_DEPTH);
+    treeBefore.depth <== depth;
+    for(var i = 0; i < MAX_DEPTH; i++) {
+        treeBefore.indices[i] <== indexBits.out[i];
+        treeBefore.siblings[i] <== pathElements[i];
     }
-    treeBefore.leaf <== zeroLeaf;
-    treeBefore.root === current_root;
+    treeBefore.leaf <== 0;
+    treeBefore.out === current_root;
 
-    component treeAfter = RawMerkleTree(depth);
-    for(var i = 0; i < depth; i++) {
-        treeAfter.pathIndices[i] <== indexBits.out[i];
-        treeAfter.pathElements[i] <== pathElements[i];
+    component treeAfter = RawMerkleTree(MAX_DEPTH);
+    treeAfter.depth <== depth;
+    for(var i = 0; i < MAX_DEPTH; i++) {
+        treeAfter.indices[i] <== indexBits.out[i];
+        treeAfter.siblings[i] <== pathElements[i];
     }
     treeAfter.leaf <== new_leaf;
-    treeAfter.root === new_root;
+    treeAfter.out === new_root;
 }
 
-component main(public [current_root, new_leaf, new_root]) = MerkleTreeUpdater(depth, zeroLeaf);
\ No newline at end of file
+component main {public [current_root, new_leaf, new_root]} = MerkleTreeUpdater(20);
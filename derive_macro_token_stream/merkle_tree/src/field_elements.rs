@@ -0,0 +1,149 @@
+//! Canonical field element encodings for values committed into leaves.
+//!
+//! Every encoding is injective and self-delimiting: a decoder reading elements from the
+//! front always knows where a value ends. That makes concatenation injective too, which is
+//! what `#[derive(ToFieldElements)]` does for structs, field by field in declaration order.
+//!
+//! | Type            | Elements                                                  |
+//! |-----------------|-----------------------------------------------------------|
+//! | `Fr`            | the element itself                                        |
+//! | `u8`, `u32`, `u64`, `bool` | the value                                      |
+//! | `i32`, `i64`    | the value, negatives as their field negation              |
+//! | `String`        | byte length, then 31-byte big-endian chunks               |
+//! | `NaiveDate`     | days since 0001-01-01 (CE day 1), signed                  |
+//! | `NaiveDateTime` | UTC unix seconds (signed), then nanoseconds               |
+//! | `Option<T>`     | `0` for `None`, `1` followed by `T` for `Some`            |
+//! | `Vec<T>`        | length, then each item                                    |
+
+use ark_ff::PrimeField;
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use merkle_tree_storage::{poseidon::Poseidon, Fr};
+
+// Bytes packed into one element, small enough to stay below the field modulus
+const CHUNK_BYTES: usize = 31;
+const NONE_TAG: u64 = 0;
+const SOME_TAG: u64 = 1;
+
+/// Turns a value into the field elements its leaf hash is computed over.
+/// Derive it for structs with `#[derive(ToFieldElements)]`.
+pub trait ToFieldElements {
+    fn to_field_elements(&self) -> Vec<Fr> {
+        let mut elements = Vec::new();
+        self.append_field_elements(&mut elements);
+        elements
+    }
+
+    /// Appends this value's encoding to `elements`.
+    fn append_field_elements(&self, elements: &mut Vec<Fr>);
+}
+
+/// Poseidon(2) chain over `elements`, seeded with their count.
+pub fn hash_field_elements(elements: &[Fr]) -> Fr {
+    // Two inputs is always supported
+    let poseidon = Poseidon::new(2).unwrap();
+    elements
+        .iter()
+        .fold(fr_from_u64(elements.len() as u64), |acc, element| {
+            poseidon.hash(&[acc, *element]).unwrap()
+        })
+}
+
+pub(crate) fn fr_from_u64(value: u64) -> Fr {
+    Fr::from(value)
+}
+
+fn fr_from_i64(value: i64) -> Fr {
+    let element = fr_from_u64(value.unsigned_abs());
+    if value < 0 {
+        -element
+    } else {
+        element
+    }
+}
+
+// Big-endian bytes, at most CHUNK_BYTES of them, so never reduced
+fn fr_from_be_bytes(bytes: &[u8]) -> Fr {
+    Fr::from_be_bytes_mod_order(bytes)
+}
+
+impl ToFieldElements for Fr {
+    fn append_field_elements(&self, elements: &mut Vec<Fr>) {
+        elements.push(*self);
+    }
+}
+
+impl ToFieldElements for String {
+    fn append_field_elements(&self, elements: &mut Vec<Fr>) {
+        self.as_str().append_field_elements(elements);
+    }
+}
+
+impl ToFieldElements for str {
+    fn append_field_elements(&self, elements: &mut Vec<Fr>) {
+        elements.push(fr_from_u64(self.len() as u64));
+        elements.extend(self.as_bytes().chunks(CHUNK_BYTES).map(fr_from_be_bytes));
+    }
+}
+
+macro_rules! unsigned_to_field_elements {
+    ($($ty:ty),*) => {
+        $(impl ToFieldElements for $ty {
+            fn append_field_elements(&self, elements: &mut Vec<Fr>) {
+                elements.push(fr_from_u64(u64::from(*self)));
+            }
+        })*
+    };
+}
+
+macro_rules! signed_to_field_elements {
+    ($($ty:ty),*) => {
+        $(impl ToFieldElements for $ty {
+            fn append_field_elements(&self, elements: &mut Vec<Fr>) {
+                elements.push(fr_from_i64(i64::from(*self)));
+            }
+        })*
+    };
+}
+
+unsigned_to_field_elements!(bool, u8, u32, u64);
+signed_to_field_elements!(i32, i64);
+
+impl ToFieldElements for NaiveDate {
+    fn append_field_elements(&self, elements: &mut Vec<Fr>) {
+        elements.push(fr_from_i64(self.num_days_from_ce() as i64));
+    }
+}
+
+impl ToFieldElements for NaiveDateTime {
+    fn append_field_elements(&self, elements: &mut Vec<Fr>) {
+        elements.push(fr_from_i64(self.and_utc().timestamp()));
+        elements.push(fr_from_u64(self.nanosecond() as u64));
+    }
+}
+
+impl<T: ToFieldElements> ToFieldElements for Option<T> {
+    fn append_field_elements(&self, elements: &mut Vec<Fr>) {
+        match self {
+            Some(value) => {
+                elements.push(fr_from_u64(SOME_TAG));
+                value.append_field_elements(elements);
+            }
+            None => elements.push(fr_from_u64(NONE_TAG)),
+        }
+    }
+}
+
+impl<T: ToFieldElements> ToFieldElements for Vec<T> {
+    fn append_field_elements(&self, elements: &mut Vec<Fr>) {
+        elements.push(fr_from_u64(self.len() as u64));
+        for item in self {
+            item.append_field_elements(elements);
+        }
+    }
+}
+
+impl<T: ToFieldElements + ?Sized> ToFieldElements for &T {
+    fn append_field_elements(&self, elements: &mut Vec<Fr>) {
+        (**self).append_field_elements(elements);
+    }
+}
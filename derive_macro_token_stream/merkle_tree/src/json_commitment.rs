@@ -0,0 +1,272 @@
+//! Poseidon vector commitments to JSON values, used for `MerchantRecord::data_record`.
+//!
+//! Every object and array is its own zero-padded Poseidon Merkle tree, the kind
+//! `RawMerkleTree` proves membership in, so a single field can be disclosed or proven in a
+//! circuit without revealing its siblings.
+//!
+//! | Value       | Digest                                                          |
+//! |-------------|-----------------------------------------------------------------|
+//! | `null`      | `H(0)`                                                          |
+//! | bool        | `H(1, b)`                                                       |
+//! | integer     | `H(2, n)`, negatives as their field negation                    |
+//! | float       | `H(3, bits)`, the IEEE 754 bits of the `f64`                    |
+//! | string      | `H(4, s)`, `s` encoded as in `ToFieldElements`                  |
+//! | array       | `H(5, len, root)`, leaf `i` is `H(i, digest)`                   |
+//! | object      | `H(6, len, root)`, one leaf `H(key, digest)` per key, sorted    |
+//!
+//! `H` is `hash_field_elements`. A tree holding `len` leaves has depth `ceil(log2(len))`,
+//! at least one. The commitment's root is the digest of the whole value.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use merkle_tree_storage::{Fr, MerkleProof, MerkleTreeStorage, PoseidonHasher};
+use serde_json::{Number, Value};
+
+use crate::field_elements::{fr_from_u64, hash_field_elements, ToFieldElements};
+
+const NULL_TAG: u64 = 0;
+const BOOL_TAG: u64 = 1;
+const INTEGER_TAG: u64 = 2;
+const FLOAT_TAG: u64 = 3;
+const STRING_TAG: u64 = 4;
+const ARRAY_TAG: u64 = 5;
+const OBJECT_TAG: u64 = 6;
+
+/// One step of a path: an object key or an array index.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PathKey {
+    Key(String),
+    Index(usize),
+}
+
+impl PathKey {
+    fn to_field_elements(&self) -> Vec<Fr> {
+        match self {
+            PathKey::Key(key) => key.to_field_elements(),
+            PathKey::Index(index) => vec![fr_from_u64(*index as u64)],
+        }
+    }
+}
+
+/// A committed JSON value, kept so openings can be produced for any path in it.
+pub struct JsonCommitment {
+    value: Value,
+    node: Node,
+}
+
+enum Node {
+    Scalar(Fr),
+    Container {
+        tree: Box<MerkleTreeStorage>,
+        // Leaf and subtree of each child, in leaf order
+        children: BTreeMap<PathKey, (Fr, Node)>,
+        digest: Fr,
+    },
+}
+
+/// Opening of one path against a commitment root.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonOpening {
+    pub path: Vec<PathKey>,
+    /// The disclosed value, whole if it is an object or array.
+    pub value: Value,
+    /// One level per step of `path`, innermost first.
+    pub levels: Vec<OpeningLevel>,
+}
+
+/// Membership of one child in the tree of its parent object or array.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OpeningLevel {
+    pub key: PathKey,
+    /// Number of children of the parent.
+    pub len: usize,
+    /// `leaf` is `H(key, digest of the child)` and `root` the parent's tree root.
+    pub proof: MerkleProof<Fr>,
+}
+
+/// Commits to `value`.
+pub fn commit_json(value: &Value) -> Result<JsonCommitment> {
+    Ok(JsonCommitment {
+        value: value.clone(),
+        node: Node::build(value)?,
+    })
+}
+
+/// Digest of `value` alone, the root of its commitment.
+pub fn json_digest(value: &Value) -> Result<Fr> {
+    Ok(Node::build(value)?.digest())
+}
+
+impl JsonCommitment {
+    pub fn root(&self) -> Fr {
+        self.node.digest()
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// Opens the value at `pointer`, a JSON pointer (RFC 6901) such as `/owner/phones/0`.
+    /// The empty pointer opens the whole value, with no levels.
+    pub fn open(&self, pointer: &str) -> Result<JsonOpening> {
+        let mut value = &self.value;
+        let mut node = &self.node;
+        let mut path = Vec::new();
+        let mut levels = Vec::new();
+        for token in parse_pointer(pointer)? {
+            let key = match value {
+                Value::Object(map) => {
+                    let child = map.get(&token).ok_or_else(|| anyhow!("No key {:?} at {}", token, pointer))?;
+                    value = child;
+                    PathKey::Key(token)
+                }
+                Value::Array(items) => {
+                    let index: usize = token.parse().map_err(|_| anyhow!("{:?} is not an array index", token))?;
+                    value = items.get(index).ok_or_else(|| anyhow!("No index {} at {}", index, pointer))?;
+                    PathKey::Index(index)
+                }
+                _ => return Err(anyhow!("{} goes through a scalar", pointer)),
+            };
+            let Node::Container { tree, children, .. } = node else {
+                unreachable!("containers have container nodes");
+            };
+            let (leaf, child) = &children[&key];
+            let proof = tree.generate_merkle_proof(*leaf).expect("every child has a leaf");
+            levels.push(OpeningLevel {
+                key: key.clone(),
+                len: children.len(),
+                proof,
+            });
+            path.push(key);
+            node = child;
+        }
+        levels.reverse();
+        Ok(JsonOpening {
+            path,
+            value: value.clone(),
+            levels,
+        })
+    }
+}
+
+impl JsonOpening {
+    /// Checks the opening against a commitment `root`.
+    pub fn verify(&self, root: Fr) -> bool {
+        if self.levels.len() != self.path.len() {
+            return false;
+        }
+        let Ok(mut digest) = json_digest(&self.value) else {
+            return false;
+        };
+        let hasher = PoseidonHasher::default();
+        for (level, key) in self.levels.iter().zip(self.path.iter().rev()) {
+            let mut leaf_elements = key.to_field_elements();
+            leaf_elements.push(digest);
+            let tag = match key {
+                PathKey::Key(_) => OBJECT_TAG,
+                PathKey::Index(_) => ARRAY_TAG,
+            };
+            if level.key != *key
+                || level.proof.leaf != hash_field_elements(&leaf_elements)
+                || level.proof.depth() != tree_depth(level.len) as usize
+                || !level.proof.verify(&hasher)
+            {
+                return false;
+            }
+            digest = container_digest(tag, level.len, level.proof.root);
+        }
+        digest == root
+    }
+}
+
+impl Node {
+    fn build(value: &Value) -> Result<Self> {
+        let entries: Vec<(PathKey, &Value)> = match value {
+            Value::Array(items) => items.iter().enumerate().map(|(i, item)| (PathKey::Index(i), item)).collect(),
+            Value::Object(map) => {
+                let mut entries: Vec<_> = map.iter().map(|(key, item)| (PathKey::Key(key.clone()), item)).collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                entries
+            }
+            scalar => return Ok(Node::Scalar(scalar_digest(scalar)?)),
+        };
+        let tag = if value.is_array() { ARRAY_TAG } else { OBJECT_TAG };
+
+        let mut tree = MerkleTreeStorage::new(tree_depth(entries.len()));
+        let mut children = BTreeMap::new();
+        for (key, item) in entries {
+            let child = Node::build(item)?;
+            let mut leaf_elements = key.to_field_elements();
+            leaf_elements.push(child.digest());
+            let leaf = hash_field_elements(&leaf_elements);
+            tree.insert_leaf(leaf)?;
+            children.insert(key, (leaf, child));
+        }
+        let digest = container_digest(tag, children.len(), tree.root());
+        Ok(Node::Container {
+            tree: Box::new(tree),
+            children,
+            digest,
+        })
+    }
+
+    fn digest(&self) -> Fr {
+        match self {
+            Node::Scalar(digest) | Node::Container { digest, .. } => *digest,
+        }
+    }
+}
+
+fn scalar_digest(value: &Value) -> Result<Fr> {
+    let mut elements = Vec::new();
+    match value {
+        Value::Null => elements.push(fr_from_u64(NULL_TAG)),
+        Value::Bool(b) => {
+            elements.push(fr_from_u64(BOOL_TAG));
+            b.append_field_elements(&mut elements);
+        }
+        Value::Number(number) => append_number(number, &mut elements)?,
+        Value::String(s) => {
+            elements.push(fr_from_u64(STRING_TAG));
+            s.append_field_elements(&mut elements);
+        }
+        Value::Array(_) | Value::Object(_) => unreachable!("containers are hashed as trees"),
+    }
+    Ok(hash_field_elements(&elements))
+}
+
+// Integers compare by value whether serde read them as u64 or i64
+fn append_number(number: &Number, elements: &mut Vec<Fr>) -> Result<()> {
+    if let Some(n) = number.as_u64() {
+        elements.push(fr_from_u64(INTEGER_TAG));
+        n.append_field_elements(elements);
+    } else if let Some(n) = number.as_i64() {
+        elements.push(fr_from_u64(INTEGER_TAG));
+        n.append_field_elements(elements);
+    } else {
+        let float = number.as_f64().ok_or_else(|| anyhow!("{} is not a JSON number", number))?;
+        elements.push(fr_from_u64(FLOAT_TAG));
+        float.to_bits().append_field_elements(elements);
+    }
+    Ok(())
+}
+
+fn container_digest(tag: u64, len: usize, root: Fr) -> Fr {
+    hash_field_elements(&[fr_from_u64(tag), fr_from_u64(len as u64), root])
+}
+
+fn tree_depth(len: usize) -> u32 {
+    len.next_power_of_two().trailing_zeros().max(1)
+}
+
+// RFC 6901: `/`-separated tokens, `~1` for `/` and `~0` for `~`
+fn parse_pointer(pointer: &str) -> Result<Vec<String>> {
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let rest = pointer
+        .strip_prefix('/')
+        .ok_or_else(|| anyhow!("JSON pointer {:?} does not start with /", pointer))?;
+    Ok(rest.split('/').map(|token| token.replace("~1", "/").replace("~0", "~")).collect())
+}
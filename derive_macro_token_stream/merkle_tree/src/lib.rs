@@ -1,85 +1,105 @@
-use std::collections::{HashMap, BTreeMap};
-use chrono::{DateTime, Date};
-use anyhow::Result; // For handling errors
-use sqlx::PgPool;   // For database connection
-use async_trait::async_trait; // For async functions with traits
-
-// use rand::rngs::StdRng;
-// use rand::SeedableRng;
-// use ff::Field;
-use ff::*;
-
-use poseidon_rs::{Fr, Poseidon};
-// mod merkle_tree;
-// mod merkle_tree_storage;
-// use crate::merkle_tree::MerkleTree;
-// use merkle_tree_storage::MerkleTreeStorage;
-use tree_proc_macros::MerkleTree;
-mod merkle_tree;
-use merkle_tree::MerkleTree;
+// Lets the `::merkle_tree::` paths emitted by `#[derive(MerkleTree)]` resolve in this crate too
+extern crate self as merkle_tree;
+
+use std::collections::BTreeMap;
+use chrono::{NaiveDate, NaiveDateTime};
+use serde_json::Value;
+
+pub use merkle_tree_storage::Fr;
+pub use tree_proc_macros::{MerkleTree, ToFieldElements};
+mod field_elements;
+mod json_commitment;
+pub mod on_chain;
+mod tree;
+pub use field_elements::{hash_field_elements, ToFieldElements};
+pub use json_commitment::{commit_json, json_digest, JsonCommitment, JsonOpening, OpeningLevel, PathKey};
+pub use on_chain::RootSlot;
+pub use tree::MerkleTree;
 use merkle_tree_storage::MerkleTreeStorage;
 
+// Used by the code `#[derive(MerkleTree)]` generates
+#[doc(hidden)]
+pub mod __private {
+    pub use anyhow;
+    pub use async_trait::async_trait;
+    pub use merkle_tree_storage;
+    pub use sqlx::PgPool;
+}
+
 
-type PoseidonHash = Fr;
+pub type PoseidonHash = Fr;
 
 
-#[derive(MerkleTree, sqlx::FromRow)]
-#[tree(depth = 32, storage = "CoreIdTree")]
+#[derive(MerkleTree)]
+#[tree(storage = "CoreIdTree", program = "9guwSzLJSkomxdbTM6TfKTF3KYSDxLNeSsCRdPaBGVpU", root_slot = "ddid_root")]
 pub struct CoreId {
 	#[tree_arg]
-	embedding_hash: String,
-	merchants: Vec<MerchantJoinId>,
-	records: Vec<MerchantRecord>,
+	pub embedding_hash: String,
+	pub merchants: Vec<MerchantJoinId>,
+	pub records: Vec<MerchantRecord>,
 	#[tree_arg]
-	name: String,
+	pub name: String,
 	#[tree_arg]
-	breed: String,
+	pub breed: String,
 	#[tree_arg]
-	date_of_birth: Date,
+	pub date_of_birth: NaiveDate,
 	#[tree_arg]
-	proof_level: u8,
+	pub proof_level: u8,
 	#[tree_arg]
-	microchip_id: String,
+	pub microchip_id: String,
 }
 
-#[derive(MerkleTree, sqlx::FromRow)]
-#[tree(depth = 32, storage = "MerchantJoinTree")]
+#[derive(MerkleTree)]
+#[tree(storage = "MerchantJoinTree", program = "9guwSzLJSkomxdbTM6TfKTF3KYSDxLNeSsCRdPaBGVpU", root_slot = "merchant_root")]
 pub struct MerchantJoinId {
 	#[tree_arg]
-	merchant_id: i32,
+	pub merchant_id: i32,
 	#[tree_arg]
-	embedding_hash: String,
-	write_fields: Vec<String>,
-	read_merchant_fields: Vec<BTreeMap<u32, Vec<String>>>, // can read fields of other merchants
+	pub embedding_hash: String,
+	pub write_fields: Vec<String>,
+	pub read_merchant_fields: Vec<BTreeMap<u32, Vec<String>>>, // can read fields of other merchants
 	#[tree_arg]
-	last_updated: DateTime,
+	pub last_updated: NaiveDateTime,
 	#[tree_arg]
-	latest_data_hash: PoseidonHash
+	pub latest_data_hash: PoseidonHash
 }
 
-#[derive(MerkleTree, sqlx::FromRow)]
-#[tree(depth = 32, storage = "MerchantRecordTree")]
+#[derive(MerkleTree)]
+#[tree(storage = "MerchantRecordTree", program = "9guwSzLJSkomxdbTM6TfKTF3KYSDxLNeSsCRdPaBGVpU", root_slot = "merchant_record_root")]
 pub struct MerchantRecord {
 	#[tree_arg]
-	embedding_hash: String,
+	pub embedding_hash: String,
 	#[tree_arg]
-	merchant_id: u32,
+	pub merchant_id: u32,
 	#[tree_arg]
-	date_issued: DateTime,
+	pub date_issued: NaiveDateTime,
 	#[tree_arg]
-	valid_until: Optional<DateTime>,
+	pub valid_until: Option<NaiveDateTime>,
 	#[tree_arg]
-	prev_data_hash: PoseidonHash, // should match latest_data_hash pre-update
-	data_record: Value,
+	pub prev_data_hash: PoseidonHash, // should match latest_data_hash pre-update
+	pub data_record: Value,
 	#[tree_arg]
-	data_hash: PoseidonHash, // should be the hash of prevDataHash & dataRecord
+	pub data_hash: PoseidonHash, // see `MerchantRecord::derive_data_hash`
+}
+
+impl MerchantRecord {
+	/// `data_hash` of a record: `H(prev_data_hash, root)` with `root` the commitment to
+	/// `data_record`, so each of its fields can be opened on its own.
+	pub fn derive_data_hash(prev_data_hash: PoseidonHash, data_record: &Value) -> anyhow::Result<PoseidonHash> {
+		Ok(hash_field_elements(&[prev_data_hash, json_digest(data_record)?]))
+	}
+
+	/// Whether `data_hash` commits to `prev_data_hash` and `data_record`.
+	pub fn has_valid_data_hash(&self) -> bool {
+		Self::derive_data_hash(self.prev_data_hash, &self.data_record).is_ok_and(|hash| hash == self.data_hash)
+	}
 }
 
 #[derive(sqlx::FromRow)]
 pub struct MerchantData {
-	merchant_id: i32,
-	schema: Value,
-	readable_fields: Vec<String>,
+	pub merchant_id: i32,
+	pub schema: Value,
+	pub readable_fields: Vec<String>,
 }
 
 // pub struct MerkleTreeStorage {
@@ -87,9 +107,19 @@ pub struct MerchantData {
 // 	capacity: usize, // 2^depth maximum leaves
 // }
 
-#[derive(sqlx::FromRow)]
-pub struct CoreIdTree(MerkleTreeStorage);
-#[derive(sqlx::FromRow)]
-pub struct MerchantJoinTree(MerkleTreeStorage);
-#[derive(sqlx::FromRow)]
-pub struct MerchantRecordTree(MerkleTreeStorage);
+pub struct CoreIdTree(pub MerkleTreeStorage);
+pub struct MerchantJoinTree(pub MerkleTreeStorage);
+pub struct MerchantRecordTree(pub MerkleTreeStorage);
+
+macro_rules! tree_storage_from {
+    ($($tree:ident),*) => {
+        $(impl From<MerkleTreeStorage> for $tree {
+            fn from(storage: MerkleTreeStorage) -> Self {
+                Self(storage)
+            }
+        })*
+    };
+}
+
+tree_storage_from!(CoreIdTree, MerchantJoinTree, MerchantRecordTree);
+
@@ -0,0 +1,95 @@
+//! Reads the tree roots the backend anchors in the root program's `HashAccount` PDA.
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::{BorshDeserialize, BorshSerialize};
+use merkle_tree_storage::{fr_from_bytes, Fr};
+use serde_json::{json, Value};
+use solana_keypair::read_keypair_file;
+use solana_pubkey::Pubkey;
+use solana_signer::Signer;
+
+const DEFAULT_RPC_URL: &str = "https://api.devnet.solana.com";
+const DEFAULT_KEYPAIR_PATH: &str = "src/wallet-keypair.json";
+const ROOT_SEED: &[u8] = b"root_hashes";
+
+/// Account data of the root program's PDA, as `merkle_root_hash_solana_program` writes it.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct HashAccount {
+    pub ddid_root: [u8; 32],
+    pub merchant_root: [u8; 32],
+    pub merchant_record_root: [u8; 32],
+}
+
+/// Field of `HashAccount` a tree's root is anchored in, set with `#[tree(root_slot = "...")]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RootSlot {
+    DdidRoot,
+    MerchantRoot,
+    MerchantRecordRoot,
+}
+
+impl HashAccount {
+    pub fn root(&self, slot: RootSlot) -> [u8; 32] {
+        match slot {
+            RootSlot::DdidRoot => self.ddid_root,
+            RootSlot::MerchantRoot => self.merchant_root,
+            RootSlot::MerchantRecordRoot => self.merchant_record_root,
+        }
+    }
+}
+
+/// PDA holding the roots, seeded with the authority that writes them.
+pub fn hash_account_address(program_id: &Pubkey, authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[authority.as_ref(), ROOT_SEED], program_id).0
+}
+
+/// Fetches and decodes the `HashAccount` of `program_id` and returns the root in `slot`.
+///
+/// The RPC endpoint and the authority keypair come from `SOLANA_RPC_URL` and
+/// `SOLANA_KEYPAIR_PATH`, defaulting to devnet and `src/wallet-keypair.json` like the backend.
+pub async fn read_root(program_id: [u8; 32], slot: RootSlot) -> Result<Fr> {
+    let rpc_url = std::env::var("SOLANA_RPC_URL").unwrap_or_else(|_| DEFAULT_RPC_URL.to_string());
+    let keypair_path = std::env::var("SOLANA_KEYPAIR_PATH").unwrap_or_else(|_| DEFAULT_KEYPAIR_PATH.to_string());
+    let authority = read_keypair_file(&keypair_path)
+        .map_err(|e| anyhow!("Failed to read keypair {}: {}", keypair_path, e))?;
+
+    let pda = hash_account_address(&Pubkey::new_from_array(program_id), &authority.pubkey());
+    let data = get_account_data(&rpc_url, &pda).await?;
+    let hash_account = HashAccount::deserialize(&mut data.as_slice())
+        .with_context(|| format!("PDA account {} is not a HashAccount", pda))?;
+    root_to_fr(&hash_account.root(slot))
+}
+
+// `getAccountInfo` at `confirmed` commitment. `solana-client` 1.x pins a `zeroize` that
+// cannot resolve next to sqlx 0.7, so the call is made over plain JSON-RPC.
+async fn get_account_data(rpc_url: &str, address: &Pubkey) -> Result<Vec<u8>> {
+    let request = json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "getAccountInfo",
+        "params": [address.to_string(), { "encoding": "base64", "commitment": "confirmed" }],
+    });
+    let response: Value = reqwest::Client::new()
+        .post(rpc_url)
+        .json(&request)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if let Some(error) = response.get("error") {
+        return Err(anyhow!("getAccountInfo failed: {}", error));
+    }
+    let data = response["result"]["value"]["data"][0]
+        .as_str()
+        .ok_or_else(|| anyhow!("PDA account {} does not exist", address))?;
+    STANDARD
+        .decode(data)
+        .with_context(|| format!("PDA account {} data is not base64", address))
+}
+
+/// Reads a root stored as 32 big-endian bytes, the order `Fr`'s hex form uses.
+pub fn root_to_fr(root: &[u8; 32]) -> Result<Fr> {
+    fr_from_bytes(root).ok_or_else(|| anyhow!("On-chain root is not a field element"))
+}
@@ -0,0 +1,39 @@
+use async_trait::async_trait;
+use merkle_tree_storage::MerkleTreeStorage;
+use merkle_tree_storage::Fr;
+use sqlx::PgPool;
+
+use crate::field_elements::hash_field_elements;
+
+/// Implemented with `#[derive(MerkleTree)]`, see `tree_proc_macros`.
+#[async_trait]
+pub trait MerkleTree {
+    /// Wrapper type of the tree this record is a leaf of.
+    type Storage: From<MerkleTreeStorage>;
+    const DEPTH: u32;
+    /// Name of `Storage`, which is also the table the tree is persisted in.
+    const STORAGE: &'static str;
+
+    /// One hash per `#[tree_arg]` field, in declaration order.
+    fn tree_args(&self) -> Vec<Fr>;
+
+    fn to_leaf_hash(&self) -> Fr {
+        hash_field_elements(&self.tree_args())
+    }
+
+    fn tree_storage() -> MerkleTreeStorage {
+        MerkleTreeStorage::new(Self::DEPTH)
+    }
+
+    /// Loads every leaf persisted in the `STORAGE` table and rebuilds the tree.
+    async fn load_tree(pool: &PgPool) -> anyhow::Result<Self::Storage>;
+
+    /// Appends this record's leaf to the `STORAGE` table and returns its position.
+    async fn persist_leaf(&self, pool: &PgPool) -> anyhow::Result<usize>;
+
+    /// Root anchored on chain for this tree, from the `HashAccount` field named by
+    /// `#[tree(program = "...", root_slot = "...")]`.
+    async fn read_on_chain_root() -> anyhow::Result<Fr> {
+        anyhow::bail!("{} has no on-chain root, set `program` and `root_slot` in #[tree(...)]", Self::STORAGE)
+    }
+}
@@ -0,0 +1,117 @@
+use chrono::NaiveDate;
+use merkle_tree::{hash_field_elements, Fr, MerkleTree, ToFieldElements};
+
+fn elements<T: ToFieldElements + ?Sized>(value: &T) -> Vec<Fr> {
+    value.to_field_elements()
+}
+
+#[test]
+fn strings_are_length_prefixed_and_chunked() {
+    assert_eq!(elements("").len(), 1);
+    assert_eq!(elements(&"a".repeat(31)).len(), 2);
+    assert_eq!(elements(&"a".repeat(32)).len(), 3);
+    assert_eq!(elements(&"a".repeat(62)).len(), 3);
+
+    // Leading zero bytes and splits across chunk boundaries stay distinct
+    assert_ne!(elements("a"), elements("\0a"));
+    assert_ne!(elements("a"), elements("a\0"));
+    assert_ne!(elements(&"b".repeat(31)), elements(&format!("{}\0", "b".repeat(31))));
+    assert_eq!(elements("owner"), elements(&"owner".to_string()));
+}
+
+#[test]
+fn integers_keep_their_sign() {
+    assert_ne!(elements(&-1i32), elements(&1i32));
+    assert_eq!(elements(&7i32), elements(&7u32));
+    assert_eq!(elements(&7u8), elements(&7u64));
+    assert_ne!(elements(&i32::MIN), elements(&(i32::MIN as i64 + 1)));
+    assert_eq!(elements(&true), elements(&1u8));
+}
+
+#[test]
+fn dates_are_exact() {
+    let date = NaiveDate::from_ymd_opt(2021, 3, 14).unwrap();
+    assert_ne!(elements(&date), elements(&date.succ_opt().unwrap()));
+    assert_ne!(elements(&date), elements(&NaiveDate::from_ymd_opt(-44, 3, 15).unwrap()));
+
+    let noon = date.and_hms_opt(12, 0, 0).unwrap();
+    assert_eq!(elements(&noon).len(), 2);
+    assert_ne!(elements(&noon), elements(&date.and_hms_milli_opt(12, 0, 0, 1).unwrap()));
+    assert_ne!(elements(&noon), elements(&date.and_hms_opt(12, 0, 1).unwrap()));
+}
+
+#[test]
+fn options_are_tagged() {
+    assert_ne!(elements(&None::<String>), elements(&Some(String::new())));
+    assert_ne!(elements(&None::<u32>), elements(&Some(0u32)));
+    assert_ne!(elements(&Some(None::<u32>)), elements(&None::<Option<u32>>));
+    assert_eq!(elements(&None::<u32>), elements(&0u32));
+    assert_eq!(elements(&Some(5u32))[1..], elements(&5u32)[..]);
+}
+
+#[test]
+fn vectors_are_length_prefixed() {
+    let split = vec!["ab".to_string(), "c".to_string()];
+    let joined = vec!["a".to_string(), "bc".to_string()];
+    assert_ne!(elements(&split), elements(&joined));
+    assert_ne!(elements(&vec![1u32]), elements(&vec![1u32, 0]));
+}
+
+#[derive(ToFieldElements)]
+struct Owner {
+    name: String,
+    phone: Option<String>,
+}
+
+#[derive(ToFieldElements)]
+struct Reading(u32, i32);
+
+#[derive(ToFieldElements)]
+struct Tagged<T> {
+    tag: u8,
+    value: T,
+}
+
+#[derive(MerkleTree)]
+#[tree(depth = 20, storage = "OwnedTree")]
+struct Owned {
+    #[tree_arg]
+    owner: Owner,
+    #[tree_arg]
+    readings: Vec<Reading>,
+}
+
+struct OwnedTree;
+
+impl From<merkle_tree_storage::MerkleTreeStorage> for OwnedTree {
+    fn from(_: merkle_tree_storage::MerkleTreeStorage) -> Self {
+        OwnedTree
+    }
+}
+
+#[test]
+fn derived_structs_concatenate_fields() {
+    let owner = Owner { name: "Kim".to_string(), phone: None };
+    let mut expected = elements("Kim");
+    expected.extend(elements(&None::<String>));
+    assert_eq!(elements(&owner), expected);
+
+    let mut expected = elements(&3u32);
+    expected.extend(elements(&-4i32));
+    assert_eq!(elements(&Reading(3, -4)), expected);
+
+    let tagged = Tagged { tag: 1, value: Reading(3, -4) };
+    assert_eq!(elements(&tagged)[1..], elements(&Reading(3, -4))[..]);
+}
+
+#[test]
+fn nested_structs_commit_into_leaves() {
+    let owned = |phone: Option<&str>| Owned {
+        owner: Owner { name: "Kim".to_string(), phone: phone.map(str::to_string) },
+        readings: vec![Reading(3, -4)],
+    };
+    let leaf = owned(None).to_leaf_hash();
+    assert!(leaf == owned(None).to_leaf_hash());
+    assert!(leaf != owned(Some("")).to_leaf_hash());
+    assert!(owned(None).tree_args()[0] == hash_field_elements(&elements(&owned(None).owner)));
+}
@@ -0,0 +1,112 @@
+use merkle_tree::{commit_json, hash_field_elements, json_digest, Fr, MerchantRecord, PathKey};
+use serde_json::{json, Value};
+
+fn record() -> Value {
+    json!({
+        "vaccine": "rabies",
+        "dose": 2,
+        "booster": null,
+        "vet": { "name": "Dr. Ortiz", "licensed": true, "clinic/branch": "north" },
+        "weights": [4.5, 4.75, -1],
+    })
+}
+
+#[test]
+fn key_order_does_not_matter() {
+    let reordered: Value = serde_json::from_str(
+        r#"{"weights":[4.5,4.75,-1],"vet":{"clinic/branch":"north","licensed":true,"name":"Dr. Ortiz"},"booster":null,"dose":2,"vaccine":"rabies"}"#,
+    )
+    .unwrap();
+    assert_eq!(json_digest(&record()).unwrap(), json_digest(&reordered).unwrap());
+}
+
+#[test]
+fn leaves_are_typed() {
+    let digests: Vec<Fr> = [json!(1), json!("1"), json!(1.0), json!(true), json!(null), json!([1]), json!({"0": 1})]
+        .iter()
+        .map(|value| json_digest(value).unwrap())
+        .collect();
+    for (i, a) in digests.iter().enumerate() {
+        assert!(digests[i + 1..].iter().all(|b| a != b), "digest {} is not unique", i);
+    }
+    // Integers are compared by value, however serde stored them
+    assert_eq!(json_digest(&json!(-0)).unwrap(), json_digest(&json!(0u64)).unwrap());
+    assert_ne!(json_digest(&json!([])).unwrap(), json_digest(&json!({})).unwrap());
+    assert_ne!(json_digest(&json!([1, 2])).unwrap(), json_digest(&json!([2, 1])).unwrap());
+}
+
+#[test]
+fn nested_changes_change_the_root() {
+    let root = json_digest(&record()).unwrap();
+    let mut changed = record();
+    changed["vet"]["licensed"] = json!(false);
+    assert_ne!(json_digest(&changed).unwrap(), root);
+
+    let mut extra = record();
+    extra["vet"]["phone"] = json!(null);
+    assert_ne!(json_digest(&extra).unwrap(), root);
+}
+
+#[test]
+fn fields_open_on_their_own() {
+    let commitment = commit_json(&record()).unwrap();
+    let root = commitment.root();
+    assert_eq!(root, json_digest(&record()).unwrap());
+
+    for (pointer, value, depth) in [
+        ("/vaccine", json!("rabies"), 1),
+        ("/vet/name", json!("Dr. Ortiz"), 2),
+        ("/vet/clinic~1branch", json!("north"), 2),
+        ("/weights/2", json!(-1), 2),
+        ("/vet", record()["vet"].clone(), 1),
+        ("", record(), 0),
+    ] {
+        let opening = commitment.open(pointer).unwrap();
+        assert_eq!(opening.value, value);
+        assert_eq!(opening.levels.len(), depth);
+        assert!(opening.verify(root), "{} does not verify", pointer);
+    }
+
+    let opening = commitment.open("/weights/1").unwrap();
+    assert_eq!(opening.path, vec![PathKey::Key("weights".to_string()), PathKey::Index(1)]);
+    // Five keys in a depth 3 tree, three weights in a depth 2 one
+    assert_eq!(opening.levels[0].proof.depth(), 2);
+    assert_eq!(opening.levels[1].proof.depth(), 3);
+}
+
+#[test]
+fn tampered_openings_fail() {
+    let commitment = commit_json(&record()).unwrap();
+    let root = commitment.root();
+    let opening = commitment.open("/vet/licensed").unwrap();
+
+    let mut lied = opening.clone();
+    lied.value = json!(false);
+    assert!(!lied.verify(root));
+
+    let mut moved = opening.clone();
+    moved.path[1] = PathKey::Key("name".to_string());
+    assert!(!moved.verify(root));
+
+    let mut truncated = opening.clone();
+    truncated.levels.pop();
+    assert!(!truncated.verify(root));
+
+    assert!(!opening.verify(json_digest(&json!({})).unwrap()));
+}
+
+#[test]
+fn bad_pointers_are_errors() {
+    let commitment = commit_json(&record()).unwrap();
+    for pointer in ["vaccine", "/missing", "/weights/3", "/weights/x", "/vaccine/0"] {
+        assert!(commitment.open(pointer).is_err(), "{} opened", pointer);
+    }
+}
+
+#[test]
+fn data_hash_commits_to_the_record() {
+    let prev = hash_field_elements(&[]);
+    let data_hash = MerchantRecord::derive_data_hash(prev, &record()).unwrap();
+    assert_eq!(data_hash, hash_field_elements(&[prev, commit_json(&record()).unwrap().root()]));
+    assert_ne!(data_hash, MerchantRecord::derive_data_hash(data_hash, &record()).unwrap());
+}
@@ -0,0 +1,37 @@
+use borsh::BorshDeserialize;
+use merkle_tree::on_chain::{hash_account_address, root_to_fr, HashAccount};
+use merkle_tree::{Fr, RootSlot};
+use solana_pubkey::Pubkey;
+use std::str::FromStr;
+
+#[test]
+fn hash_account_matches_program_layout() {
+    let mut data = Vec::new();
+    data.extend([1u8; 32]);
+    data.extend([2u8; 32]);
+    data.extend([3u8; 32]);
+    let account = HashAccount::try_from_slice(&data).unwrap();
+    assert_eq!(account.root(RootSlot::DdidRoot), [1u8; 32]);
+    assert_eq!(account.root(RootSlot::MerchantRoot), [2u8; 32]);
+    assert_eq!(account.root(RootSlot::MerchantRecordRoot), [3u8; 32]);
+    assert_eq!(borsh::to_vec(&account).unwrap(), data);
+}
+
+#[test]
+fn roots_are_big_endian_field_elements() {
+    let mut one = [0u8; 32];
+    one[31] = 1;
+    assert_eq!(root_to_fr(&one).unwrap(), Fr::from(1u64));
+    assert_eq!(root_to_fr(&[0u8; 32]).unwrap(), Fr::from(0u64));
+    // Above the BN254 scalar modulus
+    assert!(root_to_fr(&[0xff; 32]).is_err());
+}
+
+#[test]
+fn hash_account_address_is_seeded_with_the_authority() {
+    let program_id = Pubkey::from_str("9guwSzLJSkomxdbTM6TfKTF3KYSDxLNeSsCRdPaBGVpU").unwrap();
+    let authority = Pubkey::new_unique();
+    let (expected, _) = Pubkey::find_program_address(&[authority.as_ref(), b"root_hashes"], &program_id);
+    assert_eq!(hash_account_address(&program_id, &authority), expected);
+    assert_ne!(hash_account_address(&program_id, &Pubkey::new_unique()), expected);
+}
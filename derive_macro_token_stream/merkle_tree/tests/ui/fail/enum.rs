@@ -0,0 +1,9 @@
+use merkle_tree::MerkleTree;
+
+#[derive(MerkleTree)]
+#[tree(storage = "CoreIdTree")]
+pub enum CoreId {
+    Named { name: String },
+}
+
+fn main() {}
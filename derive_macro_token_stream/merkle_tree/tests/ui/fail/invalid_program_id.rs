@@ -0,0 +1,10 @@
+use merkle_tree::MerkleTree;
+
+#[derive(MerkleTree)]
+#[tree(storage = "CoreIdTree", program = "9guwSzLJSkomxdbTM6TfKTF3KYSDxLNeSsCRdPaBGV0l", root_slot = "ddid_root")]
+pub struct CoreId {
+    #[tree_arg]
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use merkle_tree::MerkleTree;
+
+#[derive(MerkleTree)]
+pub struct CoreId {
+    #[tree_arg]
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use merkle_tree::MerkleTree;
+
+#[derive(MerkleTree)]
+#[tree(storage = "CoreIdTree")]
+pub struct CoreId {
+    name: String,
+}
+
+fn main() {}
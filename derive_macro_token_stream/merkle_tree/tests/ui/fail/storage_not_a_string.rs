@@ -0,0 +1,10 @@
+use merkle_tree::MerkleTree;
+
+#[derive(MerkleTree)]
+#[tree(storage = 7)]
+pub struct CoreId {
+    #[tree_arg]
+    name: String,
+}
+
+fn main() {}
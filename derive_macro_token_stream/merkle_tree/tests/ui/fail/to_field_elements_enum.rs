@@ -0,0 +1,9 @@
+use merkle_tree::ToFieldElements;
+
+#[derive(ToFieldElements)]
+pub enum Species {
+    Dog,
+    Cat,
+}
+
+fn main() {}
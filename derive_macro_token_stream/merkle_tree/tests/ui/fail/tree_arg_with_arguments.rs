@@ -0,0 +1,10 @@
+use merkle_tree::MerkleTree;
+
+#[derive(MerkleTree)]
+#[tree(storage = "CoreIdTree")]
+pub struct CoreId {
+    #[tree_arg(skip)]
+    name: String,
+}
+
+fn main() {}
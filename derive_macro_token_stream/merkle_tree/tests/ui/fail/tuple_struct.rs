@@ -0,0 +1,7 @@
+use merkle_tree::MerkleTree;
+
+#[derive(MerkleTree)]
+#[tree(storage = "CoreIdTree")]
+pub struct CoreId(#[tree_arg] String);
+
+fn main() {}
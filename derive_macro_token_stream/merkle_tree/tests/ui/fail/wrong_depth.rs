@@ -0,0 +1,10 @@
+use merkle_tree::MerkleTree;
+
+#[derive(MerkleTree)]
+#[tree(depth = 32, storage = "CoreIdTree")]
+pub struct CoreId {
+    #[tree_arg]
+    name: String,
+}
+
+fn main() {}
@@ -0,0 +1,26 @@
+use chrono::NaiveDate;
+use merkle_tree::{CoreId, CoreIdTree, MerkleTree};
+
+fn core_id(name: &str) -> CoreId {
+    CoreId {
+        embedding_hash: "9f2c1e".to_string(),
+        merchants: Vec::new(),
+        records: Vec::new(),
+        name: name.to_string(),
+        breed: "Shiba Inu".to_string(),
+        date_of_birth: NaiveDate::from_ymd_opt(2021, 3, 14).unwrap(),
+        proof_level: 2,
+        microchip_id: "985112004511234".to_string(),
+    }
+}
+
+fn main() {
+    assert_eq!(CoreId::DEPTH as usize, merkle_tree_storage::CIRCUIT_MAX_DEPTH);
+    assert_eq!(CoreId::STORAGE, "CoreIdTree");
+    let _wrap: fn(_) -> <CoreId as MerkleTree>::Storage = CoreIdTree;
+
+    let rex = core_id("Rex");
+    assert_eq!(rex.tree_args().len(), 6);
+    assert!(rex.to_leaf_hash() == core_id("Rex").to_leaf_hash());
+    assert!(rex.to_leaf_hash() != core_id("Max").to_leaf_hash());
+}
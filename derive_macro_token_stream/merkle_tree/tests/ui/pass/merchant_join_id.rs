@@ -0,0 +1,31 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDate;
+use merkle_tree::{hash_field_elements, MerchantJoinId, MerchantJoinTree, MerkleTree};
+
+fn merchant_join_id(merchant_id: i32) -> MerchantJoinId {
+    MerchantJoinId {
+        merchant_id,
+        embedding_hash: "9f2c1e".to_string(),
+        write_fields: vec!["vaccinations".to_string()],
+        read_merchant_fields: Vec::new(),
+        last_updated: NaiveDate::from_ymd_opt(2025, 1, 20).unwrap().and_hms_opt(9, 15, 0).unwrap(),
+        latest_data_hash: hash_field_elements(&[]),
+    }
+}
+
+fn main() {
+    assert_eq!(MerchantJoinId::DEPTH as usize, merkle_tree_storage::CIRCUIT_MAX_DEPTH);
+    assert_eq!(MerchantJoinId::STORAGE, "MerchantJoinTree");
+    let _wrap: fn(_) -> <MerchantJoinId as MerkleTree>::Storage = MerchantJoinTree;
+
+    let join = merchant_join_id(7);
+    assert_eq!(join.tree_args().len(), 4);
+    assert!(join.to_leaf_hash() != merchant_join_id(8).to_leaf_hash());
+
+    // Only #[tree_arg] fields are hashed
+    let mut other = merchant_join_id(7);
+    other.write_fields.clear();
+    other.read_merchant_fields.push(BTreeMap::from([(3, vec!["name".to_string()])]));
+    assert!(join.to_leaf_hash() == other.to_leaf_hash());
+}
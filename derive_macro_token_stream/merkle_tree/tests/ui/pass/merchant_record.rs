@@ -0,0 +1,35 @@
+use chrono::NaiveDate;
+use merkle_tree::{hash_field_elements, MerchantRecord, MerchantRecordTree, MerkleTree, ToFieldElements};
+use serde_json::json;
+
+fn merchant_record(valid_days: Option<u32>) -> MerchantRecord {
+    let issued = NaiveDate::from_ymd_opt(2025, 1, 20).unwrap();
+    let prev_data_hash = hash_field_elements(&"previous".to_string().to_field_elements());
+    MerchantRecord {
+        embedding_hash: "9f2c1e".to_string(),
+        merchant_id: 7,
+        date_issued: issued.and_hms_opt(0, 0, 0).unwrap(),
+        valid_until: valid_days.map(|days| {
+            (issued + chrono::Days::new(days as u64)).and_hms_opt(0, 0, 0).unwrap()
+        }),
+        prev_data_hash,
+        data_record: json!({ "vaccine": "rabies" }),
+        data_hash: hash_field_elements(&[prev_data_hash]),
+    }
+}
+
+fn main() {
+    assert_eq!(MerchantRecord::DEPTH as usize, merkle_tree_storage::CIRCUIT_MAX_DEPTH);
+    assert_eq!(MerchantRecord::STORAGE, "MerchantRecordTree");
+    let _wrap: fn(_) -> <MerchantRecord as MerkleTree>::Storage = MerchantRecordTree;
+
+    let record = merchant_record(Some(365));
+    assert_eq!(record.tree_args().len(), 6);
+    assert!(record.to_leaf_hash() != merchant_record(None).to_leaf_hash());
+    assert!(record.to_leaf_hash() != merchant_record(Some(30)).to_leaf_hash());
+
+    // data_record is covered through data_hash, not hashed directly
+    let mut other = merchant_record(Some(365));
+    other.data_record = json!({ "vaccine": "distemper" });
+    assert!(record.to_leaf_hash() == other.to_leaf_hash());
+}
@@ -1,143 +1,368 @@
 extern crate proc_macro;
 use proc_macro::TokenStream;
-use syn::{parse_macro_input, DeriveInput, Data, Attribute, Meta, NestedMeta, Lit};
 use quote::quote;
-use proc_macro2::Ident;
-use poseidon_rs::{Fr, Poseidon};
-use merkle_tree_storage::MerkleTreeStorage;
-
+use merkle_tree_storage::CIRCUIT_MAX_DEPTH;
+use syn::{parse_macro_input, parse_quote, spanned::Spanned, Data, DeriveInput, Fields, Ident, Index, Lit, Meta, NestedMeta, Path};
 
+// Trees are proven with the circuits, so they are as deep as the circuits were compiled
+const DEPTH: u32 = CIRCUIT_MAX_DEPTH as u32;
+// `HashAccount` fields and the `merkle_tree::RootSlot` variant naming each
+const ROOT_SLOTS: [(&str, &str); 3] = [
+    ("ddid_root", "DdidRoot"),
+    ("merchant_root", "MerchantRoot"),
+    ("merchant_record_root", "MerchantRecordRoot"),
+];
 
+/// Derives `merkle_tree::MerkleTree`.
+///
+/// ```ignore
+/// #[derive(MerkleTree)]
+/// #[tree(storage = "CoreIdTree")]
+/// pub struct CoreId {
+///     #[tree_arg]
+///     name: String,
+///     records: Vec<MerchantRecord>,
+/// }
+/// ```
+///
+/// `storage` becomes `MerkleTree::Storage`. `MerkleTree::DEPTH` is always
+/// `merkle_tree_storage::CIRCUIT_MAX_DEPTH`, the depth the backend keeps its trees at; `depth`
+/// may be given, but must be that depth. The leaf hash
+/// covers the `#[tree_arg]` fields in declaration order, each turned into field elements
+/// with `ToFieldElements`; the other fields do not affect it. `load_tree` and `persist_leaf`
+/// keep the tree in the table named after the storage type, `CoreIdTree` here.
+///
+/// With `program = "<base58 program id>"` and `root_slot = "ddid_root"` (or `merchant_root`,
+/// `merchant_record_root`), `read_on_chain_root` reads that field of the program's
+/// `HashAccount`. Both are optional, but only together.
 #[proc_macro_derive(MerkleTree, attributes(tree, tree_arg))]
 pub fn derive_merkle_tree(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
-    let struct_name = input.ident;
-
-    // let tree_attr = input.attrs.iter().find(|attr| attr.path.is_ident("tree"));
-    // let mut _depth = 0;
-    // let mut _storage_name = Ident::new("TreeStorage", proc_macro2::Span::call_site());
-    // if let Some(attr) = tree_attr {
-    //     _depth = attr.parse_args::<().unwrap() as u32;
-    //     _storage_name = attr.parse_args::<Ident>().unwrap();
-    // }
-
-    let attrs = &input.attrs;
-    let mut _depth = None;
-    let mut _storage = None;
-
-    for attr in attrs {
-        // Check if the attribute is `tree`
-        if attr.path.is_ident("tree") {
-            // Parse the attribute as a Meta item
-            if let Ok(Meta::List(meta_list)) = attr.parse_meta() {
-                // Iterate over the nested meta items (e.g., depth and storage)
-                for nested in meta_list.nested {
-                    if let NestedMeta::Meta(Meta::NameValue(name_value)) = nested {
-                        if name_value.path.is_ident("depth") {
-                            // Parse the `depth` value
-                            if let Lit::Int(lit_int) = &name_value.lit {
-                                _depth = Some(lit_int.base10_parse::<u32>().unwrap());
-                            }
-                        } else if name_value.path.is_ident("storage") {
-                            // Parse the `storage` value
-                            if let Lit::Str(lit_str) = &name_value.lit {
-                                _storage = Some(lit_str.value());
-                            }
-                        }
-                    }
+    expand_merkle_tree(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `merkle_tree::ToFieldElements` for a struct by concatenating the encodings of
+/// all its fields in declaration order, so nested structs can be `#[tree_arg]`s.
+#[proc_macro_derive(ToFieldElements)]
+pub fn derive_to_field_elements(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_to_field_elements(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_to_field_elements(mut input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(data) => {
+            return Err(syn::Error::new(data.enum_token.span(), "ToFieldElements can only be derived for structs"))
+        }
+        Data::Union(data) => {
+            return Err(syn::Error::new(data.union_token.span(), "ToFieldElements can only be derived for structs"))
+        }
+    };
+    let accessors: Vec<proc_macro2::TokenStream> = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => quote! { #ident },
+            None => {
+                let index = Index::from(i);
+                quote! { #index }
+            }
+        })
+        .collect();
+
+    // Every type parameter has to be encodable for the struct to be
+    let type_params: Vec<Ident> = input.generics.type_params().map(|param| param.ident.clone()).collect();
+    let where_clause = input.generics.make_where_clause();
+    for param in type_params {
+        where_clause.predicates.push(parse_quote! { #param: ::merkle_tree::ToFieldElements });
+    }
+
+    let struct_name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::merkle_tree::ToFieldElements for #struct_name #ty_generics #where_clause {
+            fn append_field_elements(&self, elements: &mut ::std::vec::Vec<::merkle_tree::Fr>) {
+                #(::merkle_tree::ToFieldElements::append_field_elements(&self.#accessors, elements);)*
+            }
+        }
+    })
+}
+
+struct TreeOptions {
+    depth: u32,
+    storage: Path,
+    storage_name: String,
+    on_chain_root: Option<OnChainRoot>,
+}
+
+struct OnChainRoot {
+    program_id: [u8; 32],
+    slot: Ident,
+}
+
+fn expand_merkle_tree(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let struct_name = &input.ident;
+    let options = parse_tree_options(&input)?;
+    let tree_arg_fields = tree_arg_fields(&input)?;
+
+    let depth = options.depth;
+    let storage = &options.storage;
+    let storage_name = &options.storage_name;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let read_on_chain_root = options.on_chain_root.as_ref().map(|root| {
+        let program_id = root.program_id;
+        let slot = &root.slot;
+        quote! {
+            async fn read_on_chain_root() -> ::merkle_tree::__private::anyhow::Result<::merkle_tree::Fr> {
+                ::merkle_tree::on_chain::read_root([#(#program_id),*], ::merkle_tree::RootSlot::#slot).await
+            }
+        }
+    });
+
+    Ok(quote! {
+        #[::merkle_tree::__private::async_trait]
+        impl #impl_generics ::merkle_tree::MerkleTree for #struct_name #ty_generics #where_clause {
+            type Storage = #storage;
+            const DEPTH: u32 = #depth;
+            const STORAGE: &'static str = #storage_name;
+
+            fn tree_args(&self) -> ::std::vec::Vec<::merkle_tree::Fr> {
+                ::std::vec![
+                    #(::merkle_tree::hash_field_elements(
+                        &::merkle_tree::ToFieldElements::to_field_elements(&self.#tree_arg_fields)
+                    )),*
+                ]
+            }
+
+            async fn load_tree(
+                pool: &::merkle_tree::__private::PgPool,
+            ) -> ::merkle_tree::__private::anyhow::Result<Self::Storage> {
+                let storage = <::merkle_tree::__private::merkle_tree_storage::MerkleTreeStorage>::load(
+                    pool, #storage_name, #depth,
+                )
+                .await?;
+                ::std::result::Result::Ok(<Self::Storage as ::std::convert::From<
+                    ::merkle_tree::__private::merkle_tree_storage::MerkleTreeStorage,
+                >>::from(storage))
+            }
+
+            async fn persist_leaf(
+                &self,
+                pool: &::merkle_tree::__private::PgPool,
+            ) -> ::merkle_tree::__private::anyhow::Result<usize> {
+                let leaf = ::merkle_tree::MerkleTree::to_leaf_hash(self);
+                let mut tx = pool.begin().await?;
+                let position =
+                    ::merkle_tree::__private::merkle_tree_storage::persist_leaf(&mut tx, #storage_name, #depth, leaf).await?;
+                tx.commit().await?;
+                ::std::result::Result::Ok(position)
+            }
+
+            #read_on_chain_root
+        }
+    })
+}
+
+// Reads `#[tree(storage = "<type>", depth = <int>, program = "<id>", root_slot = "<field>")]`
+fn parse_tree_options(input: &DeriveInput) -> syn::Result<TreeOptions> {
+    let mut depth = None;
+    let mut storage = None;
+    let mut program = None;
+    let mut root_slot = None;
+    let mut found = false;
+
+    for attr in input.attrs.iter().filter(|attr| attr.path.is_ident("tree")) {
+        if found {
+            return Err(syn::Error::new(attr.span(), "duplicate #[tree(...)] attribute"));
+        }
+        found = true;
+
+        let meta_list = match attr.parse_meta()? {
+            Meta::List(meta_list) => meta_list,
+            meta => {
+                return Err(syn::Error::new(
+                    meta.span(),
+                    "expected #[tree(storage = \"<type>\")]",
+                ))
+            }
+        };
+        for nested in meta_list.nested {
+            let name_value = match nested {
+                NestedMeta::Meta(Meta::NameValue(name_value)) => name_value,
+                other => return Err(syn::Error::new(other.span(), "expected `<option> = <value>`")),
+            };
+
+            if name_value.path.is_ident("depth") {
+                if depth.is_some() {
+                    return Err(syn::Error::new(name_value.path.span(), "duplicate `depth`"));
+                }
+                let value = match &name_value.lit {
+                    Lit::Int(lit_int) => lit_int.base10_parse::<u32>()?,
+                    lit => return Err(syn::Error::new(lit.span(), "`depth` must be an integer")),
+                };
+                if value != DEPTH {
+                    return Err(syn::Error::new(
+                        name_value.lit.span(),
+                        format!("`depth` must be {}, the depth the circuits are compiled with", DEPTH),
+                    ));
+                }
+                depth = Some(value);
+            } else if name_value.path.is_ident("storage") {
+                if storage.is_some() {
+                    return Err(syn::Error::new(name_value.path.span(), "duplicate `storage`"));
                 }
+                let lit_str = match &name_value.lit {
+                    Lit::Str(lit_str) => lit_str,
+                    lit => return Err(syn::Error::new(lit.span(), "`storage` must be a string naming the storage type")),
+                };
+                let path: Path = lit_str
+                    .parse()
+                    .map_err(|_| syn::Error::new(lit_str.span(), "`storage` is not a valid type path"))?;
+                // The type's own name doubles as the table the tree is persisted in
+                let table = path.segments.last().unwrap().ident.to_string();
+                storage = Some((path, table));
+            } else if name_value.path.is_ident("program") {
+                if program.is_some() {
+                    return Err(syn::Error::new(name_value.path.span(), "duplicate `program`"));
+                }
+                let lit_str = match &name_value.lit {
+                    Lit::Str(lit_str) => lit_str,
+                    lit => return Err(syn::Error::new(lit.span(), "`program` must be a base58 program id string")),
+                };
+                let program_id = decode_program_id(&lit_str.value())
+                    .ok_or_else(|| syn::Error::new(lit_str.span(), "`program` is not a base58 encoded 32-byte program id"))?;
+                program = Some((program_id, name_value.path.span()));
+            } else if name_value.path.is_ident("root_slot") {
+                if root_slot.is_some() {
+                    return Err(syn::Error::new(name_value.path.span(), "duplicate `root_slot`"));
+                }
+                let lit_str = match &name_value.lit {
+                    Lit::Str(lit_str) => lit_str,
+                    lit => return Err(syn::Error::new(lit.span(), "`root_slot` must be a string naming a HashAccount field")),
+                };
+                let field = lit_str.value();
+                let variant = ROOT_SLOTS
+                    .iter()
+                    .find(|(name, _)| *name == field)
+                    .map(|(_, variant)| Ident::new(variant, lit_str.span()))
+                    .ok_or_else(|| {
+                        syn::Error::new(
+                            lit_str.span(),
+                            "unknown `root_slot`, expected `ddid_root`, `merchant_root` or `merchant_record_root`",
+                        )
+                    })?;
+                root_slot = Some((variant, name_value.path.span()));
+            } else {
+                return Err(syn::Error::new(
+                    name_value.path.span(),
+                    "unknown tree option, expected `depth`, `storage`, `program` or `root_slot`",
+                ));
             }
         }
     }
-    let _depth = _depth.expect("depth must be specified");
-    let _storage = _storage.expect("storage must be specified");
-
-    let tree_arg_fields = if let Data::Struct(data_struct) = &input.data {
-        data_struct.fields.iter()
-            .filter_map(|field| {
-                if field.attrs.iter().any(|attr| attr.path.is_ident("tree_arg")) {
-                    Some(field.ident.as_ref().unwrap())
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>()
-    } else {
-        vec![]
+
+    if !found {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "missing #[tree(storage = \"<type>\")] attribute",
+        ));
+    }
+    let missing = |what: &str| {
+        syn::Error::new(
+            input.ident.span(),
+            format!("missing `{}` in #[tree(storage = \"<type>\")]", what),
+        )
     };
-    
-
-    // let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
-
-    let expanded = match input.data {
-        Data::Struct(ref data) => {
-            // let leaf_hash_method = generate_leaf_hash_method(&struct_name);
-            // let tree_storage_method = generate_tree_storage_method(&struct_name);
-            // let read_on_chain_root_method = generate_read_on_chain_root_method(&struct_name);
-    // let expanded = quote! {
-    //     impl #impl_generics #struct_name #ty_generics #where_clause {
-            let contract_address = quote! {
-                let CONTRACT_ADDRESS: [u8;32] = [0;32];
-            };
-            let depth = quote! {let DEPTH: u32 = #_depth;};
-            // fn tree_args(&self) -> Vec<String> {
-            //     vec![
-            //         #(self.#tree_arg_fields.to_string()), *
-            //     ]
-            // }
-            quote! {
-                impl MerkleTree for #struct_name {
-                    type Storage = MerkleTreeStorage;
-                    const CONTRACT_ADDRESS: [u8;32] = #contract_address;
-                    const DEPTH: u32 = #depth;
-
-                    fn to_leaf_hash(&self) -> Fr {
-                        // #leaf_hash_method
-                        let data = vec![#(#tree_arg_fields.to_string())*].join("/");
-                        Fr::from_str(&data).unwrap()
-                    }
-
-                    fn tree_storage(&self) -> MerkleTreeStorage {
-                        // #tree_storage_method
-                        MerkleTreeStorage::new(self.#depth)
-                    }
-
-                    async fn read_on_chain_root(&self) -> Fr {
-                        // #read_on_chain_root_method
-                        Fr::from_str("0").unwrap()
-                    }
-                }
+    let depth = depth.unwrap_or(DEPTH);
+    let (storage, storage_name) = storage.ok_or_else(|| missing("storage"))?;
+    let on_chain_root = match (program, root_slot) {
+        (Some((program_id, _)), Some((slot, _))) => Some(OnChainRoot { program_id, slot }),
+        (Some((_, span)), None) => return Err(syn::Error::new(span, "`program` needs a `root_slot`")),
+        (None, Some((_, span))) => return Err(syn::Error::new(span, "`root_slot` needs a `program`")),
+        (None, None) => None,
+    };
+    Ok(TreeOptions {
+        depth,
+        storage,
+        storage_name,
+        on_chain_root,
+    })
+}
+
+// Solana program ids are 32 bytes written in base58
+fn decode_program_id(encoded: &str) -> Option<[u8; 32]> {
+    bs58::decode(encoded).into_vec().ok()?.try_into().ok()
+}
+
+// Named fields marked `#[tree_arg]`, in declaration order
+fn tree_arg_fields(input: &DeriveInput) -> syn::Result<Vec<Ident>> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields,
+            _ => {
+                return Err(syn::Error::new(
+                    input.ident.span(),
+                    "MerkleTree can only be derived for structs with named fields",
+                ))
             }
+        },
+        Data::Enum(data) => {
+            return Err(syn::Error::new(data.enum_token.span(), "MerkleTree can only be derived for structs"))
         }
-        _ => {
-            panic!("MerkleTree can only be derived for structs");
+        Data::Union(data) => {
+            return Err(syn::Error::new(data.union_token.span(), "MerkleTree can only be derived for structs"))
         }
     };
 
-    TokenStream::from(expanded)
+    let mut tree_args = Vec::new();
+    for field in fields.named.iter() {
+        if let Some(attr) = field.attrs.iter().find(|attr| attr.path.is_ident("tree_arg")) {
+            if !attr.tokens.is_empty() {
+                return Err(syn::Error::new(attr.tokens.span(), "#[tree_arg] takes no arguments"));
+            }
+            tree_args.push(field.ident.clone().unwrap());
+        }
+    }
+    if tree_args.is_empty() {
+        return Err(syn::Error::new(
+            input.ident.span(),
+            "at least one field must be marked #[tree_arg] to form the leaf hash",
+        ));
+    }
+    Ok(tree_args)
 }
 
-// fn generate_leaf_hash_method(struct_name: &Ident) -> proc_macro2::TokenStream {
-//     let field_name = struct_name.to_string();
-//     quote! {
-//         let mut field_value = self.#field_name;
-//         field_value.to_bytes_be();
-//         #Fr::from_str(field_value)
-//     }
-// }
-
-// fn generate_tree_storage_method(struct_name: &Ident) -> proc_macro2::TokenStream {
-//     let field_name = struct_name.to_string();
-//     quote! {
-//         // MerkleTreeStorage::new()
-//     }
-// }
-
-// fn generate_read_on_chain_root_method(struct_name: &Ident) -> proc_macro2::TokenStream {
-//     let field_name = struct_name.to_string();
-//     quote! {
-//         let mut field_value = self.#field_name;
-//         field_value.to_bytes_be();
-//         #Fr::from_str(field_value)
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use super::decode_program_id;
+
+    #[test]
+    fn leading_ones_decode_to_leading_zero_bytes() {
+        assert_eq!(decode_program_id("11111111111111111111111111111111"), Some([0; 32]));
+        let mut expected = [0u8; 32];
+        for (byte, value) in expected[2..].iter_mut().zip(1..) {
+            *byte = value;
+        }
+        assert_eq!(decode_program_id("11CiMQsCUhqABwwLyCFeX2iPnBZX3s28dUUCBrirhs"), Some(expected));
+    }
+
+    #[test]
+    fn invalid_characters_are_rejected() {
+        for c in ['0', 'O', 'I', 'l', '+'] {
+            let encoded = format!("9guwSzLJSkomxdbTM6TfKTF3KYSDxLNeSsCRdPaBGVp{}", c);
+            assert_eq!(decode_program_id(&encoded), None, "{}", encoded);
+        }
+    }
+
+    #[test]
+    fn ids_that_are_not_32_bytes_are_rejected() {
+        assert_eq!(decode_program_id(""), None);
+        assert_eq!(decode_program_id("1111111111111111111111111111111"), None);
+        assert_eq!(decode_program_id("9guwSzLJSkomxdbTM6TfKTF3KYSDxLNeSsCRdPaBGVpU9"), None);
+    }
+}
@@ -0,0 +1,271 @@
+//! Byte, hex and base58 encodings of BN254 values, in the layout the `alt_bn128` syscalls use.
+//!
+//! The big-endian form is the syscall one: 32-byte big-endian words, G1 as `x || y`, G2 as
+//! `x.c1 || x.c0 || y.c1 || y.c0` (EIP-197), the point at infinity as all zeros, and a proof
+//! as `a || b || c`. The little-endian form keeps the same words in the same order and
+//! reverses the bytes of each one, which is what arkworks and `groth16-solana`'s
+//! `convert_endianness` produce. Hex is the big-endian form with a `0x` prefix, base58 is the
+//! big-endian form as Solana tooling prints it.
+//!
+//! Decoding rejects anything the syscalls reject: coordinates and scalars not below their
+//! modulus, points off the curve and G2 points outside the prime-order subgroup.
+
+use ark_bn254::{g1, g2, Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::{pairing::Pairing, short_weierstrass::Affine, AffineRepr};
+use ark_ff::{BigInteger, One, PrimeField, Zero};
+use ark_groth16::Proof;
+use thiserror::Error;
+
+/// Bytes in one encoded scalar or coordinate.
+pub const WORD_LEN: usize = 32;
+pub const G1_LEN: usize = 2 * WORD_LEN;
+pub const G2_LEN: usize = 4 * WORD_LEN;
+pub const PROOF_LEN: usize = 2 * G1_LEN + G2_LEN;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CodecError {
+    #[error("Expected {expected} bytes, got {actual}")]
+    Length { expected: usize, actual: usize },
+    #[error("Word {0} is not below the field modulus")]
+    NotReduced(usize),
+    #[error("Point is not on the curve")]
+    NotOnCurve,
+    #[error("Point is not in the prime-order subgroup")]
+    NotInSubgroup,
+    #[error("Invalid hex: {0}")]
+    Hex(String),
+    #[error("Invalid base58: {0}")]
+    Base58(String),
+}
+
+/// A value with a fixed-size encoding made of 32-byte words.
+pub trait Codec: Sized {
+    /// Length of the encoding in bytes.
+    const LEN: usize;
+
+    fn to_be_bytes(&self) -> Vec<u8>;
+
+    fn from_be_bytes(bytes: &[u8]) -> Result<Self, CodecError>;
+
+    fn to_le_bytes(&self) -> Vec<u8> {
+        swap_words(&self.to_be_bytes())
+    }
+
+    fn from_le_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        check_len(bytes, Self::LEN)?;
+        Self::from_be_bytes(&swap_words(bytes))
+    }
+
+    /// `0x` followed by the big-endian bytes, lowercase.
+    fn to_hex(&self) -> String {
+        format!("0x{}", hex::encode(self.to_be_bytes()))
+    }
+
+    /// Reads the big-endian bytes as hex, with or without the `0x` prefix.
+    fn from_hex(s: &str) -> Result<Self, CodecError> {
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        let bytes = hex::decode(digits).map_err(|e| CodecError::Hex(e.to_string()))?;
+        Self::from_be_bytes(&bytes)
+    }
+
+    fn to_base58(&self) -> String {
+        bs58::encode(self.to_be_bytes()).into_string()
+    }
+
+    fn from_base58(s: &str) -> Result<Self, CodecError> {
+        let bytes = bs58::decode(s).into_vec().map_err(|e| CodecError::Base58(e.to_string()))?;
+        Self::from_be_bytes(&bytes)
+    }
+}
+
+impl Codec for Fr {
+    const LEN: usize = WORD_LEN;
+
+    fn to_be_bytes(&self) -> Vec<u8> {
+        fr_to_be(self).to_vec()
+    }
+
+    fn from_be_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        check_len(bytes, Self::LEN)?;
+        word_from_be(bytes, 0)
+    }
+}
+
+// Spelled with the curve configs: through `G1Affine`/`G2Affine` the two impls would overlap
+impl Codec for Affine<g1::Config> {
+    const LEN: usize = G1_LEN;
+
+    fn to_be_bytes(&self) -> Vec<u8> {
+        g1_to_be(self).to_vec()
+    }
+
+    fn from_be_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        check_len(bytes, Self::LEN)?;
+        g1_from_be(bytes.try_into().unwrap())
+    }
+}
+
+impl Codec for Affine<g2::Config> {
+    const LEN: usize = G2_LEN;
+
+    fn to_be_bytes(&self) -> Vec<u8> {
+        g2_to_be(self).to_vec()
+    }
+
+    fn from_be_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        check_len(bytes, Self::LEN)?;
+        g2_from_be(bytes.try_into().unwrap())
+    }
+}
+
+impl Codec for Proof<Bn254> {
+    const LEN: usize = PROOF_LEN;
+
+    fn to_be_bytes(&self) -> Vec<u8> {
+        proof_to_be(self).to_vec()
+    }
+
+    fn from_be_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        check_len(bytes, Self::LEN)?;
+        Ok(Proof {
+            a: g1_from_be(bytes[..G1_LEN].try_into().unwrap())?,
+            b: g2_from_be(bytes[G1_LEN..G1_LEN + G2_LEN].try_into().unwrap())?,
+            c: g1_from_be(bytes[G1_LEN + G2_LEN..].try_into().unwrap())?,
+        })
+    }
+}
+
+pub fn fr_to_be(value: &Fr) -> [u8; WORD_LEN] {
+    word_to_be(value)
+}
+
+pub fn fr_to_le(value: &Fr) -> [u8; WORD_LEN] {
+    convert_endianness(&fr_to_be(value))
+}
+
+/// `x || y`, all zeros for the point at infinity.
+pub fn g1_to_be(point: &G1Affine) -> [u8; G1_LEN] {
+    let mut bytes = [0u8; G1_LEN];
+    if let Some((x, y)) = point.xy() {
+        bytes[..WORD_LEN].copy_from_slice(&word_to_be(x));
+        bytes[WORD_LEN..].copy_from_slice(&word_to_be(y));
+    }
+    bytes
+}
+
+/// `x.c1 || x.c0 || y.c1 || y.c0`, all zeros for the point at infinity.
+pub fn g2_to_be(point: &G2Affine) -> [u8; G2_LEN] {
+    let mut bytes = [0u8; G2_LEN];
+    if let Some((x, y)) = point.xy() {
+        for (i, coordinate) in [x.c1, x.c0, y.c1, y.c0].iter().enumerate() {
+            bytes[i * WORD_LEN..(i + 1) * WORD_LEN].copy_from_slice(&word_to_be(coordinate));
+        }
+    }
+    bytes
+}
+
+/// `a || b || c`. The verifier program takes `a` negated; that is up to the caller.
+pub fn proof_to_be(proof: &Proof<Bn254>) -> [u8; PROOF_LEN] {
+    let mut bytes = [0u8; PROOF_LEN];
+    bytes[..G1_LEN].copy_from_slice(&g1_to_be(&proof.a));
+    bytes[G1_LEN..G1_LEN + G2_LEN].copy_from_slice(&g2_to_be(&proof.b));
+    bytes[G1_LEN + G2_LEN..].copy_from_slice(&g1_to_be(&proof.c));
+    bytes
+}
+
+pub fn g1_from_be(bytes: &[u8; G1_LEN]) -> Result<G1Affine, CodecError> {
+    if bytes.iter().all(|b| *b == 0) {
+        return Ok(G1Affine::zero());
+    }
+    let point = G1Affine::new_unchecked(word_from_be(bytes, 0)?, word_from_be(bytes, 1)?);
+    // G1 has cofactor one, every point on the curve is in the group
+    if !point.is_on_curve() {
+        return Err(CodecError::NotOnCurve);
+    }
+    Ok(point)
+}
+
+pub fn g2_from_be(bytes: &[u8; G2_LEN]) -> Result<G2Affine, CodecError> {
+    if bytes.iter().all(|b| *b == 0) {
+        return Ok(G2Affine::zero());
+    }
+    let mut words = [Fq::zero(); 4];
+    for (i, word) in words.iter_mut().enumerate() {
+        *word = word_from_be(bytes, i)?;
+    }
+    let [x_c1, x_c0, y_c1, y_c0] = words;
+    let point = G2Affine::new_unchecked(Fq2::new(x_c0, x_c1), Fq2::new(y_c0, y_c1));
+    if !point.is_on_curve() {
+        return Err(CodecError::NotOnCurve);
+    }
+    if !point.is_in_correct_subgroup_assuming_on_curve() {
+        return Err(CodecError::NotInSubgroup);
+    }
+    Ok(point)
+}
+
+/// Input of `alt_bn128_pairing`: each pair as `g1 || g2`, back to back. The syscall returns
+/// one when the product of the pairings is the identity.
+pub fn pairing_input(pairs: &[(G1Affine, G2Affine)]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(pairs.len() * (G1_LEN + G2_LEN));
+    for (g1, g2) in pairs {
+        bytes.extend_from_slice(&g1_to_be(g1));
+        bytes.extend_from_slice(&g2_to_be(g2));
+    }
+    bytes
+}
+
+/// What `alt_bn128_pairing` computes, natively: whether the product of the pairings in
+/// `input` is the identity. Empty input is the empty product.
+pub fn pairing_check(input: &[u8]) -> Result<bool, CodecError> {
+    const PAIR_LEN: usize = G1_LEN + G2_LEN;
+    if !input.len().is_multiple_of(PAIR_LEN) {
+        return Err(CodecError::Length {
+            expected: input.len().next_multiple_of(PAIR_LEN),
+            actual: input.len(),
+        });
+    }
+    let mut g1 = Vec::new();
+    let mut g2 = Vec::new();
+    for pair in input.chunks_exact(PAIR_LEN) {
+        g1.push(g1_from_be(pair[..G1_LEN].try_into().unwrap())?);
+        g2.push(g2_from_be(pair[G1_LEN..].try_into().unwrap())?);
+    }
+    Ok(Bn254::multi_pairing(g1, g2).0.is_one())
+}
+
+/// Swaps between the big- and little-endian forms by reversing every 32-byte word in place.
+/// `N` has to be a multiple of 32.
+pub fn convert_endianness<const N: usize>(bytes: &[u8; N]) -> [u8; N] {
+    assert!(N.is_multiple_of(WORD_LEN), "{} bytes is not a whole number of words", N);
+    let mut swapped = *bytes;
+    for word in swapped.chunks_exact_mut(WORD_LEN) {
+        word.reverse();
+    }
+    swapped
+}
+
+fn swap_words(bytes: &[u8]) -> Vec<u8> {
+    bytes.chunks(WORD_LEN).flat_map(|word| word.iter().rev().copied()).collect()
+}
+
+fn check_len(bytes: &[u8], expected: usize) -> Result<(), CodecError> {
+    if bytes.len() != expected {
+        return Err(CodecError::Length { expected, actual: bytes.len() });
+    }
+    Ok(())
+}
+
+fn word_to_be<F: PrimeField>(value: &F) -> [u8; WORD_LEN] {
+    value.into_bigint().to_bytes_be().try_into().unwrap()
+}
+
+// Word `index` of `bytes`, rejected unless it is below the modulus
+fn word_from_be<F: PrimeField>(bytes: &[u8], index: usize) -> Result<F, CodecError> {
+    let word = &bytes[index * WORD_LEN..(index + 1) * WORD_LEN];
+    let mut repr = F::BigInt::default();
+    for (limb, chunk) in repr.as_mut().iter_mut().zip(word.rchunks_exact(8)) {
+        *limb = u64::from_be_bytes(chunk.try_into().unwrap());
+    }
+    F::from_bigint(repr).ok_or(CodecError::NotReduced(index))
+}
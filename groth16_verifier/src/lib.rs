@@ -0,0 +1,158 @@
+//! Native Groth16 verification of the proofs sent to the verifier program.
+//!
+//! The program checks `Groth16VerifierPrepared` with `alt_bn128_pairing`; this crate decodes
+//! the same Borsh bytes back into arkworks types and runs the pairing check off chain, so a
+//! proof can be checked before paying for the transaction or replayed from a failed one.
+
+use ark_bn254::{Bn254, Fr, G1Affine, G1Projective, G2Affine};
+use ark_groth16::{prepare_verifying_key, Groth16, PreparedVerifyingKey, Proof, VerifyingKey};
+use borsh::{BorshDeserialize, BorshSerialize};
+use thiserror::Error;
+
+pub mod codec;
+
+pub use codec::{g1_to_be as encode_g1, g2_to_be as encode_g2};
+
+// Variant index of `ProgramInstruction::VerifyProof` in the instruction data
+const VERIFY_PROOF_TAG: u8 = 0;
+
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error("Failed to decode verifier: {0}")]
+    InvalidVerifier(String),
+    #[error("{0} is not a valid G1 point")]
+    InvalidG1(&'static str),
+    #[error("{0} is not a valid G2 point")]
+    InvalidG2(&'static str),
+    #[error("Verifying key in the proof does not match the given verifying key")]
+    VerifyingKeyMismatch,
+    #[error("Public inputs do not match the prepared public inputs in the proof")]
+    PublicInputsMismatch,
+    #[error("Pairing check failed: {0}")]
+    Pairing(String),
+}
+
+/// Same layout as the verifier program's `Groth16VerifyingKeyPrepared`.
+#[derive(PartialEq, Eq, Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct Groth16VerifyingKeyPrepared {
+    pub vk_alpha_g1: [u8; 64],
+    pub vk_beta_g2: [u8; 128],
+    pub vk_gamma_g2: [u8; 128],
+    pub vk_delta_g2: [u8; 128],
+}
+
+/// Same layout as the verifier program's `Groth16VerifierPrepared`: big-endian
+/// `alt_bn128` points, with `proof_a` negated so the program's pairing product is one.
+#[derive(PartialEq, Eq, Debug, Clone, BorshSerialize, BorshDeserialize)]
+pub struct Groth16VerifierPrepared {
+    pub proof_a: [u8; 64],
+    pub proof_b: [u8; 128],
+    pub proof_c: [u8; 64],
+    pub prepared_public_inputs: [u8; 64],
+    pub verifying_key: Box<Groth16VerifyingKeyPrepared>,
+}
+
+impl Groth16VerifyingKeyPrepared {
+    pub fn from_verifying_key(vk: &VerifyingKey<Bn254>) -> Self {
+        Self {
+            vk_alpha_g1: encode_g1(&vk.alpha_g1),
+            vk_beta_g2: encode_g2(&vk.beta_g2),
+            vk_gamma_g2: encode_g2(&vk.gamma_g2),
+            vk_delta_g2: encode_g2(&vk.delta_g2),
+        }
+    }
+}
+
+impl Groth16VerifierPrepared {
+    /// Packs an arkworks proof the way the verifier program expects it.
+    pub fn from_proof(proof: &Proof<Bn254>, prepared_public_inputs: &G1Projective, vk: &VerifyingKey<Bn254>) -> Self {
+        Self {
+            proof_a: encode_g1(&(-proof.a)),
+            proof_b: encode_g2(&proof.b),
+            proof_c: encode_g1(&proof.c),
+            prepared_public_inputs: encode_g1(&(*prepared_public_inputs).into()),
+            verifying_key: Box::new(Groth16VerifyingKeyPrepared::from_verifying_key(vk)),
+        }
+    }
+
+    /// Decodes either the bare Borsh verifier or a whole `VerifyProof` instruction.
+    pub fn from_instruction_data(data: &[u8]) -> Result<Self, VerifyError> {
+        let payload = match data.split_first() {
+            Some((&VERIFY_PROOF_TAG, rest)) if data.len() == serialized_len() + 1 => rest,
+            _ => data,
+        };
+        Self::try_from_slice(payload).map_err(|e| VerifyError::InvalidVerifier(e.to_string()))
+    }
+
+    /// The `alt_bn128_pairing` input the program checks: `(-a, b)`, `(inputs, gamma)`,
+    /// `(c, delta)`, `(alpha, beta)`, whose product is the identity for a valid proof.
+    pub fn pairing_input(&self) -> Vec<u8> {
+        let vk = &self.verifying_key;
+        [
+            &self.proof_a[..],
+            &self.proof_b,
+            &self.prepared_public_inputs,
+            &vk.vk_gamma_g2,
+            &self.proof_c,
+            &vk.vk_delta_g2,
+            &vk.vk_alpha_g1,
+            &vk.vk_beta_g2,
+        ]
+        .concat()
+    }
+
+    /// Decodes the proof, with `proof_a` negated back.
+    pub fn proof(&self) -> Result<Proof<Bn254>, VerifyError> {
+        Ok(Proof {
+            a: -decode_g1(&self.proof_a, "proof_a")?,
+            b: decode_g2(&self.proof_b, "proof_b")?,
+            c: decode_g1(&self.proof_c, "proof_c")?,
+        })
+    }
+}
+
+/// Runs the program's pairing check natively against a trusted `vk`, e.g. the circuit's
+/// `.vk` file. The key embedded in the verifier has to match it: a proof that carries its
+/// own key proves nothing.
+pub fn verify_prepared(verifier: &Groth16VerifierPrepared, vk: &VerifyingKey<Bn254>) -> Result<bool, VerifyError> {
+    if *verifier.verifying_key != Groth16VerifyingKeyPrepared::from_verifying_key(vk) {
+        return Err(VerifyError::VerifyingKeyMismatch);
+    }
+    let proof = verifier.proof()?;
+    let prepared_inputs: G1Projective = decode_g1(&verifier.prepared_public_inputs, "prepared_public_inputs")?.into();
+    Groth16::<Bn254>::verify_proof_with_prepared_inputs(&prepare_verifying_key(vk), &proof, &prepared_inputs)
+        .map_err(|e| VerifyError::Pairing(e.to_string()))
+}
+
+/// Like `verify_prepared`, and also checks the prepared inputs were built from `public_inputs`.
+pub fn verify_with_public_inputs(
+    verifier: &Groth16VerifierPrepared,
+    vk: &VerifyingKey<Bn254>,
+    public_inputs: &[Fr],
+) -> Result<bool, VerifyError> {
+    let pvk: PreparedVerifyingKey<Bn254> = prepare_verifying_key(vk);
+    let prepared_inputs = Groth16::<Bn254>::prepare_inputs(&pvk, public_inputs)
+        .map_err(|e| VerifyError::Pairing(e.to_string()))?;
+    if encode_g1(&prepared_inputs.into()) != verifier.prepared_public_inputs {
+        return Err(VerifyError::PublicInputsMismatch);
+    }
+    if *verifier.verifying_key != Groth16VerifyingKeyPrepared::from_verifying_key(vk) {
+        return Err(VerifyError::VerifyingKeyMismatch);
+    }
+    Groth16::<Bn254>::verify_proof(&pvk, &verifier.proof()?, public_inputs)
+        .map_err(|e| VerifyError::Pairing(e.to_string()))
+}
+
+/// Decodes an `alt_bn128` G1 point, naming it `name` in the error.
+pub fn decode_g1(bytes: &[u8; 64], name: &'static str) -> Result<G1Affine, VerifyError> {
+    codec::g1_from_be(bytes).map_err(|_| VerifyError::InvalidG1(name))
+}
+
+/// Decodes an `alt_bn128` G2 point, naming it `name` in the error.
+pub fn decode_g2(bytes: &[u8; 128], name: &'static str) -> Result<G2Affine, VerifyError> {
+    codec::g2_from_be(bytes).map_err(|_| VerifyError::InvalidG2(name))
+}
+
+fn serialized_len() -> usize {
+    64 + 128 + 64 + 64 + 64 + 3 * 128
+}
@@ -0,0 +1,44 @@
+use std::{env, fs, process::ExitCode};
+
+use ark_bn254::Bn254;
+use ark_groth16::VerifyingKey;
+use ark_serialize::CanonicalDeserialize;
+use groth16_verifier::{verify_prepared, Groth16VerifierPrepared};
+
+const USAGE: &str = "Usage: verify_proof <proof-file> <verifying-key-file>
+
+  proof-file          Borsh Groth16VerifierPrepared, or the whole VerifyProof instruction data
+  verifying-key-file  uncompressed arkworks verifying key, e.g. build/circuits/InsertLeaf.vk";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let [proof_path, vk_path] = args.as_slice() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(2);
+    };
+
+    match run(proof_path, vk_path) {
+        Ok(true) => {
+            println!("Proof is valid!");
+            ExitCode::SUCCESS
+        }
+        Ok(false) => {
+            println!("Proof is invalid!");
+            ExitCode::FAILURE
+        }
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(proof_path: &str, vk_path: &str) -> Result<bool, String> {
+    let proof_bytes = fs::read(proof_path).map_err(|e| format!("Failed to read {}: {}", proof_path, e))?;
+    let vk_bytes = fs::read(vk_path).map_err(|e| format!("Failed to read {}: {}", vk_path, e))?;
+
+    let verifier = Groth16VerifierPrepared::from_instruction_data(&proof_bytes).map_err(|e| e.to_string())?;
+    let vk = VerifyingKey::<Bn254>::deserialize_uncompressed(vk_bytes.as_slice())
+        .map_err(|e| format!("Invalid verifying key {}: {}", vk_path, e))?;
+    verify_prepared(&verifier, &vk).map_err(|e| e.to_string())
+}
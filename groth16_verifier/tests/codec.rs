@@ -0,0 +1,129 @@
+use ark_bn254::{g2, Bn254, Fq, Fq2, Fr, G1Affine, G2Affine};
+use ark_ec::{short_weierstrass::SWCurveConfig, AffineRepr};
+use ark_ff::{Field, One};
+use ark_groth16::Proof;
+use groth16_verifier::codec::{
+    convert_endianness, fr_to_be, fr_to_le, g1_to_be, g2_to_be, pairing_check, pairing_input, Codec, CodecError,
+};
+
+// BN254 scalar modulus
+const R_HEX: &str = "0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001";
+
+fn word(hex: &str) -> Vec<u8> {
+    hex::decode(hex).unwrap()
+}
+
+fn round_trips<T: Codec + PartialEq + std::fmt::Debug>(value: &T) {
+    assert_eq!(value.to_be_bytes().len(), T::LEN);
+    assert_eq!(T::from_be_bytes(&value.to_be_bytes()).unwrap(), *value);
+    assert_eq!(T::from_le_bytes(&value.to_le_bytes()).unwrap(), *value);
+    assert_eq!(T::from_hex(&value.to_hex()).unwrap(), *value);
+    assert_eq!(T::from_base58(&value.to_base58()).unwrap(), *value);
+}
+
+fn proof() -> Proof<Bn254> {
+    Proof {
+        a: (G1Affine::generator() * Fr::from(3u64)).into(),
+        b: (G2Affine::generator() * Fr::from(5u64)).into(),
+        c: (G1Affine::generator() * Fr::from(7u64)).into(),
+    }
+}
+
+#[test]
+fn scalars_are_32_byte_words() {
+    let one = Fr::one();
+    let mut be = [0u8; 32];
+    be[31] = 1;
+    assert_eq!(fr_to_be(&one), be);
+    assert_eq!(fr_to_le(&one), convert_endianness(&be));
+    assert_eq!(fr_to_le(&one)[0], 1);
+    assert_eq!(one.to_hex(), format!("0x{}01", "00".repeat(31)));
+    assert_eq!(Fr::from_hex("0x2a").unwrap_err(), CodecError::Length { expected: 32, actual: 1 });
+
+    for value in [Fr::from(0u64), one, -one, Fr::from(u64::MAX)] {
+        round_trips(&value);
+    }
+    assert_eq!(Fr::from_hex(R_HEX).unwrap_err(), CodecError::NotReduced(0));
+}
+
+#[test]
+fn points_use_the_eip197_layout() {
+    // G1 generator is (1, 2)
+    let g1 = g1_to_be(&G1Affine::generator());
+    assert_eq!(g1[..32], word("0000000000000000000000000000000000000000000000000000000000000001"));
+    assert_eq!(g1[32..], word("0000000000000000000000000000000000000000000000000000000000000002"));
+
+    // G2 generator with the imaginary part of each coordinate first
+    let g2 = g2_to_be(&G2Affine::generator());
+    for (i, expected) in [
+        "198e9393920d483a7260bfb731fb5d25f1aa493335a9e71297e485b7aef312c2",
+        "1800deef121f1e76426a00665e5c4479674322d4f75edadd46debd5cd992f6ed",
+        "090689d0585ff075ec9e99ad690c3395bc4b313370b38ef355acdadcd122975b",
+        "12c85ea5db8c6deb4aab71808dcb408fe3d1e7690c43d37b4ce6cc0166fa7daa",
+    ]
+    .iter()
+    .enumerate()
+    {
+        assert_eq!(g2[i * 32..(i + 1) * 32], word(expected));
+    }
+
+    assert_eq!(g1_to_be(&G1Affine::zero()), [0u8; 64]);
+    assert_eq!(g2_to_be(&G2Affine::zero()), [0u8; 128]);
+}
+
+#[test]
+fn points_and_proofs_round_trip() {
+    let proof = proof();
+    for point in [proof.a, proof.c, G1Affine::zero()] {
+        round_trips(&point);
+    }
+    for point in [proof.b, G2Affine::zero()] {
+        round_trips(&point);
+    }
+    round_trips(&proof);
+
+    let be = proof.to_be_bytes();
+    assert_eq!(be[..64], g1_to_be(&proof.a));
+    assert_eq!(be[64..192], g2_to_be(&proof.b));
+    assert_eq!(be[192..], g1_to_be(&proof.c));
+    assert_eq!(proof.to_le_bytes(), convert_endianness::<256>(&be.clone().try_into().unwrap()));
+}
+
+#[test]
+fn invalid_encodings_are_rejected() {
+    let mut off_curve = g1_to_be(&G1Affine::generator());
+    off_curve[63] ^= 1;
+    assert_eq!(G1Affine::from_be_bytes(&off_curve).unwrap_err(), CodecError::NotOnCurve);
+
+    // A point on the twist outside the prime-order subgroup
+    let outside = (1u64..)
+        .find_map(|x| {
+            let x = Fq2::new(Fq::from(x), Fq::one());
+            let y = (x * x * x + g2::Config::COEFF_B).sqrt()?;
+            let point = G2Affine::new_unchecked(x, y);
+            (!point.is_in_correct_subgroup_assuming_on_curve()).then_some(point)
+        })
+        .unwrap();
+    assert_eq!(G2Affine::from_be_bytes(&g2_to_be(&outside)).unwrap_err(), CodecError::NotInSubgroup);
+
+    assert_eq!(
+        Proof::<Bn254>::from_be_bytes(&[0u8; 255]).unwrap_err(),
+        CodecError::Length { expected: 256, actual: 255 }
+    );
+    assert!(matches!(Fr::from_hex("0xzz"), Err(CodecError::Hex(_))));
+    assert!(matches!(Fr::from_base58("0OIl"), Err(CodecError::Base58(_))));
+}
+
+#[test]
+fn pairing_input_matches_the_syscall() {
+    let p = G1Affine::generator();
+    let q = G2Affine::generator();
+    let input = pairing_input(&[(p, q), (-p, q)]);
+    assert_eq!(input.len(), 2 * 192);
+    assert_eq!(input[..64], g1_to_be(&p));
+    assert_eq!(input[64..192], g2_to_be(&q));
+    assert!(pairing_check(&input).unwrap());
+    assert!(!pairing_check(&pairing_input(&[(p, q), (p, q)])).unwrap());
+    assert!(pairing_check(&[]).unwrap());
+    assert!(matches!(pairing_check(&input[1..]), Err(CodecError::Length { .. })));
+}
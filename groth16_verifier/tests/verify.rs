@@ -0,0 +1,109 @@
+use ark_bn254::{Bn254, Fr};
+use ark_groth16::{prepare_verifying_key, Groth16, ProvingKey};
+use ark_relations::{
+    lc,
+    r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError},
+};
+use ark_snark::SNARK;
+use ark_std::rand::{rngs::StdRng, SeedableRng};
+use borsh::to_vec;
+use groth16_verifier::{
+    codec::pairing_check, decode_g1, decode_g2, encode_g1, encode_g2, verify_prepared, verify_with_public_inputs,
+    Groth16VerifierPrepared, VerifyError,
+};
+
+// Proves knowledge of x, y with x * y = z for public z
+#[derive(Clone)]
+struct Product {
+    x: Fr,
+    y: Fr,
+}
+
+impl ConstraintSynthesizer<Fr> for Product {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let z = cs.new_input_variable(|| Ok(self.x * self.y))?;
+        let x = cs.new_witness_variable(|| Ok(self.x))?;
+        let y = cs.new_witness_variable(|| Ok(self.y))?;
+        cs.enforce_constraint(lc!() + x, lc!() + y, lc!() + z)
+    }
+}
+
+fn setup(seed: u64) -> ProvingKey<Bn254> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let circuit = Product { x: Fr::from(0u64), y: Fr::from(0u64) };
+    Groth16::<Bn254>::circuit_specific_setup(circuit, &mut rng).unwrap().0
+}
+
+fn prove(pk: &ProvingKey<Bn254>, x: u64, y: u64, claimed: u64) -> Groth16VerifierPrepared {
+    let mut rng = StdRng::seed_from_u64(x * 31 + y);
+    let proof = Groth16::<Bn254>::prove(pk, Product { x: Fr::from(x), y: Fr::from(y) }, &mut rng).unwrap();
+    let pvk = prepare_verifying_key(&pk.vk);
+    let prepared_inputs = Groth16::<Bn254>::prepare_inputs(&pvk, &[Fr::from(claimed)]).unwrap();
+    Groth16VerifierPrepared::from_proof(&proof, &prepared_inputs, &pk.vk)
+}
+
+#[test]
+fn accepts_valid_proof() {
+    let pk = setup(1);
+    let verifier = prove(&pk, 6, 7, 42);
+    assert!(verify_prepared(&verifier, &pk.vk).unwrap());
+    assert!(verify_with_public_inputs(&verifier, &pk.vk, &[Fr::from(42u64)]).unwrap());
+}
+
+#[test]
+fn rejects_wrong_public_input() {
+    let pk = setup(1);
+    let verifier = prove(&pk, 6, 7, 43);
+    assert!(!verify_prepared(&verifier, &pk.vk).unwrap());
+    assert!(matches!(
+        verify_with_public_inputs(&verifier, &pk.vk, &[Fr::from(42u64)]),
+        Err(VerifyError::PublicInputsMismatch)
+    ));
+}
+
+#[test]
+fn rejects_foreign_verifying_key() {
+    let pk = setup(1);
+    let other = setup(2);
+    let verifier = prove(&pk, 6, 7, 42);
+    assert!(matches!(verify_prepared(&verifier, &other.vk), Err(VerifyError::VerifyingKeyMismatch)));
+}
+
+#[test]
+fn decodes_borsh_and_instruction_data() {
+    let pk = setup(1);
+    let verifier = prove(&pk, 3, 5, 15);
+    let bytes = to_vec(&verifier).unwrap();
+    assert_eq!(Groth16VerifierPrepared::from_instruction_data(&bytes).unwrap(), verifier);
+
+    let mut instruction = vec![0u8];
+    instruction.extend_from_slice(&bytes);
+    assert_eq!(Groth16VerifierPrepared::from_instruction_data(&instruction).unwrap(), verifier);
+
+    assert!(Groth16VerifierPrepared::from_instruction_data(&bytes[1..]).is_err());
+}
+
+#[test]
+fn point_encodings_round_trip() {
+    let pk = setup(3);
+    for point in [pk.vk.alpha_g1, pk.a_query[1], Default::default()] {
+        assert_eq!(decode_g1(&encode_g1(&point), "g1").unwrap(), point);
+    }
+    for point in [pk.vk.beta_g2, pk.vk.delta_g2, Default::default()] {
+        assert_eq!(decode_g2(&encode_g2(&point), "g2").unwrap(), point);
+    }
+
+    let mut off_curve = encode_g1(&pk.vk.alpha_g1);
+    off_curve[63] ^= 1;
+    assert!(matches!(decode_g1(&off_curve, "g1"), Err(VerifyError::InvalidG1("g1"))));
+    assert!(matches!(decode_g2(&[0xff; 128], "g2"), Err(VerifyError::InvalidG2("g2"))));
+}
+
+#[test]
+fn pairing_input_is_what_the_program_checks() {
+    let pk = setup(4);
+    let valid = prove(&pk, 2, 9, 18);
+    assert_eq!(valid.pairing_input().len(), 4 * 192);
+    assert!(pairing_check(&valid.pairing_input()).unwrap());
+    assert!(!pairing_check(&prove(&pk, 2, 9, 19).pairing_input()).unwrap());
+}
@@ -0,0 +1,44 @@
+//! Full rebuilds of a Poseidon tree, one layer at a time against rayon's thread pool, and
+//! the per-leaf `insert_leaf` loop trees were loaded with before.
+//!
+//! `cargo bench --bench rebuild`
+
+use std::time::Duration;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use merkle_tree_storage::{Fr, MerkleTreeStorage, PoseidonHasher};
+
+fn rebuild(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rebuild");
+    // A 2^20 leaf rebuild takes seconds, the default 100 samples would take most of an hour
+    group.sample_size(10).measurement_time(Duration::from_secs(30));
+
+    for depth in [16u32, 20] {
+        let leaves = (0..1u64 << depth).map(Fr::from);
+        let mut tree = MerkleTreeStorage::<PoseidonHasher>::from_leaves(depth, leaves).unwrap();
+        group.throughput(Throughput::Elements(1 << depth));
+        let size = format!("2^{}", depth);
+
+        group.bench_function(BenchmarkId::new("sequential", &size), |b| b.iter(|| tree.generate_merkle_tree()));
+        group.bench_function(BenchmarkId::new("parallel", &size), |b| b.iter(|| tree.generate_merkle_tree_parallel()));
+        // Hashes `depth` nodes per leaf instead of about one, at 2^20 a single run takes minutes
+        if depth <= 16 {
+            group.bench_function(BenchmarkId::new("insert_leaf", &size), |b| {
+                b.iter_batched(
+                    || MerkleTreeStorage::<PoseidonHasher>::new(depth),
+                    |mut tree| {
+                        for leaf in 0..1u64 << depth {
+                            tree.insert_leaf(Fr::from(leaf)).unwrap();
+                        }
+                        tree
+                    },
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, rebuild);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+//! Inputs of the circom circuits built on `RawMerkleTree` (`InsertLeaf`, `MerkleTreeUpdater`).
+
+use anyhow::Result;
+use ark_bn254::Fr;
+use ark_ff::Zero;
+
+use crate::proof::MerkleProof;
+
+/// `MAX_DEPTH` `InsertLeaf` and `MerkleTreeUpdater` are compiled with. Trees proven with them
+/// can be at most this deep, and their paths are padded to it.
+pub const CIRCUIT_MAX_DEPTH: usize = 20;
+
+/// A proof in the shape `RawMerkleTree` takes it: `pathIndices` packs the left/right bits,
+/// `depth` is the number of levels hashed, and `pathElements` is padded with zeros up to the
+/// `MAX_DEPTH` the circuit is compiled with. The nodes are the tree's own field elements.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CircuitPath {
+    pub leaf: Fr,
+    pub root: Fr,
+    pub path_indices: u64,
+    pub depth: u32,
+    pub path_elements: Vec<Fr>,
+}
+
+impl MerkleProof<Fr> {
+    pub fn to_circuit_path(&self, max_depth: usize) -> Result<CircuitPath> {
+        if self.depth() > max_depth {
+            return Err(anyhow::anyhow!(
+                "Proof of depth {} does not fit a circuit of depth {}",
+                self.depth(),
+                max_depth
+            ));
+        }
+        let mut path_elements = self.siblings.clone();
+        path_elements.resize(max_depth, Fr::zero());
+        Ok(CircuitPath {
+            leaf: self.leaf,
+            root: self.root,
+            path_indices: self.position as u64,
+            depth: self.depth() as u32,
+            path_elements,
+        })
+    }
+}
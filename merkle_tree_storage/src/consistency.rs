@@ -0,0 +1,51 @@
+use crate::{hasher::MerkleHasher, tree::MAX_DEPTH};
+
+/// Proof that the tree holding `new_size` leaves only appended to the one holding `old_size`,
+/// in the spirit of RFC 6962's consistency proofs for the zero-padded trees here.
+///
+/// `leaf` is the last leaf of the old tree and `siblings` its path in the new one. Siblings on
+/// the left cover slots both trees filled, siblings on the right cover slots that were still
+/// empty in the old tree. Folding the path with zeros on the right gives the old root, with the
+/// siblings as given the new one, so both roots commit to the same first `old_size` leaves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsistencyProof<N> {
+    pub old_size: usize,
+    pub new_size: usize,
+    pub leaf: N,
+    pub siblings: Vec<N>,
+}
+
+impl<N: Copy + Eq> ConsistencyProof<N> {
+    pub fn depth(&self) -> usize {
+        self.siblings.len()
+    }
+
+    /// Checks that `new_root`, the root at `new_size` leaves, extends `old_root`, the root at
+    /// `old_size` leaves. Every tree extends the empty one.
+    pub fn verify<H: MerkleHasher<Node = N>>(&self, hasher: &H, old_root: N, new_root: N) -> bool {
+        let depth = self.depth();
+        if depth == 0 || depth > MAX_DEPTH as usize || self.old_size > self.new_size || self.new_size > 1 << depth {
+            return false;
+        }
+        let mut zeros = vec![hasher.zero()];
+        for height in 0..depth {
+            zeros.push(hasher.hash(&zeros[height], &zeros[height]));
+        }
+        if self.old_size == 0 {
+            return old_root == zeros[depth];
+        }
+
+        let position = self.old_size - 1;
+        let (mut old_node, mut new_node) = (self.leaf, self.leaf);
+        for (height, sibling) in self.siblings.iter().enumerate() {
+            if (position >> height) & 1 == 1 {
+                old_node = hasher.hash(sibling, &old_node);
+                new_node = hasher.hash(sibling, &new_node);
+            } else {
+                old_node = hasher.hash(&old_node, &zeros[height]);
+                new_node = hasher.hash(&new_node, sibling);
+            }
+        }
+        old_node == old_root && new_node == new_root
+    }
+}
@@ -0,0 +1,27 @@
+use thiserror::Error;
+
+/// Why a tree operation failed. Shared by `MerkleTreeStorage`, `MmapMerkleTree` and
+/// `MerkleMountainRange`; the first two's other errors (I/O, file format) stay `anyhow`
+/// errors wrapping these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum TreeError {
+    #[error("Merkle tree is full")]
+    Full,
+    #[error("Leaf not found")]
+    LeafNotFound,
+    #[error("Merkle tree depth must be between 1 and {max}, not {0}", max = crate::MAX_DEPTH)]
+    InvalidDepth(u32),
+    /// Returned by `insert_unique_leaf`, `position` is the first slot holding the leaf.
+    #[error("Leaf is already stored at position {position}")]
+    DuplicateLeaf { position: usize },
+    #[error("Merkle tree holds {len} leaves, not {size}")]
+    InvalidSize { size: usize, len: usize },
+    #[error("Leaf {position} was added after size {size}")]
+    LeafAfterSize { position: usize, size: usize },
+    #[error("Old size {old_size} is larger than new size {new_size}")]
+    InvalidRange { old_size: usize, new_size: usize },
+    /// Returned by `MmapMerkleTree` for a node in its file that is not one, e.g. not a field
+    /// element.
+    #[error("Tree file holds an invalid node at height {height}, index {index}")]
+    CorruptNode { height: usize, index: usize },
+}
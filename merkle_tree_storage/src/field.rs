@@ -0,0 +1,46 @@
+//! Conversions of tree nodes. Nodes are `ark_bn254::Fr`, the field the circuits and the witness
+//! calculator work in, so a proof goes into a circuit without leaving the field; these are only
+//! for the edges where a node is stored or handed to an API taking integers. The hex form is
+//! `groth16_verifier::codec::Codec`'s.
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, PrimeField};
+use num_bigint::{BigInt, BigUint, Sign};
+
+/// The node as an integer, e.g. for `CircomBuilder::push_input`. Copies the limbs, no rounding
+/// through strings.
+pub fn fr_to_bigint(element: &Fr) -> BigInt {
+    BigUint::from(*element).into()
+}
+
+/// Inverse of [`fr_to_bigint`]. `None` for negative values and values not below the modulus.
+pub fn bigint_to_fr(value: &BigInt) -> Option<Fr> {
+    let (Sign::Plus | Sign::NoSign, magnitude) = value.to_bytes_be() else {
+        return None;
+    };
+    let bytes: [u8; 32] = left_pad(&magnitude)?;
+    fr_from_bytes(&bytes)
+}
+
+/// The node as 32 big-endian bytes.
+pub fn fr_to_bytes(element: &Fr) -> [u8; 32] {
+    let mut bytes = [0; 32];
+    bytes.copy_from_slice(&element.into_bigint().to_bytes_be());
+    bytes
+}
+
+/// Inverse of [`fr_to_bytes`]. `None` if the bytes are not below the modulus.
+pub fn fr_from_bytes(bytes: &[u8; 32]) -> Option<Fr> {
+    let mut limbs = [0u64; 4];
+    for (limb, chunk) in limbs.iter_mut().zip(bytes.rchunks_exact(8)) {
+        *limb = u64::from_be_bytes(chunk.try_into().unwrap());
+    }
+    Fr::from_bigint(ark_ff::BigInt(limbs))
+}
+
+fn left_pad(bytes: &[u8]) -> Option<[u8; 32]> {
+    let offset = 32usize.checked_sub(bytes.len())?;
+    let mut padded = [0; 32];
+    padded[offset..].copy_from_slice(bytes);
+    Some(padded)
+}
@@ -0,0 +1,75 @@
+use std::{fmt::Debug, hash::Hash};
+
+use ark_bn254::Fr;
+use ark_ff::Zero;
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+
+use crate::poseidon::Poseidon;
+
+/// Hashes two child nodes into their parent. Shared between threads when a tree is rebuilt in
+/// parallel.
+pub trait MerkleHasher: Sync {
+    type Node: Copy + Eq + Hash + Debug + Send + Sync;
+
+    /// Value of an empty leaf slot.
+    fn zero(&self) -> Self::Node;
+
+    fn hash(&self, left: &Self::Node, right: &Self::Node) -> Self::Node;
+}
+
+/// Poseidon(2) over BN254 with circomlib's round constants, the hash `RawMerkleTree` uses.
+pub struct PoseidonHasher(Poseidon);
+
+impl Default for PoseidonHasher {
+    fn default() -> Self {
+        // Two inputs is always supported
+        Self(Poseidon::new(2).unwrap())
+    }
+}
+
+impl MerkleHasher for PoseidonHasher {
+    type Node = Fr;
+
+    fn zero(&self) -> Fr {
+        Fr::zero()
+    }
+
+    fn hash(&self, left: &Fr, right: &Fr) -> Fr {
+        // Only fails for a number of inputs other than two
+        self.0.hash(&[*left, *right]).unwrap()
+    }
+}
+
+/// Keccak-256 of `left || right`, what Solidity's `keccak256(abi.encodePacked(left, right))`
+/// computes, so roots can be checked on EVM chains.
+#[derive(Default)]
+pub struct KeccakHasher;
+
+impl MerkleHasher for KeccakHasher {
+    type Node = [u8; 32];
+
+    fn zero(&self) -> [u8; 32] {
+        [0; 32]
+    }
+
+    fn hash(&self, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        Keccak256::new().chain_update(left).chain_update(right).finalize().into()
+    }
+}
+
+/// SHA-256 of `left || right`, for auditors recomputing roots with standard tooling.
+#[derive(Default)]
+pub struct Sha256Hasher;
+
+impl MerkleHasher for Sha256Hasher {
+    type Node = [u8; 32];
+
+    fn zero(&self) -> [u8; 32] {
+        [0; 32]
+    }
+
+    fn hash(&self, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+        Sha256::new().chain_update(left).chain_update(right).finalize().into()
+    }
+}
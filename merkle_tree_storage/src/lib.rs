@@ -0,0 +1,34 @@
+//! The append-only Merkle tree shared by the backend, the `#[derive(MerkleTree)]` models and
+//! `merkletree_generation`.
+//!
+//! A tree has a fixed depth and starts with every leaf zero, which is the tree the circuits'
+//! `RawMerkleTree` hashes. Leaves fill the slots left to right. Append-only logs with no
+//! capacity can use a `MerkleMountainRange` instead, and trees too large for RAM a
+//! `MmapMerkleTree`. A tree can be exported to a `Snapshot` file and imported again without a
+//! database.
+
+mod circuit;
+mod consistency;
+mod error;
+mod field;
+mod hasher;
+mod mmap;
+mod mmr;
+pub mod poseidon;
+mod postgres;
+mod proof;
+mod snapshot;
+mod tree;
+
+pub use ark_bn254::Fr;
+pub use circuit::{CircuitPath, CIRCUIT_MAX_DEPTH};
+pub use consistency::ConsistencyProof;
+pub use error::TreeError;
+pub use field::{bigint_to_fr, fr_from_bytes, fr_to_bigint, fr_to_bytes};
+pub use hasher::{KeccakHasher, MerkleHasher, PoseidonHasher, Sha256Hasher};
+pub use mmap::MmapMerkleTree;
+pub use mmr::{bag_peaks, MerkleMountainRange, MmrConsistencyProof, MmrInclusionProof};
+pub use postgres::persist_leaf;
+pub use proof::MerkleProof;
+pub use snapshot::{HasherId, Snapshot, SnapshotHasher, SNAPSHOT_VERSION};
+pub use tree::{MerkleTreeStorage, PreparedAppend, MAX_DEPTH};
@@ -0,0 +1,372 @@
+//! `MmapMerkleTree`: the tree of `MerkleTreeStorage` kept in a memory-mapped file instead of
+//! in RAM, for capacities where the layers and the leaf index would take gigabytes.
+//!
+//! The file is a header page followed by every layer as a flat array of 32 byte nodes,
+//! leaves first, and then an open-addressing index from leaf to position with twice as many
+//! slots as the tree has. It is created sparse, so only the pages of filled slots take disk
+//! space. Like `MerkleTreeStorage`, a layer only holds nodes above filled slots, the others
+//! read as the empty subtree of their height.
+//!
+//! An insert or a proof touches the O(depth) nodes on a path plus a few index slots. Nothing
+//! is cached in the process besides the empty subtree roots, the pages are the OS page cache's
+//! to keep or evict. A depth 32 tree maps about 576 GiB of address space.
+//!
+//! The leaf count in the header is written last, and leaves below it are never written again.
+//! An insert the process did not finish has still rewritten the nodes its path shares with the
+//! previous leaf, so `open` hashes that path again from the leaves. This covers the process
+//! dying, not the machine: the OS may write the pages back in any order, so a file is only
+//! consistent after a power loss if it was `flush`ed after its last insert.
+
+use std::{fs::File, path::Path};
+
+use anyhow::Result;
+use memmap2::MmapMut;
+
+use crate::{
+    consistency::ConsistencyProof, error::TreeError, hasher::PoseidonHasher, proof::MerkleProof,
+    snapshot::SnapshotHasher, tree::MAX_DEPTH,
+};
+
+const MAGIC: &[u8; 4] = b"MTMM";
+const VERSION: u16 = 1;
+// A whole page, so the layers start page aligned
+const HEADER_LEN: usize = 4096;
+const LEN_OFFSET: usize = 16;
+// Leaf bytes, then its position plus one, zero marking an empty slot
+const INDEX_SLOT_LEN: usize = 40;
+
+pub struct MmapMerkleTree<H: SnapshotHasher = PoseidonHasher> {
+    hasher: H,
+    depth: u32,
+    len: usize,
+    file: File,
+    map: MmapMut,
+    zeros: Vec<H::Node>, // Root of an empty subtree of each height
+}
+
+impl<H: SnapshotHasher + Default> MmapMerkleTree<H> {
+    /// Creates an empty tree with 2^depth leaf slots at `path`, replacing any file there.
+    pub fn create(path: impl AsRef<Path>, depth: u32) -> Result<Self> {
+        Self::create_with_hasher(path, depth, H::default())
+    }
+
+    /// Opens a tree `create` made at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open_with_hasher(path, H::default())
+    }
+}
+
+impl<H: SnapshotHasher> MmapMerkleTree<H> {
+    pub fn create_with_hasher(path: impl AsRef<Path>, depth: u32, hasher: H) -> Result<Self> {
+        if !(1..=MAX_DEPTH).contains(&depth) {
+            return Err(TreeError::InvalidDepth(depth).into());
+        }
+        let file = File::options().read(true).write(true).create(true).truncate(true).open(path)?;
+        let mut tree = Self::map(file, depth, 0, hasher)?;
+        tree.write_header();
+        Ok(tree)
+    }
+
+    pub fn open_with_hasher(path: impl AsRef<Path>, hasher: H) -> Result<Self> {
+        let file = File::options().read(true).write(true).open(path)?;
+        let mut header = [0u8; LEN_OFFSET + 8];
+        std::io::Read::read_exact(&mut &file, &mut header)
+            .map_err(|_| anyhow::anyhow!("Not a memory-mapped Merkle tree"))?;
+        if &header[..4] != MAGIC {
+            return Err(anyhow::anyhow!("Not a memory-mapped Merkle tree"));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != VERSION {
+            return Err(anyhow::anyhow!("Unsupported tree file version {}", version));
+        }
+        if header[6] != H::ID as u8 {
+            return Err(anyhow::anyhow!("Tree file was not built with {}", H::ID.name()));
+        }
+        let depth = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        let len = u64::from_le_bytes(header[LEN_OFFSET..LEN_OFFSET + 8].try_into().unwrap()) as usize;
+        if !(1..=MAX_DEPTH).contains(&depth) || len > 1 << depth {
+            return Err(anyhow::anyhow!("Tree file of {} leaves at depth {} is corrupt", len, depth));
+        }
+        if file.metadata()?.len() != file_len(depth) as u64 {
+            return Err(anyhow::anyhow!("Tree file is truncated"));
+        }
+        let mut tree = Self::map(file, depth, len, hasher)?;
+        // Undoes an insert that did not finish
+        if let Some(last) = tree.len.checked_sub(1) {
+            let leaf = tree.node(0, last)?;
+            tree.write_path(last, leaf)?;
+        }
+        Ok(tree)
+    }
+
+    fn map(file: File, depth: u32, len: usize, hasher: H) -> Result<Self> {
+        file.set_len(file_len(depth) as u64)?;
+        // Only this process should write the file while the tree is open
+        let map = unsafe { MmapMut::map_mut(&file)? };
+        let mut zeros = vec![hasher.zero()];
+        for height in 0..depth as usize {
+            zeros.push(hasher.hash(&zeros[height], &zeros[height]));
+        }
+        Ok(Self { hasher, depth, len, file, map, zeros })
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Number of leaf slots, 2^depth.
+    pub fn capacity(&self) -> usize {
+        1 << self.depth
+    }
+
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Number of filled slots.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Leaf in slot `index`, if it is filled.
+    pub fn leaf_at(&self, index: usize) -> Result<Option<H::Node>, TreeError> {
+        (index < self.len).then(|| self.node(0, index)).transpose()
+    }
+
+    /// First slot holding `leaf`. The index only keeps the first slot of a leaf stored more
+    /// than once.
+    pub fn position_of(&self, leaf: &H::Node) -> Option<usize> {
+        let bytes = H::node_to_bytes(leaf);
+        for offset in index_slots(self.depth, &bytes) {
+            let slot = &self.map[offset..offset + INDEX_SLOT_LEN];
+            let stored = u64::from_le_bytes(slot[32..].try_into().unwrap()) as usize;
+            if stored == 0 {
+                return None;
+            }
+            if slot[..32] == bytes {
+                // An insert that did not finish may have indexed a slot past the end, or one
+                // another leaf was stored in since
+                return self.holds(stored - 1, &bytes).then_some(stored - 1);
+            }
+        }
+        None
+    }
+
+    pub fn root(&self) -> Result<H::Node, TreeError> {
+        self.node(self.depth as usize, 0)
+    }
+
+    /// Root the tree had when it held its first `size` leaves.
+    pub fn root_at(&self, size: usize) -> Result<H::Node, TreeError> {
+        self.check_size(size)?;
+        self.node_at(self.depth as usize, 0, size)
+    }
+
+    /// Resets the Merkle tree, clearing all stored leaves. The file is truncated and extended
+    /// again, which gives the disk space back.
+    pub fn reset_tree(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.set_len(file_len(self.depth) as u64)?;
+        self.map = unsafe { MmapMut::map_mut(&self.file)? };
+        self.len = 0;
+        self.write_header();
+        Ok(())
+    }
+
+    /// Appends a leaf in the next empty slot and returns its position. A leaf stored twice is
+    /// indexed at the slot it was first stored at.
+    pub fn insert_leaf(&mut self, leaf: H::Node) -> Result<usize, TreeError> {
+        let position = self.len;
+        if position >= self.capacity() {
+            return Err(TreeError::Full);
+        }
+        self.write_path(position, leaf)?;
+        self.len += 1;
+        self.index_leaf(&leaf, position);
+        // Last, see the module docs for an insert that does not get here
+        self.map[LEN_OFFSET..LEN_OFFSET + 8].copy_from_slice(&(self.len as u64).to_le_bytes());
+        Ok(position)
+    }
+
+    /// Like `insert_leaf`, but fails with `TreeError::DuplicateLeaf` if `leaf` is already stored.
+    pub fn insert_unique_leaf(&mut self, leaf: H::Node) -> Result<usize, TreeError> {
+        if let Some(position) = self.position_of(&leaf) {
+            return Err(TreeError::DuplicateLeaf { position });
+        }
+        self.insert_leaf(leaf)
+    }
+
+    /// Writes the changes to the file, without waiting for the OS to.
+    pub fn flush(&self) -> Result<()> {
+        Ok(self.map.flush()?)
+    }
+
+    /// Generates a Merkle proof for a given leaf, in the first slot holding it.
+    pub fn generate_merkle_proof(&self, leaf: H::Node) -> Result<Option<MerkleProof<H::Node>>, TreeError> {
+        self.position_of(&leaf).map(|position| self.proof_at(position, leaf, self.len)).transpose()
+    }
+
+    /// Proof for the leaf in slot `position`, e.g. a later copy of a leaf stored more than once.
+    pub fn generate_merkle_proof_at(&self, position: usize) -> Result<Option<MerkleProof<H::Node>>, TreeError> {
+        match self.leaf_at(position)? {
+            Some(leaf) => self.proof_at(position, leaf, self.len).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Proof for `leaf` against the root the tree had at `size` leaves.
+    pub fn inclusion_proof_at(&self, leaf: H::Node, size: usize) -> Result<MerkleProof<H::Node>, TreeError> {
+        self.check_size(size)?;
+        let position = self.position_of(&leaf).ok_or(TreeError::LeafNotFound)?;
+        if position >= size {
+            return Err(TreeError::LeafAfterSize { position, size });
+        }
+        self.proof_at(position, leaf, size)
+    }
+
+    /// Proof that the next empty slot holds zero, for proving an append to it.
+    pub fn append_proof(&self) -> Result<MerkleProof<H::Node>, TreeError> {
+        if self.len >= self.capacity() {
+            return Err(TreeError::Full);
+        }
+        self.proof_at(self.len, self.zeros[0], self.len)
+    }
+
+    /// Proof that the tree at `new_size` leaves extends the one at `old_size`, see
+    /// `ConsistencyProof`.
+    pub fn consistency_proof(
+        &self,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<ConsistencyProof<H::Node>, TreeError> {
+        self.check_size(new_size)?;
+        if old_size > new_size {
+            return Err(TreeError::InvalidRange { old_size, new_size });
+        }
+        let position = old_size.saturating_sub(1);
+        let siblings = (0..self.depth as usize)
+            .map(|height| self.node_at(height, (position >> height) ^ 1, new_size))
+            .collect::<Result<_, _>>()?;
+        Ok(ConsistencyProof { old_size, new_size, leaf: self.node_at(0, position, old_size)?, siblings })
+    }
+
+    fn proof_at(&self, position: usize, leaf: H::Node, size: usize) -> Result<MerkleProof<H::Node>, TreeError> {
+        let siblings = (0..self.depth as usize)
+            .map(|height| self.node_at(height, (position >> height) ^ 1, size))
+            .collect::<Result<_, _>>()?;
+        Ok(MerkleProof { leaf, position, siblings, root: self.node_at(self.depth as usize, 0, size)? })
+    }
+
+    // Fails with `TreeError::CorruptNode` if the file holds bytes that are not a node
+    fn node(&self, height: usize, index: usize) -> Result<H::Node, TreeError> {
+        // Nodes above a filled slot, `ceil(len / 2^height)` of them
+        if index >= (self.len + (1 << height) - 1) >> height {
+            return Ok(self.zeros[height]);
+        }
+        let offset = self.node_offset(height, index);
+        let bytes: &[u8; 32] = self.map[offset..offset + 32].try_into().unwrap();
+        H::node_from_bytes(bytes).ok_or(TreeError::CorruptNode { height, index })
+    }
+
+    // Node as it was when the tree held `size` leaves, see `MerkleTreeStorage::node_at`
+    fn node_at(&self, height: usize, index: usize, size: usize) -> Result<H::Node, TreeError> {
+        let (first, end) = (index << height, (index + 1) << height);
+        if end <= size {
+            return self.node(height, index);
+        }
+        if first >= size {
+            return Ok(self.zeros[height]);
+        }
+        Ok(self.hasher.hash(&self.node_at(height - 1, 2 * index, size)?, &self.node_at(height - 1, 2 * index + 1, size)?))
+    }
+
+    // Writes `leaf` to slot `position` and hashes the path above it again. Everything is read
+    // first, so a corrupt node fails before anything is written.
+    fn write_path(&mut self, position: usize, leaf: H::Node) -> Result<(), TreeError> {
+        let mut path = Vec::with_capacity(self.depth as usize);
+        let mut node = leaf;
+        for height in 0..self.depth as usize {
+            let index = position >> height;
+            // Slots after `position` are empty, so a right sibling reads as zero
+            let sibling = self.node(height, index ^ 1)?;
+            node = if index & 1 == 1 { self.hasher.hash(&sibling, &node) } else { self.hasher.hash(&node, &sibling) };
+            path.push(node);
+        }
+        self.write_node(0, position, &leaf);
+        for (height, node) in path.iter().enumerate() {
+            self.write_node(height + 1, position >> (height + 1), node);
+        }
+        Ok(())
+    }
+
+    // Whether filled slot `position` holds the leaf with these bytes
+    fn holds(&self, position: usize, bytes: &[u8; 32]) -> bool {
+        let offset = self.node_offset(0, position);
+        position < self.len && self.map[offset..offset + 32] == bytes[..]
+    }
+
+    fn write_node(&mut self, height: usize, index: usize, node: &H::Node) {
+        let offset = self.node_offset(height, index);
+        self.map[offset..offset + 32].copy_from_slice(&H::node_to_bytes(node));
+    }
+
+    // Layer `h` holds 2^(depth - h) nodes and comes after the ones below it
+    fn node_offset(&self, height: usize, index: usize) -> usize {
+        let depth = self.depth as usize;
+        HEADER_LEN + 32 * ((1 << (depth + 1)) - (1 << (depth + 1 - height)) + index)
+    }
+
+    fn index_leaf(&mut self, leaf: &H::Node, position: usize) {
+        let bytes = H::node_to_bytes(leaf);
+        // The index has twice as many slots as the tree, so there always is an empty one
+        for offset in index_slots(self.depth, &bytes) {
+            let slot = &self.map[offset..offset + INDEX_SLOT_LEN];
+            let stored = u64::from_le_bytes(slot[32..].try_into().unwrap()) as usize;
+            // An entry an insert that did not finish left behind is replaced, see `position_of`
+            let stale = stored > 0 && slot[..32] == bytes && !self.holds(stored - 1, &bytes);
+            if stored == 0 || stale {
+                let slot = &mut self.map[offset..offset + INDEX_SLOT_LEN];
+                slot[..32].copy_from_slice(&bytes);
+                slot[32..].copy_from_slice(&(position as u64 + 1).to_le_bytes());
+                return;
+            }
+            if slot[..32] == bytes {
+                return;
+            }
+        }
+    }
+
+    fn write_header(&mut self) {
+        self.map[..4].copy_from_slice(MAGIC);
+        self.map[4..6].copy_from_slice(&VERSION.to_le_bytes());
+        self.map[6] = H::ID as u8;
+        self.map[8..12].copy_from_slice(&self.depth.to_le_bytes());
+        self.map[LEN_OFFSET..LEN_OFFSET + 8].copy_from_slice(&(self.len as u64).to_le_bytes());
+    }
+
+    fn check_size(&self, size: usize) -> Result<(), TreeError> {
+        if size > self.len {
+            return Err(TreeError::InvalidSize { size, len: self.len });
+        }
+        Ok(())
+    }
+}
+
+// Header, 2^(depth + 1) - 1 nodes and 2^(depth + 1) index slots
+fn file_len(depth: u32) -> usize {
+    HEADER_LEN + 32 * ((1 << (depth + 1)) - 1) + INDEX_SLOT_LEN * (1 << (depth + 1))
+}
+
+// Offsets of the index slots to probe for `leaf`, linearly from one picked by its last 8 bytes
+fn index_slots(depth: u32, leaf: &[u8; 32]) -> impl Iterator<Item = usize> {
+    let bits = depth + 1;
+    let key = u64::from_le_bytes(leaf[24..].try_into().unwrap());
+    let start = (key.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (64 - bits)) as usize;
+    let slots = 1usize << bits;
+    let base = HEADER_LEN + 32 * (slots - 1);
+    (0..slots).map(move |probe| base + INDEX_SLOT_LEN * ((start + probe) & (slots - 1)))
+}
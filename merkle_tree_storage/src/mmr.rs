@@ -0,0 +1,249 @@
+use crate::{
+    error::TreeError,
+    hasher::{MerkleHasher, PoseidonHasher},
+};
+
+/// Merkle Mountain Range: an append-only accumulator with no capacity, for logs such as
+/// merchant record histories.
+///
+/// A range of `size` leaves is a list of perfect trees ("peaks"), one of height `h` for each
+/// bit `h` set in `size`, largest first. The root bags the peaks from the right:
+/// `H(p0, H(p1, ... H(pk-1, pk)))`, and is the hasher's zero when empty. Proofs carry the
+/// size they are for, since the root alone does not encode it.
+pub struct MerkleMountainRange<H: MerkleHasher = PoseidonHasher> {
+    hasher: H,
+    // `layers[h][i]` is the root of the perfect tree over leaves `i * 2^h .. (i + 1) * 2^h`.
+    // A range of `size` leaves uses the first `size >> h` nodes of each layer, so every
+    // earlier size can still be proven.
+    layers: Vec<Vec<H::Node>>,
+}
+
+/// Proof that `leaf` is leaf `leaf_index` of the range of `size` leaves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MmrInclusionProof<N> {
+    pub leaf_index: usize,
+    pub size: usize,
+    pub leaf: N,
+    /// Path from the leaf to the peak it is under, lowest first.
+    pub siblings: Vec<N>,
+    /// Every peak of the range, largest first.
+    pub peaks: Vec<N>,
+}
+
+/// Proof that the range of `new_size` leaves extends the one of `old_size` leaves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MmrConsistencyProof<N> {
+    pub old_size: usize,
+    pub new_size: usize,
+    pub old_peaks: Vec<N>,
+    /// For each old peak, the path from it to the new peak it is under.
+    pub paths: Vec<Vec<N>>,
+    pub new_peaks: Vec<N>,
+}
+
+impl<H: MerkleHasher + Default> MerkleMountainRange<H> {
+    pub fn new() -> Self {
+        Self::with_hasher(H::default())
+    }
+
+    pub fn from_leaves(leaves: impl IntoIterator<Item = H::Node>) -> Self {
+        let mut range = Self::new();
+        for leaf in leaves {
+            range.append(leaf);
+        }
+        range
+    }
+}
+
+impl<H: MerkleHasher + Default> Default for MerkleMountainRange<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: MerkleHasher> MerkleMountainRange<H> {
+    pub fn with_hasher(hasher: H) -> Self {
+        Self { hasher, layers: vec![Vec::new()] }
+    }
+
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Number of leaves appended.
+    pub fn len(&self) -> usize {
+        self.layers[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn leaf(&self, index: usize) -> Option<H::Node> {
+        self.layers[0].get(index).copied()
+    }
+
+    /// Appends a leaf and returns its index. Merges the peaks it completes, O(log n).
+    pub fn append(&mut self, leaf: H::Node) -> usize {
+        let index = self.len();
+        self.layers[0].push(leaf);
+        let mut height = 0;
+        while self.layers[height].len().is_multiple_of(2) {
+            let layer = &self.layers[height];
+            let parent = self.hasher.hash(&layer[layer.len() - 2], &layer[layer.len() - 1]);
+            if height + 1 == self.layers.len() {
+                self.layers.push(Vec::new());
+            }
+            self.layers[height + 1].push(parent);
+            height += 1;
+        }
+        index
+    }
+
+    pub fn root(&self) -> H::Node {
+        bag_peaks(&self.hasher, &self.peaks_at(self.len()))
+    }
+
+    /// Root the range had when it held `size` leaves.
+    pub fn root_at(&self, size: usize) -> Result<H::Node, TreeError> {
+        self.check_size(size)?;
+        Ok(bag_peaks(&self.hasher, &self.peaks_at(size)))
+    }
+
+    /// Peaks of the range at `size` leaves, largest first.
+    pub fn peaks(&self, size: usize) -> Result<Vec<H::Node>, TreeError> {
+        self.check_size(size)?;
+        Ok(self.peaks_at(size))
+    }
+
+    /// Proof of leaf `leaf_index` against the root at `size` leaves, which can be any size
+    /// the leaf was already part of.
+    pub fn inclusion_proof(&self, leaf_index: usize, size: usize) -> Result<MmrInclusionProof<H::Node>, TreeError> {
+        self.check_size(size)?;
+        if leaf_index >= size {
+            return Err(TreeError::LeafAfterSize { position: leaf_index, size });
+        }
+        let (_, peak_height, _) = peak_of(leaf_index, size);
+        Ok(MmrInclusionProof {
+            leaf_index,
+            size,
+            leaf: self.layers[0][leaf_index],
+            siblings: self.path(0, leaf_index, peak_height),
+            peaks: self.peaks_at(size),
+        })
+    }
+
+    /// Proof that the range at `new_size` leaves only appended to the one at `old_size`.
+    pub fn consistency_proof(&self, old_size: usize, new_size: usize) -> Result<MmrConsistencyProof<H::Node>, TreeError> {
+        self.check_size(new_size)?;
+        if old_size > new_size {
+            return Err(TreeError::InvalidRange { old_size, new_size });
+        }
+        let paths = peak_positions(old_size)
+            .map(|(height, index)| {
+                let (_, new_height, _) = peak_of(index << height, new_size);
+                self.path(height, index, new_height)
+            })
+            .collect();
+        Ok(MmrConsistencyProof {
+            old_size,
+            new_size,
+            old_peaks: self.peaks_at(old_size),
+            paths,
+            new_peaks: self.peaks_at(new_size),
+        })
+    }
+
+    fn peaks_at(&self, size: usize) -> Vec<H::Node> {
+        peak_positions(size).map(|(height, index)| self.layers[height][index]).collect()
+    }
+
+    // Siblings of node `index` at `height` on the way up to `top`
+    fn path(&self, height: usize, index: usize, top: usize) -> Vec<H::Node> {
+        (height..top).map(|h| self.layers[h][(index >> (h - height)) ^ 1]).collect()
+    }
+
+    fn check_size(&self, size: usize) -> Result<(), TreeError> {
+        if size > self.len() {
+            return Err(TreeError::InvalidSize { size, len: self.len() });
+        }
+        Ok(())
+    }
+}
+
+impl<N: Copy + Eq> MmrInclusionProof<N> {
+    /// Checks the proof against the root of the range at `self.size` leaves.
+    pub fn verify<H: MerkleHasher<Node = N>>(&self, hasher: &H, root: N) -> bool {
+        if self.leaf_index >= self.size || self.peaks.len() != self.size.count_ones() as usize {
+            return false;
+        }
+        let (peak, height, local_index) = peak_of(self.leaf_index, self.size);
+        if self.siblings.len() != height {
+            return false;
+        }
+        let computed = fold_path(hasher, self.leaf, local_index, &self.siblings);
+        computed == self.peaks[peak] && bag_peaks(hasher, &self.peaks) == root
+    }
+}
+
+impl<N: Copy + Eq> MmrConsistencyProof<N> {
+    /// Checks that `new_root`, the root at `new_size` leaves, extends `old_root`, the root at
+    /// `old_size` leaves.
+    pub fn verify<H: MerkleHasher<Node = N>>(&self, hasher: &H, old_root: N, new_root: N) -> bool {
+        if self.old_size > self.new_size
+            || self.old_peaks.len() != self.old_size.count_ones() as usize
+            || self.new_peaks.len() != self.new_size.count_ones() as usize
+            || self.paths.len() != self.old_peaks.len()
+        {
+            return false;
+        }
+        let old_positions = peak_positions(self.old_size);
+        for (((height, index), old_peak), path) in old_positions.zip(&self.old_peaks).zip(&self.paths) {
+            let (new_peak, new_height, _) = peak_of(index << height, self.new_size);
+            if path.len() != new_height - height {
+                return false;
+            }
+            // Index of the old peak among the nodes of its height under the new peak
+            let local_index = index & ((1 << (new_height - height)) - 1);
+            if fold_path(hasher, *old_peak, local_index, path) != self.new_peaks[new_peak] {
+                return false;
+            }
+        }
+        bag_peaks(hasher, &self.old_peaks) == old_root && bag_peaks(hasher, &self.new_peaks) == new_root
+    }
+}
+
+/// `H(p0, H(p1, ... H(pk-1, pk)))`, or zero without peaks.
+pub fn bag_peaks<H: MerkleHasher>(hasher: &H, peaks: &[H::Node]) -> H::Node {
+    let Some((last, rest)) = peaks.split_last() else {
+        return hasher.zero();
+    };
+    rest.iter().rev().fold(*last, |bag, peak| hasher.hash(peak, &bag))
+}
+
+// (height, index in its layer) of each peak of a range of `size` leaves, largest first
+fn peak_positions(size: usize) -> impl Iterator<Item = (usize, usize)> {
+    (0..usize::BITS as usize).rev().filter(move |h| (size >> h) & 1 == 1).map(move |h| (h, (size >> h) - 1))
+}
+
+// (peak number, peak height, index under the peak) of leaf `leaf_index` in a range of `size`
+fn peak_of(leaf_index: usize, size: usize) -> (usize, usize, usize) {
+    let mut offset = 0;
+    for (peak, (height, _)) in peak_positions(size).enumerate() {
+        if leaf_index < offset + (1 << height) {
+            return (peak, height, leaf_index - offset);
+        }
+        offset += 1 << height;
+    }
+    unreachable!("leaf {} is not in a range of {} leaves", leaf_index, size)
+}
+
+fn fold_path<H: MerkleHasher>(hasher: &H, node: H::Node, index: usize, siblings: &[H::Node]) -> H::Node {
+    siblings.iter().enumerate().fold(node, |node, (i, sibling)| {
+        if (index >> i) & 1 == 1 {
+            hasher.hash(sibling, &node)
+        } else {
+            hasher.hash(&node, sibling)
+        }
+    })
+}
@@ -0,0 +1,75 @@
+//! circomlib's `Poseidon(n)` over BN254, hashing `ark_bn254::Fr` in place.
+//!
+//! The round constants and MDS matrices are the ones circomlib ships, taken from
+//! `light_poseidon`. Its hasher keeps its state in `&mut self`, so the permutation is run here
+//! on a local state and one instance can be shared by readers of the tree.
+
+use anyhow::Result;
+use ark_bn254::Fr;
+use ark_ff::{Field, Zero};
+use light_poseidon::{parameters::bn254_x5::get_poseidon_parameters, PoseidonParameters};
+
+/// Most inputs circomlib's `Poseidon` takes.
+pub const MAX_INPUTS: usize = 12;
+
+pub struct Poseidon {
+    params: PoseidonParameters<Fr>,
+}
+
+impl Poseidon {
+    /// Hasher for `inputs` field elements, circomlib's `Poseidon(inputs)`.
+    pub fn new(inputs: usize) -> Result<Self> {
+        if !(1..=MAX_INPUTS).contains(&inputs) {
+            return Err(anyhow::anyhow!("Poseidon takes between 1 and {} inputs, not {}", MAX_INPUTS, inputs));
+        }
+        let params = get_poseidon_parameters::<Fr>(inputs as u8 + 1)
+            .map_err(|e| anyhow::anyhow!("No Poseidon parameters for {} inputs: {}", inputs, e))?;
+        Ok(Self { params })
+    }
+
+    /// Number of inputs this hasher takes.
+    pub fn inputs(&self) -> usize {
+        self.params.width - 1
+    }
+
+    pub fn hash(&self, inputs: &[Fr]) -> Result<Fr> {
+        if inputs.len() != self.inputs() {
+            return Err(anyhow::anyhow!("Poseidon({}) cannot hash {} inputs", self.inputs(), inputs.len()));
+        }
+        // circomlib starts the state with a zero capacity element
+        let mut state = Vec::with_capacity(self.params.width);
+        state.push(Fr::zero());
+        state.extend_from_slice(inputs);
+        let mut mixed = vec![Fr::zero(); self.params.width];
+
+        let half_full = self.params.full_rounds / 2;
+        let partial_end = half_full + self.params.partial_rounds;
+        for round in 0..self.params.full_rounds + self.params.partial_rounds {
+            self.add_round_constants(&mut state, round);
+            if round < half_full || round >= partial_end {
+                for element in state.iter_mut() {
+                    *element = element.pow([self.params.alpha]);
+                }
+            } else {
+                state[0] = state[0].pow([self.params.alpha]);
+            }
+            self.mix(&state, &mut mixed);
+            std::mem::swap(&mut state, &mut mixed);
+        }
+        Ok(state[0])
+    }
+
+    fn add_round_constants(&self, state: &mut [Fr], round: usize) {
+        let constants = &self.params.ark[round * self.params.width..];
+        for (element, constant) in state.iter_mut().zip(constants) {
+            *element += constant;
+        }
+    }
+
+    // Into a buffer the caller keeps, so a hash allocates once and not once per round
+    fn mix(&self, state: &[Fr], mixed: &mut [Fr]) {
+        for (element, row) in mixed.iter_mut().zip(&self.params.mds) {
+            *element = row.iter().zip(state).map(|(m, element)| *m * element).sum();
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Trees kept in a storage table (`CoreIdTree`, `MerchantJoinTree`, ...): a single row whose
+//! `leaves` JSONB is the array of leaf hexes (`Codec::to_hex`) in slot order, so a leaf stored
+//! twice keeps both slots. Rows written before that map each leaf's hex to its position, and
+//! are still read, as are hexes stored without the `0x` prefix.
+
+use std::collections::HashMap;
+
+use anyhow::Result;
+use ark_bn254::Fr;
+use groth16_verifier::codec::Codec;
+use serde_json::Value;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::{
+    error::TreeError,
+    hasher::PoseidonHasher,
+    tree::MerkleTreeStorage,
+};
+
+impl MerkleTreeStorage<PoseidonHasher> {
+    /// Loads the tree kept in `table`.
+    pub async fn load(pool: &PgPool, table: &str, depth: u32) -> Result<Self> {
+        check_table_name(table)?;
+        let row: Option<(Value, i64)> = sqlx::query_as(&format!(
+            r#"SELECT leaves, capacity FROM {} ORDER BY storage_id LIMIT 1"#,
+            table
+        ))
+        .fetch_optional(pool)
+        .await?;
+
+        let Some((leaves, capacity)) = row else {
+            return Ok(Self::new(depth));
+        };
+        Self::from_stored(table, depth, leaves, capacity)
+    }
+
+    /// Rebuilds a tree from a row of a storage table, e.g. one read from a database export.
+    /// `table` only names the tree in errors.
+    pub fn from_stored(table: &str, depth: u32, leaves: Value, capacity: i64) -> Result<Self> {
+        let expected = 1usize << depth;
+        if capacity as usize != expected {
+            return Err(anyhow::anyhow!(
+                "{} has capacity {}, depth {} needs {}",
+                table, capacity, depth, expected
+            ));
+        }
+        Ok(Self::from_leaves(depth, parse_leaves(table, leaves)?)?)
+    }
+
+    /// The `leaves` and `capacity` of the row `store` writes for this tree.
+    pub fn to_stored(&self) -> (Value, i64) {
+        let leaves = self.leaves().iter().map(|leaf| Value::from(leaf.to_hex())).collect();
+        (Value::Array(leaves), self.capacity() as i64)
+    }
+
+    /// Replaces the tree kept in `table` with this one, e.g. to seed it from a snapshot.
+    pub async fn store(&self, pool: &PgPool, table: &str) -> Result<()> {
+        check_table_name(table)?;
+        let (leaves, capacity) = self.to_stored();
+
+        let mut tx = pool.begin().await?;
+        sqlx::query(&format!(r#"LOCK TABLE {} IN EXCLUSIVE MODE"#, table))
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(&format!(r#"DELETE FROM {}"#, table)).execute(&mut *tx).await?;
+        sqlx::query(&format!(r#"INSERT INTO {} (leaves, capacity) VALUES ($1, $2)"#, table))
+            .bind(leaves)
+            .bind(capacity)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// Appends `leaf` to the tree in `table` within `tx` and returns its position, so the leaf is
+/// stored together with whatever else `tx` writes, or not at all. A leaf that is already
+/// stored is appended again in the next slot, as `MerkleTreeStorage::insert_leaf` does.
+pub async fn persist_leaf(tx: &mut Transaction<'_, Postgres>, table: &str, depth: u32, leaf: Fr) -> Result<usize> {
+    check_table_name(table)?;
+    let capacity = MerkleTreeStorage::<PoseidonHasher>::new(depth).capacity();
+    let key = leaf.to_hex();
+
+    // Writers queue up here until `tx` ends, so positions are handed out one at a time
+    sqlx::query(&format!(r#"LOCK TABLE {} IN EXCLUSIVE MODE"#, table))
+        .execute(&mut **tx)
+        .await?;
+    let row: Option<(i32, Value)> = sqlx::query_as(&format!(
+        r#"SELECT storage_id, leaves FROM {} ORDER BY storage_id LIMIT 1"#,
+        table
+    ))
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let position = match row {
+        None => {
+            insert_first_leaf(tx, table, &key, capacity).await?;
+            0
+        }
+        Some((storage_id, stored)) => {
+            let legacy = stored.is_object();
+            let mut leaves = parse_leaves(table, stored)?;
+            if leaves.len() >= capacity {
+                return Err(TreeError::Full.into());
+            }
+            let position = leaves.len();
+            if legacy {
+                // Rewrite a position map as an array on its first append
+                leaves.push(leaf);
+                let leaves: Vec<Value> = leaves.iter().map(|leaf| Value::from(leaf.to_hex())).collect();
+                sqlx::query(&format!(r#"UPDATE {} SET leaves = $1 WHERE storage_id = $2"#, table))
+                    .bind(Value::Array(leaves))
+                    .bind(storage_id)
+                    .execute(&mut **tx)
+                    .await?;
+            } else {
+                sqlx::query(&format!(r#"UPDATE {} SET leaves = leaves || to_jsonb($1::TEXT) WHERE storage_id = $2"#, table))
+                    .bind(&key)
+                    .bind(storage_id)
+                    .execute(&mut **tx)
+                    .await?;
+            }
+            position
+        }
+    };
+    Ok(position)
+}
+
+async fn insert_first_leaf(tx: &mut Transaction<'_, Postgres>, table: &str, key: &str, capacity: usize) -> Result<()> {
+    sqlx::query(&format!(
+        r#"INSERT INTO {} (leaves, capacity) VALUES (jsonb_build_array($1::TEXT), $2)"#,
+        table
+    ))
+    .bind(key)
+    .bind(capacity as i64)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+// Leaves in slot order, from an array or from a legacy hex -> position map
+fn parse_leaves(table: &str, leaves: Value) -> Result<Vec<Fr>> {
+    let hexes: Vec<String> = if leaves.is_object() {
+        let positions: HashMap<String, usize> = serde_json::from_value(leaves)
+            .map_err(|e| anyhow::anyhow!("Invalid leaves in {}: {}", table, e))?;
+        let mut ordered = vec![None; positions.len()];
+        for (hex, position) in positions {
+            let slot = ordered
+                .get_mut(position)
+                .ok_or_else(|| anyhow::anyhow!("Leaf positions in {} are not contiguous", table))?;
+            *slot = Some(hex);
+        }
+        ordered
+            .into_iter()
+            .collect::<Option<_>>()
+            .ok_or_else(|| anyhow::anyhow!("Leaf positions in {} are not contiguous", table))?
+    } else {
+        serde_json::from_value(leaves).map_err(|e| anyhow::anyhow!("Invalid leaves in {}: {}", table, e))?
+    };
+    hexes
+        .iter()
+        .map(|hex| Fr::from_hex(hex).map_err(|e| anyhow::anyhow!("Invalid leaf {} in {}: {}", hex, table, e)))
+        .collect()
+}
+
+// Table names are spliced into the SQL, so only plain identifiers are accepted
+fn check_table_name(table: &str) -> Result<()> {
+    let valid = table.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(anyhow::anyhow!("{:?} is not a valid storage table name", table));
+    }
+    Ok(())
+}
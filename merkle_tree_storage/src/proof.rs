@@ -0,0 +1,39 @@
+use crate::hasher::MerkleHasher;
+
+/// Path from a leaf to the root. `siblings[i]` is the node beside the path at height `i`, and
+/// bit `i` of `position` is set where the path node is the right child.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof<N> {
+    pub leaf: N,
+    pub position: usize,
+    pub siblings: Vec<N>,
+    pub root: N,
+}
+
+impl<N: Copy + Eq> MerkleProof<N> {
+    pub fn depth(&self) -> usize {
+        self.siblings.len()
+    }
+
+    /// Bit `i` of `position`: `true` where the path node is the right child.
+    pub fn indices(&self) -> Vec<bool> {
+        (0..self.depth()).map(|i| (self.position >> i) & 1 == 1).collect()
+    }
+
+    /// Root of the same path with `leaf` in the slot, e.g. the root after filling an empty one.
+    pub fn root_with_leaf<H: MerkleHasher<Node = N>>(&self, hasher: &H, leaf: N) -> N {
+        let mut node = leaf;
+        for (i, sibling) in self.siblings.iter().enumerate() {
+            node = if (self.position >> i) & 1 == 1 {
+                hasher.hash(sibling, &node)
+            } else {
+                hasher.hash(&node, sibling)
+            };
+        }
+        node
+    }
+
+    pub fn verify<H: MerkleHasher<Node = N>>(&self, hasher: &H) -> bool {
+        self.root_with_leaf(hasher, self.leaf) == self.root
+    }
+}
@@ -0,0 +1,224 @@
+//! Portable snapshots of a tree, e.g. to seed a staging database from production or to prove
+//! leaves offline.
+//!
+//! A snapshot file is, with integers little-endian:
+//!
+//! ```text
+//! magic "MTSN" | version u16 | hasher u8 | flags u8 | depth u32 | leaf count u64
+//! leaves, 32 bytes each
+//! layers 1 to depth, 32 bytes per node, only with FLAG_LAYERS
+//! root, 32 bytes
+//! SHA-256 of everything above, 32 bytes
+//! ```
+//!
+//! A stored layer `h` holds `ceil(n / 2)` nodes for `n` nodes in layer `h - 1`, so the layer
+//! sizes follow from the leaf count.
+
+use anyhow::Result;
+use ark_bn254::Fr;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    field::{fr_from_bytes, fr_to_bytes},
+    hasher::{KeccakHasher, MerkleHasher, PoseidonHasher, Sha256Hasher},
+    tree::MerkleTreeStorage,
+};
+
+const MAGIC: &[u8; 4] = b"MTSN";
+/// Format version written by `Snapshot::to_bytes`.
+pub const SNAPSHOT_VERSION: u16 = 1;
+/// The layers above the leaves are included, so importing skips rehashing them.
+const FLAG_LAYERS: u8 = 1;
+const HEADER_LEN: usize = 20;
+
+/// Hash function a snapshot's tree was built with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HasherId {
+    Poseidon = 1,
+    Keccak = 2,
+    Sha256 = 3,
+}
+
+impl HasherId {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HasherId::Poseidon => "poseidon",
+            HasherId::Keccak => "keccak",
+            HasherId::Sha256 => "sha256",
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self> {
+        match byte {
+            1 => Ok(HasherId::Poseidon),
+            2 => Ok(HasherId::Keccak),
+            3 => Ok(HasherId::Sha256),
+            _ => Err(anyhow::anyhow!("Unknown snapshot hasher {}", byte)),
+        }
+    }
+}
+
+/// A hasher whose trees can be written to files, as a `Snapshot` or an `MmapMerkleTree`: it
+/// has an id and its nodes are 32 bytes.
+pub trait SnapshotHasher: MerkleHasher {
+    const ID: HasherId;
+
+    fn node_to_bytes(node: &Self::Node) -> [u8; 32];
+
+    /// `None` for bytes that are not a node, e.g. not below the field modulus.
+    fn node_from_bytes(bytes: &[u8; 32]) -> Option<Self::Node>;
+}
+
+impl SnapshotHasher for PoseidonHasher {
+    const ID: HasherId = HasherId::Poseidon;
+
+    fn node_to_bytes(node: &Fr) -> [u8; 32] {
+        fr_to_bytes(node)
+    }
+
+    fn node_from_bytes(bytes: &[u8; 32]) -> Option<Fr> {
+        fr_from_bytes(bytes)
+    }
+}
+
+impl SnapshotHasher for KeccakHasher {
+    const ID: HasherId = HasherId::Keccak;
+
+    fn node_to_bytes(node: &[u8; 32]) -> [u8; 32] {
+        *node
+    }
+
+    fn node_from_bytes(bytes: &[u8; 32]) -> Option<[u8; 32]> {
+        Some(*bytes)
+    }
+}
+
+impl SnapshotHasher for Sha256Hasher {
+    const ID: HasherId = HasherId::Sha256;
+
+    fn node_to_bytes(node: &[u8; 32]) -> [u8; 32] {
+        *node
+    }
+
+    fn node_from_bytes(bytes: &[u8; 32]) -> Option<[u8; 32]> {
+        Some(*bytes)
+    }
+}
+
+/// Contents of a snapshot file, with nodes as bytes so it can be inspected without knowing
+/// the hasher up front.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub hasher: HasherId,
+    pub depth: u32,
+    pub leaves: Vec<[u8; 32]>,
+    /// Layers 1 to `depth`, if they were exported.
+    pub layers: Option<Vec<Vec<[u8; 32]>>>,
+    pub root: [u8; 32],
+}
+
+impl Snapshot {
+    /// Snapshot of `tree`, with the layers above the leaves if `with_layers`.
+    pub fn of<H: SnapshotHasher>(tree: &MerkleTreeStorage<H>, with_layers: bool) -> Self {
+        let to_bytes = |layer: &Vec<H::Node>| layer.iter().map(H::node_to_bytes).collect::<Vec<_>>();
+        Self {
+            hasher: H::ID,
+            depth: tree.depth(),
+            leaves: to_bytes(&tree.layers()[0]),
+            layers: with_layers.then(|| tree.layers()[1..].iter().map(to_bytes).collect()),
+            root: H::node_to_bytes(&tree.root()),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + 32 * (self.leaves.len() + 2));
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        bytes.push(self.hasher as u8);
+        bytes.push(if self.layers.is_some() { FLAG_LAYERS } else { 0 });
+        bytes.extend_from_slice(&self.depth.to_le_bytes());
+        bytes.extend_from_slice(&(self.leaves.len() as u64).to_le_bytes());
+        for node in self.leaves.iter().chain(self.layers.iter().flatten().flatten()) {
+            bytes.extend_from_slice(node);
+        }
+        bytes.extend_from_slice(&self.root);
+        let checksum: [u8; 32] = Sha256::digest(&bytes).into();
+        bytes.extend_from_slice(&checksum);
+        bytes
+    }
+
+    /// Inverse of `to_bytes`. Fails on a bad checksum, an unknown version or a truncated file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let Some((body, checksum)) = bytes.split_last_chunk::<32>() else {
+            return Err(anyhow::anyhow!("Snapshot is truncated"));
+        };
+        if body.len() < HEADER_LEN || &body[..4] != MAGIC {
+            return Err(anyhow::anyhow!("Not a tree snapshot"));
+        }
+        let version = u16::from_le_bytes([body[4], body[5]]);
+        if version != SNAPSHOT_VERSION {
+            return Err(anyhow::anyhow!("Unsupported snapshot version {}", version));
+        }
+        if Sha256::digest(body).as_slice() != checksum {
+            return Err(anyhow::anyhow!("Snapshot checksum does not match"));
+        }
+
+        let hasher = HasherId::from_byte(body[6])?;
+        let flags = body[7];
+        if flags & !FLAG_LAYERS != 0 {
+            return Err(anyhow::anyhow!("Unknown snapshot flags {:#04x}", flags));
+        }
+        let depth = u32::from_le_bytes(body[8..12].try_into().unwrap());
+        let leaf_count = u64::from_le_bytes(body[12..20].try_into().unwrap());
+        if !(1..=crate::MAX_DEPTH).contains(&depth) || leaf_count > 1 << depth {
+            return Err(anyhow::anyhow!("Snapshot of {} leaves at depth {} is not a valid tree", leaf_count, depth));
+        }
+
+        let mut layer_sizes = vec![leaf_count as usize];
+        if flags & FLAG_LAYERS != 0 {
+            for height in 0..depth as usize {
+                layer_sizes.push(layer_sizes[height].div_ceil(2));
+            }
+        }
+        let node_count: usize = layer_sizes.iter().sum::<usize>() + 1;
+        if body.len() != HEADER_LEN + 32 * node_count {
+            return Err(anyhow::anyhow!("Snapshot is truncated"));
+        }
+
+        let mut nodes = body[HEADER_LEN..].chunks_exact(32).map(|chunk| <[u8; 32]>::try_from(chunk).unwrap());
+        let mut layers: Vec<Vec<[u8; 32]>> =
+            layer_sizes.iter().map(|size| nodes.by_ref().take(*size).collect()).collect();
+        let root = nodes.next().unwrap();
+        let leaves = layers.remove(0);
+        Ok(Self { hasher, depth, leaves, layers: (flags & FLAG_LAYERS != 0).then_some(layers), root })
+    }
+
+    /// Rebuilds the tree. Exported layers are taken as they are, otherwise they are rehashed
+    /// from the leaves; either way the root has to match the exported one.
+    pub fn into_tree<H: SnapshotHasher + Default>(self) -> Result<MerkleTreeStorage<H>> {
+        if self.hasher != H::ID {
+            return Err(anyhow::anyhow!("Snapshot is of a {} tree, not {}", self.hasher.name(), H::ID.name()));
+        }
+        let to_nodes = |layer: Vec<[u8; 32]>| {
+            layer
+                .iter()
+                .map(|bytes| H::node_from_bytes(bytes).ok_or_else(|| anyhow::anyhow!("Invalid node in snapshot")))
+                .collect::<Result<Vec<_>>>()
+        };
+        let leaves = to_nodes(self.leaves)?;
+        let tree = match self.layers {
+            Some(layers) => {
+                let mut nodes = vec![leaves];
+                for layer in layers {
+                    nodes.push(to_nodes(layer)?);
+                }
+                MerkleTreeStorage::from_layers(self.depth, H::default(), nodes)?
+            }
+            None => MerkleTreeStorage::from_leaves(self.depth, leaves)?,
+        };
+        if H::node_to_bytes(&tree.root()) != self.root {
+            return Err(anyhow::anyhow!("Snapshot root does not match its leaves"));
+        }
+        Ok(tree)
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use anyhow::Result;
+use rayon::prelude::*;
+
+use crate::{
+    consistency::ConsistencyProof,
+    error::TreeError,
+    hasher::{MerkleHasher, PoseidonHasher},
+    proof::MerkleProof,
+};
+
+/// Deepest supported tree, 2^32 leaf slots.
+pub const MAX_DEPTH: u32 = 32;
+
+// Fewest pairs a thread hashes in a parallel rebuild, smaller layers are not worth splitting
+const PARALLEL_MIN_PAIRS: usize = 512;
+
+/// An append worked out by `MerkleTreeStorage::prepare_append`, not yet made.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreparedAppend<N> {
+    /// Slot the leaf goes in.
+    pub position: usize,
+    /// Proof that the slot holds zero, against the root before the append.
+    pub append_proof: MerkleProof<N>,
+    /// Proof of the leaf in the slot, against the root after the append.
+    pub proof: MerkleProof<N>,
+}
+
+pub struct MerkleTreeStorage<H: MerkleHasher = PoseidonHasher> {
+    hasher: H,
+    depth: u32,
+    // Reverse index of `layers[0]`: the first slot of each leaf, and the later slots of the
+    // few leaves stored more than once
+    positions: HashMap<H::Node, usize>,
+    duplicates: HashMap<H::Node, Vec<usize>>,
+    // `layers[0]` holds the leaves in slot order and `layers[depth]` the root. A layer only
+    // stores the nodes above filled slots, the others are the empty subtree of that height.
+    layers: Vec<Vec<H::Node>>,
+    zeros: Vec<H::Node>, // Root of an empty subtree of each height
+}
+
+impl<H: MerkleHasher + Default> MerkleTreeStorage<H> {
+    /// Creates an empty tree with 2^depth leaf slots.
+    pub fn new(depth: u32) -> Self {
+        Self::with_hasher(depth, H::default())
+    }
+
+    /// Builds a tree holding `leaves` in order.
+    pub fn from_leaves(depth: u32, leaves: impl IntoIterator<Item = H::Node>) -> Result<Self, TreeError> {
+        let mut tree = Self::try_with_hasher(depth, H::default())?;
+        for leaf in leaves {
+            if tree.layers[0].len() >= tree.capacity() {
+                return Err(TreeError::Full);
+            }
+            tree.index_leaf(leaf, tree.layers[0].len());
+            tree.layers[0].push(leaf);
+        }
+        tree.generate_merkle_tree_parallel();
+        Ok(tree)
+    }
+}
+
+impl<H: MerkleHasher> MerkleTreeStorage<H> {
+    /// Panics if `depth` is not between 1 and `MAX_DEPTH`, see `try_with_hasher`.
+    pub fn with_hasher(depth: u32, hasher: H) -> Self {
+        Self::try_with_hasher(depth, hasher).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like `with_hasher`, for depths that come from outside, e.g. a config or a file.
+    pub fn try_with_hasher(depth: u32, hasher: H) -> Result<Self, TreeError> {
+        if !(1..=MAX_DEPTH).contains(&depth) {
+            return Err(TreeError::InvalidDepth(depth));
+        }
+        let mut zeros = vec![hasher.zero()];
+        for height in 0..depth as usize {
+            zeros.push(hasher.hash(&zeros[height], &zeros[height]));
+        }
+        Ok(Self {
+            hasher,
+            depth,
+            positions: HashMap::new(),
+            duplicates: HashMap::new(),
+            layers: vec![Vec::new(); depth as usize + 1],
+            zeros,
+        })
+    }
+
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Number of leaf slots, 2^depth.
+    pub fn capacity(&self) -> usize {
+        1 << self.depth
+    }
+
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Number of filled slots.
+    pub fn len(&self) -> usize {
+        self.layers[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers[0].is_empty()
+    }
+
+    /// Leaves in slot order.
+    pub fn leaves(&self) -> &[H::Node] {
+        &self.layers[0]
+    }
+
+    /// Leaf in slot `index`, if it is filled.
+    pub fn leaf_at(&self, index: usize) -> Option<H::Node> {
+        self.layers[0].get(index).copied()
+    }
+
+    /// First slot holding `leaf`.
+    pub fn position_of(&self, leaf: &H::Node) -> Option<usize> {
+        self.positions.get(leaf).copied()
+    }
+
+    /// Every slot holding `leaf`, in order.
+    pub fn positions_of(&self, leaf: &H::Node) -> Vec<usize> {
+        let first = self.positions.get(leaf).copied();
+        first.into_iter().chain(self.duplicates.get(leaf).into_iter().flatten().copied()).collect()
+    }
+
+    pub fn root(&self) -> H::Node {
+        self.node(self.depth as usize, 0)
+    }
+
+    /// Root the tree had when it held its first `size` leaves.
+    pub fn root_at(&self, size: usize) -> Result<H::Node, TreeError> {
+        self.check_size(size)?;
+        Ok(self.node_at(self.depth as usize, 0, size))
+    }
+
+    /// Every stored layer, leaves first. Layer `h` holds the nodes above filled slots only.
+    pub(crate) fn layers(&self) -> &[Vec<H::Node>] {
+        &self.layers
+    }
+
+    /// Tree with the given layers as they are, without rehashing them. The layers must be the
+    /// ones `layers` returned for a tree of the same depth.
+    pub(crate) fn from_layers(depth: u32, hasher: H, layers: Vec<Vec<H::Node>>) -> Result<Self> {
+        let mut tree = Self::try_with_hasher(depth, hasher)?;
+        if layers.len() != depth as usize + 1 {
+            return Err(anyhow::anyhow!("Depth {} tree needs {} layers, not {}", depth, depth + 1, layers.len()));
+        }
+        if layers[0].len() > tree.capacity() {
+            return Err(TreeError::Full.into());
+        }
+        for (height, pair) in layers.windows(2).enumerate() {
+            let expected = pair[0].len().div_ceil(2);
+            if pair[1].len() != expected {
+                return Err(anyhow::anyhow!("Layer {} has {} nodes, not {}", height + 1, pair[1].len(), expected));
+            }
+        }
+        for (position, leaf) in layers[0].iter().enumerate() {
+            tree.index_leaf(*leaf, position);
+        }
+        tree.layers = layers;
+        Ok(tree)
+    }
+
+    /// Resets the Merkle tree, clearing all stored leaves.
+    pub fn reset_tree(&mut self) {
+        self.positions.clear();
+        self.duplicates.clear();
+        for layer in self.layers.iter_mut() {
+            layer.clear();
+        }
+    }
+
+    /// Appends a leaf in the next empty slot and returns its position. A leaf can be stored
+    /// more than once, see `positions_of`.
+    pub fn insert_leaf(&mut self, leaf: H::Node) -> Result<usize, TreeError> {
+        let position = self.layers[0].len();
+        if position >= self.capacity() {
+            return Err(TreeError::Full);
+        }
+        self.index_leaf(leaf, position);
+        self.layers[0].push(leaf);
+
+        // Rehash the path above the new slot
+        let mut index = position;
+        for height in 0..self.depth as usize {
+            let parent = self.hasher.hash(&self.node(height, index & !1), &self.node(height, index | 1));
+            index >>= 1;
+            let layer = &mut self.layers[height + 1];
+            if index == layer.len() {
+                layer.push(parent);
+            } else {
+                layer[index] = parent;
+            }
+        }
+        Ok(position)
+    }
+
+    /// Works out appending `leaf` without changing the tree, e.g. to store the leaf in a
+    /// transaction first. `apply_append` makes the append, dropping it leaves the tree as it was.
+    pub fn prepare_append(&self, leaf: H::Node) -> Result<PreparedAppend<H::Node>, TreeError> {
+        let append_proof = self.append_proof()?;
+        // Filling the slot leaves the siblings along its path as they are
+        let proof = MerkleProof {
+            leaf,
+            position: append_proof.position,
+            siblings: append_proof.siblings.clone(),
+            root: append_proof.root_with_leaf(&self.hasher, leaf),
+        };
+        Ok(PreparedAppend {
+            position: append_proof.position,
+            append_proof,
+            proof,
+        })
+    }
+
+    /// Makes an append prepared with `prepare_append`. Fails with `TreeError::InvalidSize` if
+    /// the tree has changed since.
+    pub fn apply_append(&mut self, prepared: &PreparedAppend<H::Node>) -> Result<usize, TreeError> {
+        if prepared.position != self.len() || prepared.append_proof.root != self.root() {
+            return Err(TreeError::InvalidSize {
+                size: prepared.position,
+                len: self.len(),
+            });
+        }
+        self.insert_leaf(prepared.proof.leaf)
+    }
+
+    /// Like `insert_leaf`, but fails with `TreeError::DuplicateLeaf` if `leaf` is already stored.
+    pub fn insert_unique_leaf(&mut self, leaf: H::Node) -> Result<usize, TreeError> {
+        if let Some(position) = self.position_of(&leaf) {
+            return Err(TreeError::DuplicateLeaf { position });
+        }
+        self.insert_leaf(leaf)
+    }
+
+    /// Rebuilds every layer above the leaves.
+    pub fn generate_merkle_tree(&mut self) {
+        for height in 0..self.depth as usize {
+            let zero = self.zeros[height];
+            let parents: Vec<H::Node> = self.layers[height]
+                .chunks(2)
+                .map(|pair| self.hasher.hash(&pair[0], pair.get(1).unwrap_or(&zero)))
+                .collect();
+            self.layers[height + 1] = parents;
+        }
+    }
+
+    /// Rebuilds every layer above the leaves, hashing each layer on rayon's thread pool. Used
+    /// by `from_leaves`, so loading a tree or importing a snapshot rebuilds it this way.
+    pub fn generate_merkle_tree_parallel(&mut self) {
+        for height in 0..self.depth as usize {
+            let zero = self.zeros[height];
+            let hasher = &self.hasher;
+            let parents: Vec<H::Node> = self.layers[height]
+                .par_chunks(2)
+                .with_min_len(PARALLEL_MIN_PAIRS)
+                .map(|pair| hasher.hash(&pair[0], pair.get(1).unwrap_or(&zero)))
+                .collect();
+            self.layers[height + 1] = parents;
+        }
+    }
+
+    /// Generates a Merkle proof for a given leaf, in the first slot holding it.
+    pub fn generate_merkle_proof(&self, leaf: H::Node) -> Option<MerkleProof<H::Node>> {
+        let position = self.position_of(&leaf)?;
+        Some(self.proof_at(position, leaf))
+    }
+
+    /// Proof for the leaf in slot `position`, e.g. a later copy of a leaf stored more than once.
+    pub fn generate_merkle_proof_at(&self, position: usize) -> Option<MerkleProof<H::Node>> {
+        let leaf = self.leaf_at(position)?;
+        Some(self.proof_at(position, leaf))
+    }
+
+    /// Proof for `leaf` against the root the tree had at `size` leaves, e.g. the size of a
+    /// signed tree head.
+    pub fn inclusion_proof_at(&self, leaf: H::Node, size: usize) -> Result<MerkleProof<H::Node>, TreeError> {
+        self.check_size(size)?;
+        let position = self.position_of(&leaf).ok_or(TreeError::LeafNotFound)?;
+        if position >= size {
+            return Err(TreeError::LeafAfterSize { position, size });
+        }
+        let siblings = (0..self.depth as usize)
+            .map(|height| self.node_at(height, (position >> height) ^ 1, size))
+            .collect();
+        Ok(MerkleProof {
+            leaf,
+            position,
+            siblings,
+            root: self.node_at(self.depth as usize, 0, size),
+        })
+    }
+
+    /// Proof that the next empty slot holds zero, for proving an append to it.
+    pub fn append_proof(&self) -> Result<MerkleProof<H::Node>, TreeError> {
+        let position = self.layers[0].len();
+        if position >= self.capacity() {
+            return Err(TreeError::Full);
+        }
+        Ok(self.proof_at(position, self.zeros[0]))
+    }
+
+    /// Proof that the tree at `new_size` leaves extends the one at `old_size`, see
+    /// `ConsistencyProof`.
+    pub fn consistency_proof(&self, old_size: usize, new_size: usize) -> Result<ConsistencyProof<H::Node>, TreeError> {
+        self.check_size(new_size)?;
+        if old_size > new_size {
+            return Err(TreeError::InvalidRange { old_size, new_size });
+        }
+        let position = old_size.saturating_sub(1);
+        let siblings = (0..self.depth as usize)
+            .map(|height| self.node_at(height, (position >> height) ^ 1, new_size))
+            .collect();
+        Ok(ConsistencyProof {
+            old_size,
+            new_size,
+            leaf: self.node_at(0, position, old_size),
+            siblings,
+        })
+    }
+
+    fn proof_at(&self, position: usize, leaf: H::Node) -> MerkleProof<H::Node> {
+        let siblings = (0..self.depth as usize)
+            .map(|height| self.node(height, (position >> height) ^ 1))
+            .collect();
+        MerkleProof {
+            leaf,
+            position,
+            siblings,
+            root: self.root(),
+        }
+    }
+
+    fn index_leaf(&mut self, leaf: H::Node, position: usize) {
+        match self.positions.entry(leaf) {
+            Entry::Vacant(entry) => {
+                entry.insert(position);
+            }
+            Entry::Occupied(_) => self.duplicates.entry(leaf).or_default().push(position),
+        }
+    }
+
+    fn node(&self, height: usize, index: usize) -> H::Node {
+        self.layers[height].get(index).copied().unwrap_or(self.zeros[height])
+    }
+
+    // Node as it was when the tree held `size` leaves. Subtrees the old size filled or left
+    // empty are stored as is, only the ones it cut through are rehashed.
+    fn node_at(&self, height: usize, index: usize, size: usize) -> H::Node {
+        let (first, end) = (index << height, (index + 1) << height);
+        if end <= size {
+            return self.node(height, index);
+        }
+        if first >= size {
+            return self.zeros[height];
+        }
+        self.hasher.hash(&self.node_at(height - 1, 2 * index, size), &self.node_at(height - 1, 2 * index + 1, size))
+    }
+
+    fn check_size(&self, size: usize) -> Result<(), TreeError> {
+        if size > self.len() {
+            return Err(TreeError::InvalidSize { size, len: self.len() });
+        }
+        Ok(())
+    }
+}
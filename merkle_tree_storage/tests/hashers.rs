@@ -0,0 +1,87 @@
+use std::str::FromStr;
+
+use merkle_tree_storage::{Fr, KeccakHasher, MerkleHasher, MerkleTreeStorage, PoseidonHasher, Sha256Hasher};
+
+fn word(value: u8) -> [u8; 32] {
+    let mut word = [0; 32];
+    word[31] = value;
+    word
+}
+
+fn hex(bytes: &[u8; 32]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn fr(decimal: &str) -> Fr {
+    Fr::from_str(decimal).unwrap()
+}
+
+// Root of a depth 2 tree holding 1, 2, 3 with the last slot empty
+fn small_root<H: MerkleHasher + Default>(leaf: impl Fn(u8) -> H::Node) -> H::Node {
+    MerkleTreeStorage::<H>::from_leaves(2, (1..=3).map(leaf)).unwrap().root()
+}
+
+#[test]
+fn poseidon_matches_circomlib() {
+    let poseidon = PoseidonHasher::default();
+    // circomlibjs `poseidon([1, 2])` and `poseidon([0, 0])`
+    assert_eq!(
+        poseidon.hash(&fr("1"), &fr("2")),
+        fr("7853200120776062878684798364095072458815029376092732009249414926327459813530")
+    );
+    assert_eq!(
+        poseidon.hash(&fr("0"), &fr("0")),
+        fr("14744269619966411208579211824598458697587494354926760081771325075741142829156")
+    );
+    assert_eq!(
+        small_root::<PoseidonHasher>(|value| fr(&value.to_string())),
+        fr("6160282095303309562128646095777926429296053007730114230592243580818245579278")
+    );
+}
+
+#[test]
+fn keccak_matches_solidity() {
+    let keccak = KeccakHasher;
+    // keccak256(abi.encodePacked(bytes32(0), bytes32(0))) and keccak256(abi.encode(1, 2))
+    assert_eq!(
+        hex(&keccak.hash(&word(0), &word(0))),
+        "ad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5"
+    );
+    assert_eq!(
+        hex(&keccak.hash(&word(1), &word(2))),
+        "e90b7bceb6e7df5418fb78d8ee546e97c83a08bbccc01a0644d599ccd2a7c2e0"
+    );
+    assert_eq!(
+        hex(&small_root::<KeccakHasher>(word)),
+        "222ff5e0b5877792c2bc1670e2ccd0c2c97cd7bb1672a57d598db05092d3d72c"
+    );
+}
+
+#[test]
+fn sha256_matches_standard_tooling() {
+    let sha256 = Sha256Hasher;
+    // Also the first zero hash of the Ethereum deposit contract
+    assert_eq!(
+        hex(&sha256.hash(&word(0), &word(0))),
+        "f5a5fd42d16a20302798ef6ed309979b43003d2320d9f0e8ea9831a92759fb4b"
+    );
+    assert_eq!(
+        hex(&sha256.hash(&word(1), &word(2))),
+        "d6ba9329f8932c12192b37849f772104d20048f76434a3290512d9d814e4116f"
+    );
+    assert_eq!(
+        hex(&small_root::<Sha256Hasher>(word)),
+        "dfea42101f94476e3b2e26f2a3e23505a696a6fd3d3ded213cece4adb19cb9ac"
+    );
+}
+
+#[test]
+fn byte_trees_prove_membership() {
+    let mut tree = MerkleTreeStorage::<KeccakHasher>::new(4);
+    for value in 1..=9 {
+        tree.insert_leaf(word(value)).unwrap();
+    }
+    let proof = tree.generate_merkle_proof(word(7)).unwrap();
+    assert!(proof.verify(tree.hasher()));
+    assert!(!proof.verify(&Sha256Hasher));
+}
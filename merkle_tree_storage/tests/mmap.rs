@@ -0,0 +1,141 @@
+use std::{
+    fs::OpenOptions,
+    io::{Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use merkle_tree_storage::{Fr, KeccakHasher, MerkleTreeStorage, MmapMerkleTree, PoseidonHasher, TreeError};
+use tempfile::TempDir;
+
+// The leaf count is at byte 16 of the header, and the leaves start after its 4096 bytes
+const LEN_OFFSET: u64 = 16;
+const LEAVES_OFFSET: u64 = 4096;
+
+fn leaves(count: u64) -> Vec<Fr> {
+    (1..=count).map(Fr::from).collect()
+}
+
+fn mapped(dir: &TempDir, depth: u32, leaves: &[Fr]) -> MmapMerkleTree {
+    let mut tree = MmapMerkleTree::create(dir.path().join("tree"), depth).unwrap();
+    for leaf in leaves {
+        tree.insert_leaf(*leaf).unwrap();
+    }
+    tree
+}
+
+#[test]
+fn mapped_tree_matches_the_in_memory_one() {
+    let dir = TempDir::new().unwrap();
+    let leaves = leaves(11);
+    let tree = mapped(&dir, 4, &leaves);
+    let memory = MerkleTreeStorage::<PoseidonHasher>::from_leaves(4, leaves.clone()).unwrap();
+
+    assert_eq!(tree.len(), 11);
+    assert_eq!(tree.root().unwrap(), memory.root());
+    assert_eq!(tree.leaf_at(3), Ok(Some(leaves[3])));
+    assert_eq!(tree.leaf_at(11), Ok(None));
+    for leaf in &leaves {
+        assert_eq!(tree.generate_merkle_proof(*leaf).unwrap(), memory.generate_merkle_proof(*leaf));
+    }
+    assert_eq!(tree.append_proof().unwrap(), memory.append_proof().unwrap());
+    for size in [0, 1, 5, 8, 11] {
+        assert_eq!(tree.root_at(size).unwrap(), memory.root_at(size).unwrap());
+    }
+    assert_eq!(tree.inclusion_proof_at(leaves[4], 7).unwrap(), memory.inclusion_proof_at(leaves[4], 7).unwrap());
+    assert!(tree.inclusion_proof_at(leaves[9], 7).is_err());
+    assert_eq!(tree.consistency_proof(5, 11).unwrap(), memory.consistency_proof(5, 11).unwrap());
+    assert_eq!(tree.generate_merkle_proof(Fr::from(99u64)), Ok(None));
+}
+
+#[test]
+fn reopened_tree_keeps_its_leaves() {
+    let dir = TempDir::new().unwrap();
+    let leaves = leaves(6);
+    let root = {
+        let tree = mapped(&dir, 3, &leaves[..5]);
+        tree.flush().unwrap();
+        tree.root().unwrap()
+    };
+
+    let mut tree: MmapMerkleTree = MmapMerkleTree::open(dir.path().join("tree")).unwrap();
+    assert_eq!((tree.depth(), tree.len(), tree.root().unwrap()), (3, 5, root));
+    assert_eq!(tree.generate_merkle_proof(leaves[2]).unwrap().unwrap().position, 2);
+    tree.insert_leaf(leaves[5]).unwrap();
+    assert_eq!(tree.root().unwrap(), MerkleTreeStorage::<PoseidonHasher>::from_leaves(3, leaves).unwrap().root());
+
+    assert!(MmapMerkleTree::<KeccakHasher>::open(dir.path().join("tree")).is_err());
+}
+
+fn overwrite(path: &Path, offset: u64, bytes: &[u8]) {
+    let mut file = OpenOptions::new().write(true).open(path).unwrap();
+    file.seek(SeekFrom::Start(offset)).unwrap();
+    file.write_all(bytes).unwrap();
+}
+
+#[test]
+fn unfinished_inserts_are_undone_on_open() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("tree");
+    let leaves = leaves(7);
+    mapped(&dir, 3, &leaves[..6]).flush().unwrap();
+    // What an insert that died before storing the leaf count leaves behind
+    overwrite(&path, LEN_OFFSET, &5u64.to_le_bytes());
+
+    let mut tree: MmapMerkleTree = MmapMerkleTree::open(&path).unwrap();
+    let memory = |leaves: &[Fr]| MerkleTreeStorage::<PoseidonHasher>::from_leaves(3, leaves.to_vec()).unwrap().root();
+    assert_eq!(tree.len(), 5);
+    assert_eq!(tree.root().unwrap(), memory(&leaves[..5]));
+    assert_eq!(tree.position_of(&leaves[5]), None);
+
+    // The index entry the unfinished insert left does not point at the leaf filling its slot
+    tree.insert_leaf(leaves[6]).unwrap();
+    assert_eq!(tree.position_of(&leaves[5]), None);
+    assert_eq!(tree.insert_leaf(leaves[5]).unwrap(), 6);
+    assert_eq!(tree.position_of(&leaves[5]), Some(6));
+    let replayed: Vec<Fr> = leaves[..5].iter().chain([&leaves[6], &leaves[5]]).copied().collect();
+    assert_eq!(tree.root().unwrap(), memory(&replayed));
+}
+
+#[test]
+fn corrupt_nodes_are_errors() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("tree");
+    let leaves = leaves(5);
+    mapped(&dir, 3, &leaves).flush().unwrap();
+    // Above the field modulus, where leaf 2 is stored
+    overwrite(&path, LEAVES_OFFSET + 2 * 32, &[0xff; 32]);
+
+    let tree: MmapMerkleTree = MmapMerkleTree::open(&path).unwrap();
+    let corrupt = TreeError::CorruptNode { height: 0, index: 2 };
+    assert_eq!(tree.leaf_at(2), Err(corrupt));
+    assert_eq!(tree.generate_merkle_proof_at(2), Err(corrupt));
+    assert_eq!(tree.inclusion_proof_at(leaves[0], 3).unwrap_err(), corrupt);
+    assert_eq!(tree.leaf_at(1), Ok(Some(leaves[1])));
+}
+
+#[test]
+fn duplicates_keep_their_first_position_and_full_trees_reject_leaves() {
+    let dir = TempDir::new().unwrap();
+    let mut tree = mapped(&dir, 2, &leaves(2));
+    assert_eq!(tree.insert_leaf(Fr::from(1u64)).unwrap(), 2);
+    assert_eq!(tree.position_of(&Fr::from(1u64)), Some(0));
+    assert_eq!(tree.generate_merkle_proof(Fr::from(1u64)).unwrap().unwrap().position, 0);
+    assert_eq!(tree.generate_merkle_proof_at(2).unwrap().unwrap().position, 2);
+    assert!(tree.insert_unique_leaf(Fr::from(2u64)).is_err());
+    tree.insert_leaf(Fr::from(4u64)).unwrap();
+    assert!(tree.insert_leaf(Fr::from(5u64)).is_err());
+
+    tree.reset_tree().unwrap();
+    assert!(tree.is_empty());
+    assert_eq!(tree.root().unwrap(), MerkleTreeStorage::<PoseidonHasher>::new(2).root());
+    assert_eq!(tree.generate_merkle_proof(Fr::from(1u64)), Ok(None));
+}
+
+#[test]
+fn deep_trees_stay_sparse() {
+    let dir = TempDir::new().unwrap();
+    let tree = mapped(&dir, 24, &leaves(3));
+    let memory = MerkleTreeStorage::<PoseidonHasher>::from_leaves(24, leaves(3)).unwrap();
+    assert_eq!(tree.root().unwrap(), memory.root());
+    assert_eq!(tree.generate_merkle_proof(Fr::from(3u64)).unwrap(), memory.generate_merkle_proof(Fr::from(3u64)));
+}
@@ -0,0 +1,134 @@
+use merkle_tree_storage::{bag_peaks, Fr, MerkleHasher, MerkleMountainRange, PoseidonHasher, TreeError};
+
+// Order-sensitive and cheap, so expected roots can be worked out by hand
+#[derive(Default)]
+struct Pair;
+
+impl MerkleHasher for Pair {
+    type Node = u64;
+
+    fn zero(&self) -> u64 {
+        0
+    }
+
+    fn hash(&self, left: &u64, right: &u64) -> u64 {
+        3 * left + 7 * right + 1
+    }
+}
+
+fn range_of(count: u64) -> MerkleMountainRange<Pair> {
+    MerkleMountainRange::from_leaves(1..=count)
+}
+
+#[test]
+fn peaks_follow_the_bits_of_the_size() {
+    let h = |l, r| Pair.hash(&l, &r);
+    let range = range_of(7);
+    assert_eq!(range.len(), 7);
+    assert_eq!(range.peaks(7).unwrap(), vec![h(h(1, 2), h(3, 4)), h(5, 6), 7]);
+    assert_eq!(range.root(), h(h(h(1, 2), h(3, 4)), h(h(5, 6), 7)));
+    assert_eq!(range.peaks(4).unwrap(), vec![h(h(1, 2), h(3, 4))]);
+    assert_eq!(range.root_at(1).unwrap(), 1);
+    assert_eq!(range.root_at(0).unwrap(), 0);
+    assert_eq!(range.root_at(8), Err(TreeError::InvalidSize { size: 8, len: 7 }));
+    assert!(MerkleMountainRange::<Pair>::new().is_empty());
+}
+
+#[test]
+fn earlier_roots_do_not_change() {
+    let mut range = MerkleMountainRange::<Pair>::new();
+    let mut roots = vec![range.root()];
+    for leaf in 1..=20 {
+        assert_eq!(range.append(leaf), leaf as usize - 1);
+        roots.push(range.root());
+    }
+    for (size, root) in roots.iter().enumerate() {
+        assert_eq!(range.root_at(size).unwrap(), *root);
+        assert_eq!(bag_peaks(&Pair, &range.peaks(size).unwrap()), *root);
+    }
+}
+
+#[test]
+fn inclusion_against_every_size() {
+    let range = range_of(21);
+    for size in 1..=21 {
+        let root = range.root_at(size).unwrap();
+        for index in 0..size {
+            let proof = range.inclusion_proof(index, size).unwrap();
+            assert_eq!(proof.leaf, index as u64 + 1);
+            assert!(proof.verify(&Pair, root), "leaf {} at size {}", index, size);
+        }
+    }
+    assert_eq!(range.inclusion_proof(5, 5).unwrap_err(), TreeError::LeafAfterSize { position: 5, size: 5 });
+    assert_eq!(range.inclusion_proof(0, 22).unwrap_err(), TreeError::InvalidSize { size: 22, len: 21 });
+}
+
+#[test]
+fn tampered_inclusion_proofs_fail() {
+    let range = range_of(13);
+    let root = range.root();
+    let proof = range.inclusion_proof(9, 13).unwrap();
+    assert!(proof.verify(&Pair, root));
+
+    let mut wrong_leaf = proof.clone();
+    wrong_leaf.leaf += 1;
+    assert!(!wrong_leaf.verify(&Pair, root));
+
+    let mut wrong_index = proof.clone();
+    wrong_index.leaf_index = 8;
+    assert!(!wrong_index.verify(&Pair, root));
+
+    let mut wrong_size = proof.clone();
+    wrong_size.size = 12;
+    assert!(!wrong_size.verify(&Pair, root));
+
+    let mut missing_peak = proof.clone();
+    missing_peak.peaks.pop();
+    assert!(!missing_peak.verify(&Pair, root));
+
+    assert!(!proof.verify(&Pair, range.root_at(12).unwrap()));
+}
+
+#[test]
+fn consistency_between_every_pair_of_sizes() {
+    let range = range_of(19);
+    for new_size in 0..=19 {
+        for old_size in 0..=new_size {
+            let proof = range.consistency_proof(old_size, new_size).unwrap();
+            let (old_root, new_root) = (range.root_at(old_size).unwrap(), range.root_at(new_size).unwrap());
+            assert!(proof.verify(&Pair, old_root, new_root), "{} -> {}", old_size, new_size);
+        }
+    }
+    assert_eq!(range.consistency_proof(5, 4).unwrap_err(), TreeError::InvalidRange { old_size: 5, new_size: 4 });
+    assert_eq!(range.consistency_proof(5, 20).unwrap_err(), TreeError::InvalidSize { size: 20, len: 19 });
+}
+
+#[test]
+fn rewritten_history_is_not_consistent() {
+    let honest = range_of(11);
+    // Same length, leaf 3 replaced
+    let forked = MerkleMountainRange::<Pair>::from_leaves((1..=11).map(|leaf| if leaf == 3 { 99 } else { leaf }));
+    let old_root = honest.root_at(6).unwrap();
+
+    let proof = forked.consistency_proof(6, 11).unwrap();
+    assert!(!proof.verify(&Pair, old_root, forked.root()));
+
+    let mut spliced = proof.clone();
+    spliced.old_peaks = honest.peaks(6).unwrap();
+    assert!(!spliced.verify(&Pair, old_root, forked.root()));
+
+    let proof = honest.consistency_proof(6, 11).unwrap();
+    assert!(proof.verify(&Pair, old_root, honest.root()));
+    assert!(!proof.verify(&Pair, honest.root_at(7).unwrap(), honest.root()));
+}
+
+#[test]
+fn poseidon_range() {
+    let range = MerkleMountainRange::<PoseidonHasher>::from_leaves((1..=5u64).map(Fr::from));
+    let poseidon = range.hasher();
+    let expected_peak = poseidon
+        .hash(&poseidon.hash(&Fr::from(1u64), &Fr::from(2u64)), &poseidon.hash(&Fr::from(3u64), &Fr::from(4u64)));
+    assert_eq!(range.root(), poseidon.hash(&expected_peak, &Fr::from(5u64)));
+    assert!(range.inclusion_proof(4, 5).unwrap().verify(poseidon, range.root()));
+    assert!(range.consistency_proof(3, 5).unwrap().verify(poseidon, range.root_at(3).unwrap(), range.root()));
+}
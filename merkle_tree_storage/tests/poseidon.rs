@@ -0,0 +1,66 @@
+use std::str::FromStr;
+
+use merkle_tree_storage::{bigint_to_fr, fr_from_bytes, fr_to_bigint, fr_to_bytes, poseidon::Poseidon, Fr};
+use num_bigint::BigInt;
+
+fn fr(decimal: &str) -> Fr {
+    Fr::from_str(decimal).unwrap()
+}
+
+// BN254 scalar modulus, the first integer that is not a field element
+const MODULUS: &str = "21888242871839275222246405745257275088548364400416034343698204186575808495617";
+
+#[test]
+fn poseidon2_matches_circomlib() {
+    let poseidon = Poseidon::new(2).unwrap();
+    // circomlibjs `poseidon([a, b])`
+    for (inputs, expected) in [
+        ([0, 0], "14744269619966411208579211824598458697587494354926760081771325075741142829156"),
+        ([1, 2], "7853200120776062878684798364095072458815029376092732009249414926327459813530"),
+        ([3, 4], "14763215145315200506921711489642608356394854266165572616578112107564877678998"),
+    ] {
+        assert_eq!(poseidon.hash(&inputs.map(Fr::from)).unwrap(), fr(expected));
+    }
+}
+
+#[test]
+fn other_widths_match_circomlib() {
+    assert_eq!(
+        Poseidon::new(1).unwrap().hash(&[Fr::from(1)]).unwrap(),
+        fr("18586133768512220936620570745912940619677854269274689475585506675881198879027")
+    );
+    assert_eq!(
+        Poseidon::new(4).unwrap().hash(&[1, 2, 3, 4].map(Fr::from)).unwrap(),
+        fr("18821383157269793795438455681495246036402687001665670618754263018637548127333")
+    );
+}
+
+#[test]
+fn wrong_number_of_inputs_is_an_error() {
+    assert!(Poseidon::new(0).is_err());
+    assert!(Poseidon::new(13).is_err());
+    assert!(Poseidon::new(2).unwrap().hash(&[Fr::from(1)]).is_err());
+}
+
+#[test]
+fn conversions_are_lossless() {
+    let largest = -Fr::from(1);
+    for element in [
+        Fr::from(0),
+        Fr::from(1),
+        fr("7853200120776062878684798364095072458815029376092732009249414926327459813530"),
+        largest,
+    ] {
+        assert_eq!(bigint_to_fr(&fr_to_bigint(&element)), Some(element));
+        assert_eq!(fr_from_bytes(&fr_to_bytes(&element)), Some(element));
+    }
+    assert_eq!(fr_to_bigint(&largest) + 1, BigInt::from_str(MODULUS).unwrap());
+}
+
+#[test]
+fn values_outside_the_field_are_rejected() {
+    let modulus = BigInt::from_str(MODULUS).unwrap();
+    assert_eq!(bigint_to_fr(&modulus), None);
+    assert_eq!(bigint_to_fr(&BigInt::from(-1)), None);
+    assert_eq!(fr_from_bytes(&[0xff; 32]), None);
+}
@@ -0,0 +1,63 @@
+use merkle_tree_storage::{Fr, HasherId, KeccakHasher, MerkleTreeStorage, PoseidonHasher, Snapshot};
+
+fn poseidon_tree(count: u64) -> MerkleTreeStorage {
+    MerkleTreeStorage::from_leaves(3, (1..=count).map(Fr::from)).unwrap()
+}
+
+#[test]
+fn snapshots_round_trip_with_and_without_layers() {
+    for count in [0, 1, 5, 8] {
+        let tree = poseidon_tree(count);
+        for with_layers in [false, true] {
+            let bytes = Snapshot::of(&tree, with_layers).to_bytes();
+            let snapshot = Snapshot::from_bytes(&bytes).unwrap();
+            assert_eq!(snapshot.hasher, HasherId::Poseidon);
+            assert_eq!(snapshot.layers.is_some(), with_layers);
+
+            let imported: MerkleTreeStorage = snapshot.into_tree().unwrap();
+            assert_eq!(imported.leaves(), tree.leaves());
+            assert_eq!(imported.root(), tree.root());
+            if let Some(leaf) = tree.leaves().last() {
+                assert_eq!(imported.generate_merkle_proof(*leaf), tree.generate_merkle_proof(*leaf));
+            }
+        }
+    }
+}
+
+#[test]
+fn imported_trees_keep_growing() {
+    let mut imported: MerkleTreeStorage =
+        Snapshot::from_bytes(&Snapshot::of(&poseidon_tree(5), true).to_bytes()).unwrap().into_tree().unwrap();
+    imported.insert_leaf(Fr::from(6u64)).unwrap();
+    assert_eq!(imported.root(), poseidon_tree(6).root());
+}
+
+#[test]
+fn corrupted_snapshots_are_rejected() {
+    let bytes = Snapshot::of(&poseidon_tree(5), true).to_bytes();
+
+    let mut flipped = bytes.clone();
+    flipped[30] ^= 1;
+    assert!(Snapshot::from_bytes(&flipped).is_err());
+    assert!(Snapshot::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    assert!(Snapshot::from_bytes(b"not a snapshot").is_err());
+
+    let mut newer = bytes.clone();
+    newer[4] = 2;
+    assert!(Snapshot::from_bytes(&newer).unwrap_err().to_string().contains("version"));
+}
+
+#[test]
+fn snapshots_check_the_hasher_and_the_root() {
+    let snapshot = Snapshot::of(&poseidon_tree(2), false);
+    assert!(snapshot.clone().into_tree::<KeccakHasher>().is_err());
+
+    let mut wrong_root = snapshot;
+    wrong_root.root[31] ^= 1;
+    assert!(wrong_root.into_tree::<PoseidonHasher>().is_err());
+
+    let keccak = MerkleTreeStorage::<KeccakHasher>::from_leaves(2, [[1; 32], [2; 32]]).unwrap();
+    let imported: MerkleTreeStorage<KeccakHasher> =
+        Snapshot::from_bytes(&Snapshot::of(&keccak, false).to_bytes()).unwrap().into_tree().unwrap();
+    assert_eq!(imported.root(), keccak.root());
+}
@@ -0,0 +1,278 @@
+use std::str::FromStr;
+
+use groth16_verifier::codec::Codec;
+use merkle_tree_storage::{Fr, MerkleHasher, MerkleTreeStorage, PoseidonHasher, TreeError};
+
+// Order-sensitive and cheap, so expected roots can be worked out by hand
+#[derive(Default)]
+struct Pair;
+
+impl MerkleHasher for Pair {
+    type Node = u64;
+
+    fn zero(&self) -> u64 {
+        0
+    }
+
+    fn hash(&self, left: &u64, right: &u64) -> u64 {
+        3 * left + 7 * right + 1
+    }
+}
+
+fn tree_of(depth: u32, count: u64) -> MerkleTreeStorage<Pair> {
+    let mut tree = MerkleTreeStorage::new(depth);
+    for leaf in 1..=count {
+        tree.insert_leaf(leaf).unwrap();
+    }
+    tree
+}
+
+#[test]
+fn empty_slots_hash_as_zero_leaves() {
+    let h = |l, r| Pair.hash(&l, &r);
+    assert_eq!(MerkleTreeStorage::<Pair>::new(2).root(), h(h(0, 0), h(0, 0)));
+    assert_eq!(tree_of(2, 3).root(), h(h(1, 2), h(3, 0)));
+    assert_eq!(tree_of(2, 4).root(), h(h(1, 2), h(3, 4)));
+}
+
+#[test]
+fn appending_matches_a_rebuild() {
+    for count in 0..=16 {
+        let appended = tree_of(4, count);
+        let rebuilt = MerkleTreeStorage::<Pair>::from_leaves(4, 1..=count).unwrap();
+        assert_eq!(appended.root(), rebuilt.root(), "{} leaves", count);
+    }
+}
+
+#[test]
+fn proofs_verify_for_every_leaf() {
+    let tree = tree_of(4, 11);
+    for leaf in 1..=11u64 {
+        let proof = tree.generate_merkle_proof(leaf).unwrap();
+        assert_eq!(proof.position, leaf as usize - 1);
+        assert_eq!(proof.depth(), 4);
+        assert_eq!(proof.root, tree.root());
+        assert_eq!(proof.indices(), (0..4).map(|i| (proof.position >> i) & 1 == 1).collect::<Vec<_>>());
+        assert!(proof.verify(tree.hasher()));
+
+        let mut tampered = proof.clone();
+        tampered.leaf += 100;
+        assert!(!tampered.verify(tree.hasher()));
+    }
+    assert!(tree.generate_merkle_proof(12).is_none());
+}
+
+#[test]
+fn append_proof_covers_the_next_empty_slot() {
+    let mut tree = tree_of(3, 5);
+    let proof = tree.append_proof().unwrap();
+    assert_eq!(proof.position, 5);
+    assert_eq!(proof.leaf, 0);
+    assert!(proof.verify(tree.hasher()));
+
+    let new_root = proof.root_with_leaf(tree.hasher(), 42);
+    tree.insert_leaf(42).unwrap();
+    assert_eq!(tree.root(), new_root);
+}
+
+#[test]
+fn prepared_appends_change_nothing_until_applied() {
+    let mut tree = tree_of(3, 5);
+    let root = tree.root();
+    let prepared = tree.prepare_append(42).unwrap();
+    assert_eq!(prepared.position, 5);
+    assert!(prepared.append_proof.verify(tree.hasher()));
+    assert!(prepared.proof.verify(tree.hasher()));
+
+    // E.g. the transaction storing the leaf failed: the tree is as it was
+    drop(prepared);
+    assert_eq!((tree.len(), tree.root()), (5, root));
+
+    let prepared = tree.prepare_append(42).unwrap();
+    assert_eq!(tree.apply_append(&prepared), Ok(5));
+    assert_eq!(tree.root(), prepared.proof.root);
+    assert_eq!(tree.generate_merkle_proof_at(5), Some(prepared.proof.clone()));
+
+    // An append prepared before the tree moved on is refused
+    assert_eq!(tree.apply_append(&prepared), Err(TreeError::InvalidSize { size: 5, len: 6 }));
+}
+
+#[test]
+fn full_trees_reject_leaves() {
+    let mut tree = tree_of(2, 4);
+    assert_eq!(tree.capacity(), 4);
+    assert_eq!(tree.insert_leaf(5), Err(TreeError::Full));
+    assert_eq!(tree.append_proof().unwrap_err(), TreeError::Full);
+    assert_eq!(MerkleTreeStorage::<Pair>::from_leaves(2, 1..=5).err(), Some(TreeError::Full));
+
+    tree.reset_tree();
+    assert_eq!(tree.root(), MerkleTreeStorage::<Pair>::new(2).root());
+    assert_eq!(tree.insert_leaf(5).unwrap(), 0);
+}
+
+#[test]
+fn circuit_paths_are_padded_to_the_circuit_depth() {
+    let mut tree = MerkleTreeStorage::<PoseidonHasher>::new(3);
+    for value in ["1", "2", "3"] {
+        tree.insert_leaf(Fr::from_str(value).unwrap()).unwrap();
+    }
+    let proof = tree.generate_merkle_proof(Fr::from_str("3").unwrap()).unwrap();
+    let path = proof.to_circuit_path(20).unwrap();
+    assert_eq!(path.depth, 3);
+    assert_eq!(path.path_indices, 2);
+    assert_eq!(path.path_elements.len(), 20);
+    assert!(path.path_elements[3..].iter().all(|element| *element == Fr::from(0u64)));
+    assert_eq!(path.path_elements[..3], proof.siblings[..]);
+    assert!(proof.to_circuit_path(2).is_err());
+}
+
+#[test]
+fn earlier_roots_are_kept() {
+    let tree = tree_of(4, 11);
+    assert_eq!(tree.len(), 11);
+    for size in 0..=11 {
+        assert_eq!(tree.root_at(size).unwrap(), tree_of(4, size as u64).root(), "{} leaves", size);
+    }
+    assert_eq!(tree.root_at(12), Err(TreeError::InvalidSize { size: 12, len: 11 }));
+}
+
+#[test]
+fn inclusion_against_earlier_sizes() {
+    let tree = tree_of(4, 11);
+    for size in 1..=11 {
+        for leaf in 1..=size as u64 {
+            let proof = tree.inclusion_proof_at(leaf, size).unwrap();
+            assert_eq!(proof.root, tree.root_at(size).unwrap());
+            assert!(proof.verify(tree.hasher()), "leaf {} at size {}", leaf, size);
+        }
+    }
+    assert_eq!(tree.inclusion_proof_at(11, 11).unwrap(), tree.generate_merkle_proof(11).unwrap());
+    assert_eq!(tree.inclusion_proof_at(6, 5), Err(TreeError::LeafAfterSize { position: 5, size: 5 }));
+    assert_eq!(tree.inclusion_proof_at(12, 11), Err(TreeError::LeafNotFound));
+    assert_eq!(tree.inclusion_proof_at(1, 12), Err(TreeError::InvalidSize { size: 12, len: 11 }));
+}
+
+#[test]
+fn consistency_between_every_pair_of_sizes() {
+    let tree = tree_of(4, 13);
+    for new_size in 0..=13 {
+        for old_size in 0..=new_size {
+            let proof = tree.consistency_proof(old_size, new_size).unwrap();
+            assert_eq!(proof.depth(), 4);
+            let (old_root, new_root) = (tree.root_at(old_size).unwrap(), tree.root_at(new_size).unwrap());
+            assert!(proof.verify(&Pair, old_root, new_root), "{} -> {}", old_size, new_size);
+        }
+    }
+    assert_eq!(tree.consistency_proof(6, 5).unwrap_err(), TreeError::InvalidRange { old_size: 6, new_size: 5 });
+    assert_eq!(tree.consistency_proof(6, 14).unwrap_err(), TreeError::InvalidSize { size: 14, len: 13 });
+}
+
+#[test]
+fn rewritten_history_is_not_consistent() {
+    let honest = tree_of(4, 10);
+    // Same size, leaf 3 replaced
+    let forked =
+        MerkleTreeStorage::<Pair>::from_leaves(4, (1..=10).map(|leaf| if leaf == 3 { 99 } else { leaf })).unwrap();
+    let old_root = honest.root_at(6).unwrap();
+
+    let proof = forked.consistency_proof(6, 10).unwrap();
+    assert!(!proof.verify(&Pair, old_root, forked.root()));
+
+    let proof = honest.consistency_proof(6, 10).unwrap();
+    assert!(proof.verify(&Pair, old_root, honest.root()));
+    assert!(!proof.verify(&Pair, honest.root_at(7).unwrap(), honest.root()));
+    assert!(!proof.verify(&Pair, old_root, forked.root()));
+
+    let mut shrunk = proof.clone();
+    shrunk.new_size = 5;
+    assert!(!shrunk.verify(&Pair, old_root, honest.root()));
+}
+
+#[test]
+fn stored_rows_rebuild_the_tree() {
+    let leaves: Vec<Fr> = [1u64, 2, 1].into_iter().map(Fr::from).collect();
+    let stored = serde_json::json!(leaves.iter().map(Codec::to_hex).collect::<Vec<_>>());
+    let tree = MerkleTreeStorage::from_stored("CoreIdTree", 2, stored.clone(), 4).unwrap();
+    assert_eq!(tree.leaves(), &leaves[..]);
+    assert_eq!(tree.to_stored(), (stored.clone(), 4));
+    assert_eq!(tree.root(), MerkleTreeStorage::<PoseidonHasher>::from_leaves(2, leaves.clone()).unwrap().root());
+    assert!(MerkleTreeStorage::from_stored("CoreIdTree", 2, stored, 2).is_err());
+
+    // Older rows store the hexes without `0x`
+    let unprefixed = serde_json::json!(leaves.iter().map(|leaf| leaf.to_hex()[2..].to_string()).collect::<Vec<_>>());
+    assert_eq!(MerkleTreeStorage::from_stored("CoreIdTree", 2, unprefixed, 4).unwrap().leaves(), &leaves[..]);
+}
+
+#[test]
+fn legacy_position_maps_are_still_read() {
+    let leaves: Vec<Fr> = (1..=3u64).map(Fr::from).collect();
+    let stored = serde_json::json!({
+        leaves[2].to_hex(): 2,
+        leaves[0].to_hex(): 0,
+        leaves[1].to_hex(): 1,
+    });
+    let tree = MerkleTreeStorage::from_stored("CoreIdTree", 2, stored, 4).unwrap();
+    assert_eq!(tree.leaves(), &leaves[..]);
+
+    let gap = serde_json::json!({ Fr::from(1u64).to_hex(): 0, Fr::from(2u64).to_hex(): 2 });
+    assert!(MerkleTreeStorage::from_stored("CoreIdTree", 2, gap, 4).is_err());
+}
+
+#[test]
+fn parallel_rebuild_matches_sequential() {
+    // Enough leaves that the lowest layers are split between threads
+    let leaves: Vec<u64> = (1..=3000).collect();
+    let mut tree = MerkleTreeStorage::<Pair>::from_leaves(12, leaves.clone()).unwrap();
+    let parallel = tree.root();
+    tree.generate_merkle_tree();
+    assert_eq!(tree.root(), parallel);
+
+    let mut inserted = MerkleTreeStorage::<Pair>::new(12);
+    for leaf in leaves {
+        inserted.insert_leaf(leaf).unwrap();
+    }
+    assert_eq!(inserted.root(), parallel);
+}
+
+#[test]
+fn duplicate_leaves_keep_every_position() {
+    let mut tree = MerkleTreeStorage::<Pair>::from_leaves(3, [5, 6, 5]).unwrap();
+    assert_eq!(tree.insert_leaf(5).unwrap(), 3);
+    assert_eq!(tree.len(), 4);
+    assert_eq!(tree.leaves(), &[5, 6, 5, 5]);
+    assert_eq!(tree.leaf_at(2), Some(5));
+    assert_eq!(tree.leaf_at(4), None);
+    assert_eq!(tree.position_of(&5), Some(0));
+    assert_eq!(tree.positions_of(&5), vec![0, 2, 3]);
+    assert_eq!(tree.positions_of(&7), Vec::<usize>::new());
+
+    // Every copy has its own proof against the same root
+    let first = tree.generate_merkle_proof(5).unwrap();
+    let last = tree.generate_merkle_proof_at(3).unwrap();
+    assert_eq!((first.position, last.position), (0, 3));
+    assert!(first.verify(&Pair) && last.verify(&Pair));
+    assert_eq!(first.root, last.root);
+
+    // Another copy is proven in its own slot, with the same path before and after
+    let prepared = tree.prepare_append(5).unwrap();
+    assert_eq!((prepared.position, prepared.append_proof.position, prepared.proof.position), (4, 4, 4));
+    assert_eq!(prepared.append_proof.siblings, prepared.proof.siblings);
+
+    tree.reset_tree();
+    assert_eq!(tree.positions_of(&5), Vec::<usize>::new());
+}
+
+#[test]
+fn unique_inserts_reject_duplicates() {
+    let mut tree = MerkleTreeStorage::<Pair>::from_leaves(2, [1, 2]).unwrap();
+    let root = tree.root();
+    assert_eq!(tree.insert_unique_leaf(2), Err(TreeError::DuplicateLeaf { position: 1 }));
+    assert_eq!((tree.len(), tree.root()), (2, root));
+    assert_eq!(tree.insert_unique_leaf(3).unwrap(), 2);
+}
+
+#[test]
+fn depths_outside_the_supported_range_are_rejected() {
+    assert_eq!(MerkleTreeStorage::try_with_hasher(0, Pair).err(), Some(TreeError::InvalidDepth(0)));
+    assert_eq!(MerkleTreeStorage::<Pair>::from_leaves(33, [1]).err(), Some(TreeError::InvalidDepth(33)));
+}
@@ -0,0 +1,118 @@
+//! Exports trees to snapshot files and imports them again, see `merkle_tree_storage::Snapshot`.
+
+use std::{env, fs, process::ExitCode};
+
+use anyhow::{anyhow, Context, Result};
+use merkle_tree_storage::{
+    HasherId, KeccakHasher, MerkleTreeStorage, PoseidonHasher, Sha256Hasher, Snapshot, SnapshotHasher,
+};
+use sqlx::{postgres::PgPoolOptions, PgPool};
+
+const USAGE: &str = "Usage: tree_snapshot <command>
+
+  export <table> <depth> <file> [--layers]  snapshot the tree in a storage table, e.g. CoreIdTree
+  import <file> <table>                     replace the tree in a storage table with the snapshot
+  inspect <file>                            print the snapshot's header and root
+  prove <file> <leaf-hex>                   print the proof for a leaf, without a database
+
+export and import read DATABASE_URL.";
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["export", table, depth, file] => export(table, depth, file, false).await,
+        ["export", table, depth, file, "--layers"] => export(table, depth, file, true).await,
+        ["import", file, table] => import(file, table).await,
+        ["inspect", file] => inspect(file),
+        ["prove", file, leaf] => prove(file, leaf),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {:#}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn export(table: &str, depth: &str, file: &str, with_layers: bool) -> Result<()> {
+    let depth: u32 = depth.parse().with_context(|| format!("Invalid depth {}", depth))?;
+    let tree = MerkleTreeStorage::load(&connect().await?, table, depth).await?;
+    fs::write(file, Snapshot::of(&tree, with_layers).to_bytes())
+        .with_context(|| format!("Failed to write {}", file))?;
+    println!("Exported {} leaves of {} to {}", tree.len(), table, file);
+    Ok(())
+}
+
+async fn import(file: &str, table: &str) -> Result<()> {
+    // Storage tables only hold Poseidon trees
+    let tree: MerkleTreeStorage = read(file)?.into_tree()?;
+    tree.store(&connect().await?, table).await?;
+    println!("Imported {} leaves into {}", tree.len(), table);
+    Ok(())
+}
+
+fn inspect(file: &str) -> Result<()> {
+    let snapshot = read(file)?;
+    println!("hasher  {}", snapshot.hasher.name());
+    println!("depth   {}", snapshot.depth);
+    println!("leaves  {}", snapshot.leaves.len());
+    println!("layers  {}", if snapshot.layers.is_some() { "included" } else { "not included" });
+    println!("root    {}", hex(&snapshot.root));
+    Ok(())
+}
+
+fn prove(file: &str, leaf: &str) -> Result<()> {
+    let snapshot = read(file)?;
+    match snapshot.hasher {
+        HasherId::Poseidon => print_proof::<PoseidonHasher>(snapshot, leaf),
+        HasherId::Keccak => print_proof::<KeccakHasher>(snapshot, leaf),
+        HasherId::Sha256 => print_proof::<Sha256Hasher>(snapshot, leaf),
+    }
+}
+
+fn print_proof<H: SnapshotHasher + Default>(snapshot: Snapshot, leaf: &str) -> Result<()> {
+    let tree: MerkleTreeStorage<H> = snapshot.into_tree()?;
+    let leaf = H::node_from_bytes(&parse_hex(leaf)?).ok_or_else(|| anyhow!("{} is not a leaf of this tree", leaf))?;
+    let proof = tree.generate_merkle_proof(leaf).ok_or_else(|| anyhow!("Leaf not found"))?;
+    println!("position  {}", proof.position);
+    println!("root      {}", hex(&H::node_to_bytes(&proof.root)));
+    for (height, sibling) in proof.siblings.iter().enumerate() {
+        println!("sibling {:>2} {}", height, hex(&H::node_to_bytes(sibling)));
+    }
+    Ok(())
+}
+
+async fn connect() -> Result<PgPool> {
+    let database_url = env::var("DATABASE_URL").context("DATABASE_URL must be set")?;
+    Ok(PgPoolOptions::new().max_connections(1).connect(&database_url).await?)
+}
+
+fn read(file: &str) -> Result<Snapshot> {
+    let bytes = fs::read(file).with_context(|| format!("Failed to read {}", file))?;
+    Snapshot::from_bytes(&bytes).with_context(|| format!("Invalid snapshot {}", file))
+}
+
+fn hex(bytes: &[u8; 32]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Up to 64 hex digits, big-endian, with or without `0x`
+fn parse_hex(hex: &str) -> Result<[u8; 32]> {
+    let digits = hex.strip_prefix("0x").unwrap_or(hex);
+    if digits.is_empty() || digits.len() > 64 || !digits.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow!("{:?} is not a hex node", hex));
+    }
+    let padded = format!("{:0>64}", digits);
+    let mut bytes = [0u8; 32];
+    for (byte, pair) in bytes.iter_mut().zip(padded.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair)?, 16)?;
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,14 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS ProvingJob (
+    id UUID PRIMARY KEY,
+    circuit VARCHAR NOT NULL,
+    witness JSONB NOT NULL,
+    status VARCHAR NOT NULL,
+    signature VARCHAR,
+    error VARCHAR,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS provingjob_status_created_at_idx ON ProvingJob (status, created_at);
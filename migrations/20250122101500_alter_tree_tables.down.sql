@@ -0,0 +1,5 @@
+-- Add down migration script here
+ALTER TABLE MerchantRecordTree ALTER COLUMN capacity TYPE INTEGER;
+ALTER TABLE MerchantJoinTree ALTER COLUMN capacity TYPE INTEGER;
+ALTER TABLE CoreIdTree ALTER COLUMN capacity TYPE INTEGER;
+ALTER TABLE MerchantJoinTree RENAME TO MerchantJoinIdTree;
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Add up migration script here
+ALTER TABLE MerchantJoinIdTree RENAME TO MerchantJoinTree;
+ALTER TABLE CoreIdTree ALTER COLUMN capacity TYPE BIGINT;
+ALTER TABLE MerchantJoinTree ALTER COLUMN capacity TYPE BIGINT;
+ALTER TABLE MerchantRecordTree ALTER COLUMN capacity TYPE BIGINT;
\ No newline at end of file
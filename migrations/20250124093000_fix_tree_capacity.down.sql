@@ -0,0 +1,4 @@
+-- Add down migration script here
+UPDATE MerchantRecordTree SET capacity = capacity / 2 WHERE capacity > 1;
+UPDATE MerchantJoinTree SET capacity = capacity / 2 WHERE capacity > 1;
+UPDATE CoreIdTree SET capacity = capacity / 2 WHERE capacity > 1;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Capacity is 2^depth, it used to be stored as 2^(depth - 1)
+UPDATE CoreIdTree SET capacity = capacity * 2;
+UPDATE MerchantJoinTree SET capacity = capacity * 2;
+UPDATE MerchantRecordTree SET capacity = capacity * 2;
@@ -0,0 +1,10 @@
+-- Add up migration script here
+CREATE TABLE IF NOT EXISTS SignedTreeHead (
+    tree_size BIGINT PRIMARY KEY,
+    root_hash VARCHAR NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    public_key VARCHAR NOT NULL,
+    signature VARCHAR NOT NULL,
+    anchor_signature VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
\ No newline at end of file
@@ -0,0 +1,5 @@
+-- Add down migration script here
+-- Maps keep one position per leaf, a tree holding a leaf twice no longer loads after this
+UPDATE CoreIdTree SET leaves = (SELECT COALESCE(jsonb_object_agg(value, position - 1), '{}'::JSONB) FROM (SELECT DISTINCT ON (value) value, position FROM jsonb_array_elements_text(leaves) WITH ORDINALITY AS t(value, position) ORDER BY value, position) first) WHERE jsonb_typeof(leaves) = 'array';
+UPDATE MerchantJoinTree SET leaves = (SELECT COALESCE(jsonb_object_agg(value, position - 1), '{}'::JSONB) FROM (SELECT DISTINCT ON (value) value, position FROM jsonb_array_elements_text(leaves) WITH ORDINALITY AS t(value, position) ORDER BY value, position) first) WHERE jsonb_typeof(leaves) = 'array';
+UPDATE MerchantRecordTree SET leaves = (SELECT COALESCE(jsonb_object_agg(value, position - 1), '{}'::JSONB) FROM (SELECT DISTINCT ON (value) value, position FROM jsonb_array_elements_text(leaves) WITH ORDINALITY AS t(value, position) ORDER BY value, position) first) WHERE jsonb_typeof(leaves) = 'array';
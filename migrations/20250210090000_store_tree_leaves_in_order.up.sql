@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Leaves are an array in slot order, so a leaf stored twice keeps both slots
+UPDATE CoreIdTree SET leaves = (SELECT COALESCE(jsonb_agg(key ORDER BY value::BIGINT), '[]'::JSONB) FROM jsonb_each_text(leaves)) WHERE jsonb_typeof(leaves) = 'object';
+UPDATE MerchantJoinTree SET leaves = (SELECT COALESCE(jsonb_agg(key ORDER BY value::BIGINT), '[]'::JSONB) FROM jsonb_each_text(leaves)) WHERE jsonb_typeof(leaves) = 'object';
+UPDATE MerchantRecordTree SET leaves = (SELECT COALESCE(jsonb_agg(key ORDER BY value::BIGINT), '[]'::JSONB) FROM jsonb_each_text(leaves)) WHERE jsonb_typeof(leaves) = 'object';
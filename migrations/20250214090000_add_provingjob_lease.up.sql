@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Refreshed by the worker holding the job, a job whose lease went stale is taken over
+ALTER TABLE ProvingJob ADD COLUMN IF NOT EXISTS locked_at TIMESTAMP;
+UPDATE ProvingJob SET locked_at = updated_at WHERE status IN ('proving', 'submitted');
@@ -0,0 +1,4 @@
+-- Add down migration script here
+-- CoreIdTree stays at depth 20, the backend has always kept it there
+UPDATE MerchantRecordTree SET capacity = 4294967296 WHERE capacity = 1048576;
+UPDATE MerchantJoinTree SET capacity = 4294967296 WHERE capacity = 1048576;
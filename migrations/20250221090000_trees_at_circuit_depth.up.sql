@@ -0,0 +1,6 @@
+-- Add up migration script here
+-- Trees are kept at the circuits' depth 20, rows the derive wrote at depth 32 are shrunk if their leaves fit.
+-- Their roots change, so rebuild the trees and anchor the new roots on chain afterwards.
+UPDATE CoreIdTree SET capacity = 1048576 WHERE capacity = 4294967296 AND jsonb_array_length(leaves) <= 1048576;
+UPDATE MerchantJoinTree SET capacity = 1048576 WHERE capacity = 4294967296 AND jsonb_array_length(leaves) <= 1048576;
+UPDATE MerchantRecordTree SET capacity = 1048576 WHERE capacity = 4294967296 AND jsonb_array_length(leaves) <= 1048576;
@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE ProvingJob DROP COLUMN IF EXISTS blockhash;
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Blockhash the submitted transaction was signed with, once it expired the transaction cannot land
+ALTER TABLE ProvingJob ADD COLUMN IF NOT EXISTS blockhash VARCHAR;
@@ -0,0 +1,148 @@
+use merkle_tree_storage::{Fr, MerkleTreeStorage};
+use uuid::Uuid;
+
+use crate::rows::RegistryRow;
+
+/// First slot where the replayed tree and the stored one disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// The row replayed into `position` has no leaf, e.g. a hash column that is not hex.
+    InvalidRow {
+        position: usize,
+        row: Uuid,
+        error: String,
+    },
+    /// Slot `position` holds `stored` but the rows replay `replayed` into it. `None` on one
+    /// side means that tree ends before the slot.
+    Leaf {
+        position: usize,
+        row: Option<Uuid>,
+        replayed: Option<Fr>,
+        stored: Option<Fr>,
+        /// Slot the stored tree has the replayed leaf in, if it has it elsewhere.
+        stored_position: Option<usize>,
+    },
+}
+
+impl Divergence {
+    pub fn position(&self) -> usize {
+        match self {
+            Divergence::InvalidRow { position, .. } | Divergence::Leaf { position, .. } => {
+                *position
+            }
+        }
+    }
+}
+
+/// How the on-chain root relates to the replayed tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainCheck {
+    /// The chain was not read.
+    Skipped,
+    Matches,
+    /// The chain holds the replayed root at `size` leaves, i.e. it has not caught up yet.
+    Behind {
+        size: usize,
+    },
+    /// The chain holds a root no prefix of the replay has.
+    Diverged,
+}
+
+/// Outcome of replaying one tree.
+pub struct TreeAudit {
+    pub table: &'static str,
+    pub rows: usize,
+    pub replayed: MerkleTreeStorage,
+    pub stored: MerkleTreeStorage,
+    pub divergence: Option<Divergence>,
+    pub on_chain_root: Option<Fr>,
+    pub chain: ChainCheck,
+}
+
+impl TreeAudit {
+    /// Whether rows, stored tree and chain all agree. A chain that is only behind counts as
+    /// agreeing.
+    pub fn is_consistent(&self) -> bool {
+        self.divergence.is_none() && self.chain != ChainCheck::Diverged
+    }
+}
+
+/// Replays `rows`, already in replay order, into a tree of `depth` and compares it slot by
+/// slot with `stored`, and its roots with `on_chain_root`.
+pub fn audit_tree<R: RegistryRow>(
+    table: &'static str,
+    depth: u32,
+    rows: &[R],
+    stored: MerkleTreeStorage,
+    on_chain_root: Option<Fr>,
+) -> TreeAudit {
+    let mut replayed = MerkleTreeStorage::new(depth);
+    let mut divergence = None;
+    for row in rows {
+        let position = replayed.len();
+        let leaf = match row.leaf() {
+            Ok(leaf) => leaf,
+            Err(err) => {
+                // Later rows would all land one slot early, the first bad row is what matters
+                divergence.get_or_insert(Divergence::InvalidRow {
+                    position,
+                    row: row.id(),
+                    error: err.to_string(),
+                });
+                continue;
+            }
+        };
+        if let Err(err) = replayed.insert_leaf(leaf) {
+            divergence.get_or_insert(Divergence::InvalidRow {
+                position,
+                row: row.id(),
+                error: err.to_string(),
+            });
+            break;
+        }
+        if divergence.is_none() && stored.leaves().get(position) != Some(&leaf) {
+            divergence = Some(Divergence::Leaf {
+                position,
+                row: Some(row.id()),
+                replayed: Some(leaf),
+                stored: stored.leaves().get(position).copied(),
+                stored_position: stored
+                    .generate_merkle_proof(leaf)
+                    .map(|proof| proof.position),
+            });
+        }
+    }
+    if divergence.is_none() && stored.len() > replayed.len() {
+        divergence = Some(Divergence::Leaf {
+            position: replayed.len(),
+            row: None,
+            replayed: None,
+            stored: Some(stored.leaves()[replayed.len()]),
+            stored_position: None,
+        });
+    }
+
+    let chain = match on_chain_root {
+        None => ChainCheck::Skipped,
+        Some(root) if root == replayed.root() => ChainCheck::Matches,
+        // Most recent sizes first, the chain usually lags by a few leaves at most
+        Some(root) => (0..replayed.len())
+            .rev()
+            .find(|size| {
+                replayed
+                    .root_at(*size)
+                    .is_ok_and(|replayed_root| replayed_root == root)
+            })
+            .map_or(ChainCheck::Diverged, |size| ChainCheck::Behind { size }),
+    };
+
+    TreeAudit {
+        table,
+        rows: rows.len(),
+        replayed,
+        stored,
+        divergence,
+        on_chain_root,
+        chain,
+    }
+}
@@ -0,0 +1,69 @@
+//! Replays the registry from its rows to check the trees the backend keeps.
+//!
+//! Every `CoreId`, `MerchantJoinId` and `MerchantRecord` row is turned into its canonical leaf,
+//! the `MerkleTree::to_leaf_hash` of its `merkle_tree` model, and appended in replay order
+//! (creation time, then id) to a fresh tree. That tree is compared slot by slot with the one
+//! in the storage table, and its root with the one in the on-chain `HashAccount`.
+
+mod audit;
+pub mod rows;
+pub mod source;
+
+use anyhow::{Context, Result};
+use merkle_tree::{CoreId, MerchantJoinId, MerchantRecord, MerkleTree};
+
+pub use audit::{audit_tree, ChainCheck, Divergence, TreeAudit};
+pub use source::{Registry, StoredTree, EXPORT_QUERY};
+
+/// Audits the three registry trees. The on-chain roots are only read with `read_chain`.
+///
+/// Each tree is rebuilt at its model's `MerkleTree::DEPTH`, the circuit depth the backend keeps
+/// the storage tables at, so a tree `--rebuild` stores is one the backend can load.
+pub async fn audit_registry(registry: &Registry, read_chain: bool) -> Result<Vec<TreeAudit>> {
+    let chain_roots = if read_chain {
+        let core_id = CoreId::read_on_chain_root()
+            .await
+            .context("Failed to read the on-chain roots")?;
+        let merchant_join = MerchantJoinId::read_on_chain_root().await?;
+        let merchant_record = MerchantRecord::read_on_chain_root().await?;
+        [Some(core_id), Some(merchant_join), Some(merchant_record)]
+    } else {
+        [None; 3]
+    };
+
+    Ok(vec![
+        audit_tree(
+            CoreId::STORAGE,
+            CoreId::DEPTH,
+            &registry.core_ids,
+            StoredTree::rebuild(
+                registry.core_id_tree.as_ref(),
+                CoreId::STORAGE,
+                CoreId::DEPTH,
+            )?,
+            chain_roots[0],
+        ),
+        audit_tree(
+            MerchantJoinId::STORAGE,
+            MerchantJoinId::DEPTH,
+            &registry.merchant_joins,
+            StoredTree::rebuild(
+                registry.merchant_join_tree.as_ref(),
+                MerchantJoinId::STORAGE,
+                MerchantJoinId::DEPTH,
+            )?,
+            chain_roots[1],
+        ),
+        audit_tree(
+            MerchantRecord::STORAGE,
+            MerchantRecord::DEPTH,
+            &registry.merchant_records,
+            StoredTree::rebuild(
+                registry.merchant_record_tree.as_ref(),
+                MerchantRecord::STORAGE,
+                MerchantRecord::DEPTH,
+            )?,
+            chain_roots[2],
+        ),
+    ])
+}
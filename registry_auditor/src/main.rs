@@ -0,0 +1,146 @@
+//! `registry_auditor [--export registry.json] [--no-chain] [--rebuild]`
+//!
+//! Reads the registry from `DATABASE_URL`, or from an export made with `EXPORT_QUERY`, and
+//! reports per tree whether the rows, the storage table and the chain agree. Exits with 1
+//! when one of them diverges.
+//!
+//! `--rebuild` replaces each storage table whose tree differs from the replayed one with the
+//! replayed tree. Trees written before the backend hashed leaves with `to_leaf_hash` differ
+//! from the first slot on, and have to be rebuilt once: stop the backend, run
+//! `registry_auditor --no-chain --rebuild` and start it again. The next anchored tree head
+//! then moves the on-chain root to the rebuilt tree.
+
+use anyhow::{anyhow, Context, Result};
+use groth16_verifier::codec::Codec;
+use registry_auditor::{audit_registry, ChainCheck, Divergence, Registry, TreeAudit};
+use sqlx::postgres::PgPoolOptions;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut export = None;
+    let mut read_chain = true;
+    let mut rebuild = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--export" => {
+                export = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("--export needs a file"))?,
+                )
+            }
+            "--no-chain" => read_chain = false,
+            "--rebuild" => rebuild = true,
+            _ => {
+                return Err(anyhow!(
+                    "Unknown argument {}, usage: registry_auditor [--export FILE] [--no-chain] [--rebuild]",
+                    arg
+                ))
+            }
+        }
+    }
+
+    let (registry, pool) = match export {
+        Some(_) if rebuild => return Err(anyhow!("--rebuild writes to DATABASE_URL, not to an export")),
+        Some(path) => {
+            let json = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path))?;
+            (Registry::from_export(&json)?, None)
+        }
+        None => {
+            let database_url = std::env::var("DATABASE_URL")
+                .context("DATABASE_URL must be set without --export")?;
+            let pool = PgPoolOptions::new()
+                .max_connections(1)
+                .connect(&database_url)
+                .await?;
+            (Registry::from_database(&pool).await?, Some(pool))
+        }
+    };
+
+    let audits = audit_registry(&registry, read_chain).await?;
+    for audit in &audits {
+        report(audit);
+    }
+    if let (true, Some(pool)) = (rebuild, pool) {
+        for audit in audits.iter().filter(|audit| audit.divergence.is_some()) {
+            if let Some(Divergence::InvalidRow { row, .. }) = &audit.divergence {
+                return Err(anyhow!("{}: row {} has no leaf, fix it before rebuilding", audit.table, row));
+            }
+            audit.replayed.store(&pool, audit.table).await?;
+            println!("{}: rebuilt from {} rows", audit.table, audit.rows);
+        }
+        return Ok(());
+    }
+    if !audits.iter().all(TreeAudit::is_consistent) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn report(audit: &TreeAudit) {
+    println!(
+        "{}: {} rows replayed, {} leaves stored",
+        audit.table,
+        audit.rows,
+        audit.stored.len()
+    );
+    let replayed_root = audit.replayed.root();
+    let stored_root = audit.stored.root();
+    println!("  replayed root  {}", replayed_root.to_hex());
+    println!(
+        "  stored root    {} ({})",
+        stored_root.to_hex(),
+        agreement(stored_root == replayed_root)
+    );
+    if let Some(root) = audit.on_chain_root {
+        let chain = match audit.chain {
+            ChainCheck::Matches => "matches".to_string(),
+            ChainCheck::Behind { size } => format!("replayed root at {} leaves", size),
+            ChainCheck::Diverged | ChainCheck::Skipped => "matches no replayed size".to_string(),
+        };
+        println!("  on-chain root  {} ({})", root.to_hex(), chain);
+    }
+
+    match &audit.divergence {
+        None => println!("  no divergence"),
+        Some(Divergence::InvalidRow {
+            position,
+            row,
+            error,
+        }) => {
+            println!(
+                "  first divergence at slot {}: row {} has no leaf: {}",
+                position, row, error
+            )
+        }
+        Some(Divergence::Leaf {
+            position,
+            row,
+            replayed,
+            stored,
+            stored_position,
+        }) => {
+            let hex = |leaf: &Option<_>| leaf.as_ref().map_or("none".to_string(), Codec::to_hex);
+            let row = row.map_or("no row".to_string(), |row| format!("row {}", row));
+            println!(
+                "  first divergence at slot {} ({}): replayed {}, stored {}",
+                position,
+                row,
+                hex(replayed),
+                hex(stored)
+            );
+            if let Some(stored_position) = stored_position {
+                println!("  the replayed leaf is stored at slot {}", stored_position);
+            }
+        }
+    }
+}
+
+fn agreement(matches: bool) -> &'static str {
+    if matches {
+        "matches"
+    } else {
+        "differs"
+    }
+}
@@ -0,0 +1,145 @@
+//! Registry rows as the backend stores them, and the canonical leaf each one is committed as.
+
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use groth16_verifier::codec::Codec;
+use merkle_tree::{CoreId, MerchantJoinId, MerchantRecord, MerkleTree};
+use merkle_tree_storage::Fr;
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+/// A row that is a leaf of one of the registry trees.
+pub trait RegistryRow {
+    /// Table the rows are read from.
+    const TABLE: &'static str;
+    /// Order the rows were appended in, for `ORDER BY`.
+    const REPLAY_ORDER: &'static str;
+
+    fn id(&self) -> Uuid;
+
+    /// Sort key matching `REPLAY_ORDER`, rows without a timestamp first.
+    fn replay_key(&self) -> (Option<NaiveDateTime>, Uuid);
+
+    /// Leaf of the row, the `MerkleTree::to_leaf_hash` of its model.
+    fn leaf(&self) -> Result<Fr>;
+}
+
+#[derive(Debug, Clone, Deserialize, sqlx::FromRow)]
+pub struct CoreIdRow {
+    pub id: Uuid,
+    pub embedding_hash: String,
+    pub name: String,
+    pub breed: String,
+    pub date_of_birth: NaiveDate,
+    pub proof_level: i16,
+    pub microchip_id: String,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Deserialize, sqlx::FromRow)]
+pub struct MerchantJoinIdRow {
+    pub id: Uuid,
+    pub merchant_id: i32,
+    pub embedding_hash: String,
+    pub write_fields: Vec<String>,
+    pub read_merchant_fields: Value,
+    pub last_data_hash: String,
+    pub last_updated: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, Deserialize, sqlx::FromRow)]
+pub struct MerchantRecordRow {
+    pub id: Uuid,
+    pub embedding_hash: String,
+    pub merchant_id: i32,
+    pub data_issued: NaiveDateTime,
+    pub valid_until: Option<NaiveDateTime>,
+    pub prev_data_hash: String,
+    pub data_record: Value,
+}
+
+impl RegistryRow for CoreIdRow {
+    const TABLE: &'static str = "CoreId";
+    const REPLAY_ORDER: &'static str = "created_at NULLS FIRST, id";
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn replay_key(&self) -> (Option<NaiveDateTime>, Uuid) {
+        (self.created_at, self.id)
+    }
+
+    fn leaf(&self) -> Result<Fr> {
+        let core_id = CoreId {
+            embedding_hash: self.embedding_hash.clone(),
+            merchants: Vec::new(),
+            records: Vec::new(),
+            name: self.name.clone(),
+            breed: self.breed.clone(),
+            date_of_birth: self.date_of_birth,
+            proof_level: u8::try_from(self.proof_level)
+                .map_err(|_| anyhow!("proof_level {} is out of range", self.proof_level))?,
+            microchip_id: self.microchip_id.clone(),
+        };
+        Ok(core_id.to_leaf_hash())
+    }
+}
+
+impl RegistryRow for MerchantJoinIdRow {
+    const TABLE: &'static str = "MerchantJoinId";
+    const REPLAY_ORDER: &'static str = "last_updated NULLS FIRST, id";
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn replay_key(&self) -> (Option<NaiveDateTime>, Uuid) {
+        (self.last_updated, self.id)
+    }
+
+    fn leaf(&self) -> Result<Fr> {
+        // `write_fields` and `read_merchant_fields` are not part of the leaf
+        let merchant_join = MerchantJoinId {
+            merchant_id: self.merchant_id,
+            embedding_hash: self.embedding_hash.clone(),
+            write_fields: self.write_fields.clone(),
+            read_merchant_fields: Vec::new(),
+            last_updated: self
+                .last_updated
+                .ok_or_else(|| anyhow!("last_updated is not set"))?,
+            latest_data_hash: Fr::from_hex(&self.last_data_hash)?,
+        };
+        Ok(merchant_join.to_leaf_hash())
+    }
+}
+
+impl RegistryRow for MerchantRecordRow {
+    const TABLE: &'static str = "MerchantRecord";
+    const REPLAY_ORDER: &'static str = "data_issued, id";
+
+    fn id(&self) -> Uuid {
+        self.id
+    }
+
+    fn replay_key(&self) -> (Option<NaiveDateTime>, Uuid) {
+        (Some(self.data_issued), self.id)
+    }
+
+    fn leaf(&self) -> Result<Fr> {
+        let prev_data_hash = Fr::from_hex(&self.prev_data_hash)?;
+        // No `data_hash` column, it is derived from the record like the backend does
+        let record = MerchantRecord {
+            embedding_hash: self.embedding_hash.clone(),
+            merchant_id: u32::try_from(self.merchant_id)
+                .map_err(|_| anyhow!("merchant_id {} is negative", self.merchant_id))?,
+            date_issued: self.data_issued,
+            valid_until: self.valid_until,
+            prev_data_hash,
+            data_record: self.data_record.clone(),
+            data_hash: MerchantRecord::derive_data_hash(prev_data_hash, &self.data_record)?,
+        };
+        Ok(record.to_leaf_hash())
+    }
+}
@@ -0,0 +1,108 @@
+//! Where the rows come from: the backend database or a JSON export of it.
+
+use anyhow::{Context, Result};
+use merkle_tree_storage::MerkleTreeStorage;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::PgPool;
+
+use crate::rows::{CoreIdRow, MerchantJoinIdRow, MerchantRecordRow, RegistryRow};
+
+/// Query producing an export `Registry::from_export` reads, e.g. with
+/// `psql -At -c "$(cat export.sql)" > registry.json`.
+pub const EXPORT_QUERY: &str = r#"SELECT json_build_object(
+    'coreid', (SELECT COALESCE(json_agg(t), '[]') FROM CoreId t),
+    'merchantjoinid', (SELECT COALESCE(json_agg(t), '[]') FROM MerchantJoinId t),
+    'merchantrecord', (SELECT COALESCE(json_agg(t), '[]') FROM MerchantRecord t),
+    'coreidtree', (SELECT row_to_json(t) FROM (SELECT leaves, capacity FROM CoreIdTree ORDER BY storage_id LIMIT 1) t),
+    'merchantjointree', (SELECT row_to_json(t) FROM (SELECT leaves, capacity FROM MerchantJoinTree ORDER BY storage_id LIMIT 1) t),
+    'merchantrecordtree', (SELECT row_to_json(t) FROM (SELECT leaves, capacity FROM MerchantRecordTree ORDER BY storage_id LIMIT 1) t)
+)"#;
+
+/// Row of a tree storage table (`CoreIdTree`, ...).
+#[derive(Debug, Clone, Deserialize, sqlx::FromRow)]
+pub struct StoredTree {
+    pub leaves: Value,
+    pub capacity: i64,
+}
+
+impl StoredTree {
+    /// The tree the row holds, empty without a row.
+    pub fn rebuild(stored: Option<&Self>, table: &str, depth: u32) -> Result<MerkleTreeStorage> {
+        match stored {
+            Some(stored) => {
+                MerkleTreeStorage::from_stored(table, depth, stored.leaves.clone(), stored.capacity)
+            }
+            None => Ok(MerkleTreeStorage::new(depth)),
+        }
+    }
+}
+
+/// Everything the audit reads, with the rows of each table in replay order.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Registry {
+    #[serde(rename = "coreid")]
+    pub core_ids: Vec<CoreIdRow>,
+    #[serde(rename = "merchantjoinid")]
+    pub merchant_joins: Vec<MerchantJoinIdRow>,
+    #[serde(rename = "merchantrecord")]
+    pub merchant_records: Vec<MerchantRecordRow>,
+    #[serde(rename = "coreidtree")]
+    pub core_id_tree: Option<StoredTree>,
+    #[serde(rename = "merchantjointree")]
+    pub merchant_join_tree: Option<StoredTree>,
+    #[serde(rename = "merchantrecordtree")]
+    pub merchant_record_tree: Option<StoredTree>,
+}
+
+impl Registry {
+    pub async fn from_database(pool: &PgPool) -> Result<Self> {
+        Ok(Self {
+            core_ids: fetch_rows(pool).await?,
+            merchant_joins: fetch_rows(pool).await?,
+            merchant_records: fetch_rows(pool).await?,
+            core_id_tree: fetch_tree(pool, "CoreIdTree").await?,
+            merchant_join_tree: fetch_tree(pool, "MerchantJoinTree").await?,
+            merchant_record_tree: fetch_tree(pool, "MerchantRecordTree").await?,
+        })
+    }
+
+    /// Reads the output of `EXPORT_QUERY`.
+    pub fn from_export(json: &str) -> Result<Self> {
+        let mut registry: Self = serde_json::from_str(json).context("Invalid registry export")?;
+        sort_rows(&mut registry.core_ids);
+        sort_rows(&mut registry.merchant_joins);
+        sort_rows(&mut registry.merchant_records);
+        Ok(registry)
+    }
+}
+
+fn sort_rows<R: RegistryRow>(rows: &mut [R]) {
+    rows.sort_by_key(|row| row.replay_key());
+}
+
+async fn fetch_rows<R>(pool: &PgPool) -> Result<Vec<R>>
+where
+    R: RegistryRow + for<'r> sqlx::FromRow<'r, sqlx::postgres::PgRow> + Send + Unpin,
+{
+    let rows = sqlx::query_as::<_, R>(&format!(
+        r#"SELECT * FROM {} ORDER BY {}"#,
+        R::TABLE,
+        R::REPLAY_ORDER
+    ))
+    .fetch_all(pool)
+    .await
+    .with_context(|| format!("Failed to read {}", R::TABLE))?;
+    Ok(rows)
+}
+
+async fn fetch_tree(pool: &PgPool, table: &str) -> Result<Option<StoredTree>> {
+    let tree = sqlx::query_as::<_, StoredTree>(&format!(
+        r#"SELECT leaves, capacity FROM {} ORDER BY storage_id LIMIT 1"#,
+        table
+    ))
+    .fetch_optional(pool)
+    .await
+    .with_context(|| format!("Failed to read {}", table))?;
+    Ok(tree)
+}
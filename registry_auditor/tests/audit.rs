@@ -0,0 +1,149 @@
+use groth16_verifier::codec::Codec;
+use merkle_tree_storage::{Fr, MerkleTreeStorage, PoseidonHasher, CIRCUIT_MAX_DEPTH};
+use registry_auditor::{
+    audit_registry, audit_tree,
+    rows::{CoreIdRow, RegistryRow},
+    ChainCheck, Divergence, Registry, TreeAudit,
+};
+use serde_json::{json, Value};
+
+fn core_id(n: u128, created_at: &str) -> serde_json::Value {
+    json!({
+        "id": uuid::Uuid::from_u128(n),
+        "embedding_hash": format!("embedding-{}", n),
+        "name": format!("pet {}", n),
+        "breed": "beagle",
+        "date_of_birth": "2020-01-01",
+        "proof_level": 1,
+        "microchip_id": format!("chip-{}", n),
+        "created_at": created_at,
+    })
+}
+
+fn export(stored: &[CoreIdRow]) -> String {
+    let leaves: Vec<_> = stored.iter().map(|row| row.leaf().unwrap().to_hex()).collect();
+    json!({
+        "coreid": [core_id(3, "2024-01-03T00:00:00"), core_id(1, "2024-01-01T00:00:00"), core_id(2, "2024-01-02T00:00:00")],
+        "merchantjoinid": [],
+        "merchantrecord": [],
+        "coreidtree": { "leaves": leaves, "capacity": 4 },
+        "merchantjointree": null,
+        "merchantrecordtree": null,
+    })
+    .to_string()
+}
+
+fn rows() -> Vec<CoreIdRow> {
+    Registry::from_export(&export(&[])).unwrap().core_ids
+}
+
+fn audit_core_ids(registry: &Registry, on_chain_root: Option<Fr>) -> TreeAudit {
+    let stored = registry.core_id_tree.as_ref().unwrap();
+    let stored =
+        MerkleTreeStorage::from_stored("CoreIdTree", 2, stored.leaves.clone(), stored.capacity)
+            .unwrap();
+    audit_tree("CoreIdTree", 2, &registry.core_ids, stored, on_chain_root)
+}
+
+#[test]
+fn export_rows_are_replayed_in_creation_order() {
+    let ids: Vec<_> = rows().iter().map(|row| row.id.as_u128()).collect();
+    assert_eq!(ids, [1, 2, 3]);
+}
+
+#[test]
+fn matching_tables_and_chain_are_consistent() {
+    let rows = rows();
+    let registry = Registry::from_export(&export(&rows)).unwrap();
+    let replayed = audit_core_ids(&registry, None).replayed.root();
+
+    let audit = audit_core_ids(&registry, Some(replayed));
+    assert_eq!(audit.divergence, None);
+    assert_eq!(audit.chain, ChainCheck::Matches);
+    assert!(audit.is_consistent());
+}
+
+#[test]
+fn first_swapped_leaf_is_reported() {
+    let rows = rows();
+    let swapped = [rows[0].clone(), rows[2].clone(), rows[1].clone()];
+    let registry = Registry::from_export(&export(&swapped)).unwrap();
+
+    let audit = audit_core_ids(&registry, None);
+    match audit.divergence {
+        Some(Divergence::Leaf {
+            position,
+            row,
+            stored_position,
+            ..
+        }) => {
+            assert_eq!(position, 1);
+            assert_eq!(row, Some(rows[1].id));
+            assert_eq!(stored_position, Some(2));
+        }
+        other => panic!("unexpected divergence {:?}", other),
+    }
+    assert!(!audit.is_consistent());
+}
+
+#[test]
+fn missing_stored_leaf_is_reported() {
+    let rows = rows();
+    let registry = Registry::from_export(&export(&rows[..2])).unwrap();
+
+    let audit = audit_core_ids(&registry, None);
+    match audit.divergence {
+        Some(Divergence::Leaf {
+            position, stored, ..
+        }) => {
+            assert_eq!(position, 2);
+            assert_eq!(stored, None);
+        }
+        other => panic!("unexpected divergence {:?}", other),
+    }
+}
+
+#[test]
+fn lagging_chain_is_behind_not_diverged() {
+    let rows = rows();
+    let registry = Registry::from_export(&export(&rows)).unwrap();
+    let at_two = MerkleTreeStorage::<PoseidonHasher>::from_leaves(
+        2,
+        rows[..2].iter().map(|row| row.leaf().unwrap()),
+    )
+    .unwrap()
+    .root();
+
+    let audit = audit_core_ids(&registry, Some(at_two));
+    assert_eq!(audit.chain, ChainCheck::Behind { size: 2 });
+    assert!(audit.is_consistent());
+
+    let audit = audit_core_ids(&registry, Some(Fr::from(7u64)));
+    assert_eq!(audit.chain, ChainCheck::Diverged);
+    assert!(!audit.is_consistent());
+}
+
+#[tokio::test]
+async fn backend_tree_is_audited_at_the_circuit_depth() {
+    // CoreIdTree as the backend keeps it: loaded at the circuit depth, appended to, stored
+    let rows = rows();
+    let mut backend = MerkleTreeStorage::<PoseidonHasher>::new(CIRCUIT_MAX_DEPTH as u32);
+    for row in &rows {
+        backend.insert_leaf(row.leaf().unwrap()).unwrap();
+    }
+    let (leaves, capacity) = backend.to_stored();
+    let mut export: Value = serde_json::from_str(&export(&[])).unwrap();
+    export["coreidtree"] = json!({ "leaves": leaves, "capacity": capacity });
+    let registry = Registry::from_export(&export.to_string()).unwrap();
+
+    let audits = audit_registry(&registry, false).await.unwrap();
+    assert!(audits.iter().all(TreeAudit::is_consistent));
+    assert_eq!(audits[0].replayed.root(), backend.root());
+
+    // What `--rebuild` would store is a tree the backend loads again
+    let (leaves, capacity) = audits[0].replayed.to_stored();
+    let reloaded =
+        MerkleTreeStorage::from_stored("CoreIdTree", CIRCUIT_MAX_DEPTH as u32, leaves, capacity)
+            .unwrap();
+    assert_eq!(reloaded.root(), backend.root());
+}
@@ -13,13 +13,29 @@ use chrono::{Utc, Date, NaiveDate};
 // use sqlx::Error;
 
 use crate::{
-    models::ddid_models::*, schemas::ddid_schemas::*, utils::{gen_merkle::{merkle_proof_callback, MerkleTreeStorage}, gen_zkp::insert_leaf_zkp, get_current_leaves::get_current_leaves, get_onchain_root::get_current_root, ml_model::ml_model}, AppState
+    models::ddid_models::*, schemas::ddid_schemas::*, utils::{gen_merkle::{merkle_update_callback, MerkleTreeStorage}, proving_queue::{enqueue_update_proof, fetch_job}, get_current_leaves::get_current_leaves, get_onchain_root::get_current_root, ml_model::ml_model, errors::{ApiError, DbError, ErrorResponse}}, AppState
 };
 
+#[utoipa::path(
+    post,
+    path = "/api/prove_ddid",
+    tag = "ddid",
+    request_body = CoreIdSchema,
+    responses(
+        (status = 200, description = "Known CoreId, its proof level is updated", body = ProveDdidResponse),
+        (status = 202, description = "New CoreId added, its proof is queued", body = ProveDdidResponse),
+        (status = 400, description = "INVALID_HASH, INVALID_PROOF_LEVEL or INVALID_DATE", body = ErrorResponse),
+        (status = 451, description = "MISSING_PARAMS", body = ErrorResponse),
+        (status = 500, description = "DB_ERROR or QUEUE_FAILED", body = ErrorResponse),
+        (status = 503, description = "TREE_UNAVAILABLE", body = ErrorResponse),
+        (status = 507, description = "TREE_FULL", body = ErrorResponse),
+    )
+)]
 pub async fn prove_ddid_handler(
     State(data): State<Arc<AppState>>,
     Json(body): Json<CoreIdSchema>,
-) -> Result<impl IntoResponse, (StatusCode, Json<serde_json::Value>)> {
+) -> Result<impl IntoResponse, ApiError> {
+    body.validate()?;
     // let embedding_hash = match ml_model(body.image_uris) {
     //     Ok(hash) => hash, 
     //     Err(_) => {
@@ -36,63 +52,96 @@ pub async fn prove_ddid_handler(
     )
     .bind(body.embedding_hash.clone())
     .fetch_all(&data.db)
-    .await;
-    if embedding_hash_query_result.unwrap().len() > 0 {
-        let _update_proof_level_query_result = sqlx::query_as::<_, CoreIdModel>(
+    .await?;
+    if !embedding_hash_query_result.is_empty() {
+        sqlx::query(
             r#"UPDATE coreid SET proof_level = $2  WHERE embedding_hash = $1"#
         )
         .bind(body.embedding_hash)
         .bind(body.proof_level)
-        .fetch_one(&data.db)
-        .await;
-        let proof_json = serde_json::json!({
-            "success" : true,
-            "proof_response" : "valid_proof"
-        });
-        Ok(Json(proof_json))
+        .execute(&data.db)
+        .await?;
+        let proof_json = ProveDdidResponse {
+            success: true,
+            proof_response: ProofResponse::ValidProof,
+            job_id: None,
+        };
+        Ok((StatusCode::OK, Json(proof_json)))
     } else {
         // New embedding_hash: assert all parameters are provided
         if body.name.is_empty() || body.breed.is_empty() || body.dob.is_empty() {
-            let proof_json = serde_json::json!({
-                "success": false,
-                "proof_response": "invalid_proof",
-                "error": Some("MISSING_PARAMS".to_string()),
-            });
-            return Err((StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS, Json(proof_json)));
+            return Err(ApiError::MissingParams);
         }
-        let date_of_birth = NaiveDate::parse_from_str(&body.dob, "%Y-%m-%d").unwrap();
-        let insert_query_result = sqlx::query_as::<_, CoreIdModel>(
-            r#"INSERT into coreid (embedding_hash, name, breed, date_of_birth, proof_level, microchip_id) VALUES ($1, $2, $3, $4, $5, $6)"#
+        let date_of_birth = body.date_of_birth()?;
+        // The row, its leaf in CoreIdTree and its proving job are committed together, and the
+        // served tree only takes the leaf after the commit. A failure before it rolls all three
+        // back and drops the prepared append.
+        let mut tx = data.db.begin().await?;
+        let inserted = sqlx::query_as::<_, CoreIdModel>(
+            r#"INSERT into coreid (id, embedding_hash, name, breed, date_of_birth, proof_level, microchip_id, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, NOW()) RETURNING *"#
         )
+        .bind(uuid::Uuid::new_v4())
         .bind(body.embedding_hash)
         .bind(body.name)
         .bind(body.breed)
         .bind(date_of_birth)
         .bind(body.proof_level)
         .bind(body.microchip_id)
-        .fetch_one(&data.db)
-        .await;
-        
-        let (merkle_proof, target_leaf) = merkle_proof_callback(State(data), insert_query_result.unwrap()).unwrap();
-        let zkp = insert_leaf_zkp(target_leaf, merkle_proof).await;
-        if zkp {
-            let proof_json = serde_json::json!({
-                "success" : true,
-                "proof_response" : "valid_proof",
-            });
-            Ok(Json(proof_json))
-        } else {
-            let proof_json = serde_json::json!({
-                "success" : false,
-                "proof_response" : "invalid_proof",
-                "error": Some("VERIFICATION_FAILED".to_string()),
-            });
-            return Err((StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS, Json(proof_json)));
-        }
+        .fetch_one(&mut *tx)
+        .await?;
         
+        // Prove the root moved from the old tree to one with this leaf in a previously empty slot.
+        // Proving and submission run on the proving workers, the caller polls /api/jobs/:id
+        let (update_witness, pending) = merkle_update_callback(State(data.clone()), &mut tx, inserted).await?;
+        let job_id = enqueue_update_proof(&mut *tx, &update_witness)
+            .await
+            .map_err(|err| ApiError::QueueFailed(err.into()))?;
+        tx.commit().await.map_err(|err| ApiError::QueueFailed(err.into()))?;
+        // Only fails if the tree service stopped, the next start loads the stored leaf
+        pending.apply().await?;
+        let proof_json = ProveDdidResponse {
+            success: true,
+            proof_response: ProofResponse::Queued,
+            job_id: Some(job_id),
+        };
+        Ok((StatusCode::ACCEPTED, Json(proof_json)))
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}",
+    tag = "ddid",
+    params(("id" = uuid::Uuid, Path, description = "Job id returned by /api/prove_ddid")),
+    responses(
+        (status = 200, description = "The proving job", body = JobResponse),
+        (status = 404, description = "JOB_NOT_FOUND", body = ErrorResponse),
+        (status = 500, description = "DB_ERROR", body = ErrorResponse),
+    )
+)]
+pub async fn get_job_handler(
+    State(data): State<Arc<AppState>>,
+    Path(id): Path<uuid::Uuid>,
+) -> Result<impl IntoResponse, ApiError> {
+    let job = fetch_job(&data.db, id).await?.ok_or(ApiError::JobNotFound(id))?;
+    let status = job
+        .status
+        .parse::<JobStatus>()
+        .map_err(|e| DbError::InvalidRow { table: "ProvingJob".to_string(), reason: e.to_string() })?;
+    let job_json = JobResponse {
+        success: true,
+        job: JobSchema {
+            id: job.id,
+            status,
+            signature: job.signature,
+            error: job.error,
+            created_at: job.created_at,
+            updated_at: job.updated_at,
+        },
+    };
+    Ok(Json(job_json))
+}
+
 
 // pub async fn is_ddid_member_handler(
 //     Json(body): Json<IsDdidMemberSchema>,
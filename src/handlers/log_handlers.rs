@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use groth16_verifier::codec::Codec;
+use merkle_tree_storage::{Fr, TreeError};
+
+use crate::{
+    models::ddid_models::SignedTreeHeadModel, schemas::log_schemas::*, utils::{errors::{ApiError, ErrorResponse}, tree_heads::{latest_tree_head, tree_head_at}}, AppState
+};
+
+fn sth_json(head: SignedTreeHeadModel) -> SthSchema {
+    SthSchema {
+        tree_size: head.tree_size,
+        root_hash: head.root_hash,
+        timestamp: head.timestamp.timestamp_millis(),
+        public_key: head.public_key,
+        signature: head.signature,
+        anchor_signature: head.anchor_signature,
+    }
+}
+
+/// Latest signed tree head, or the one at `tree_size`. The signature covers the message
+/// described in `utils::tree_heads`, checkable with the ed25519 `public_key` (base58).
+#[utoipa::path(
+    get,
+    path = "/api/sth",
+    tag = "log",
+    params(SthQuery),
+    responses(
+        (status = 200, description = "The signed tree head", body = SthResponse),
+        (status = 404, description = "STH_NOT_FOUND", body = ErrorResponse),
+        (status = 500, description = "DB_ERROR", body = ErrorResponse),
+    )
+)]
+pub async fn get_sth_handler(
+    State(data): State<Arc<AppState>>,
+    Query(query): Query<SthQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let head = match query.tree_size {
+        Some(tree_size) => tree_head_at(&data.db, tree_size).await?,
+        None => latest_tree_head(&data.db).await?,
+    };
+    let head = head.ok_or(ApiError::SthNotFound)?;
+    let sth_json = SthResponse {
+        success: true,
+        sth: sth_json(head),
+    };
+    Ok(Json(sth_json))
+}
+
+/// Inclusion proof of a leaf, by its hash, against the root at `tree_size` leaves. Without a
+/// size it is the latest signed head's, so the root can be checked against its signature.
+#[utoipa::path(
+    get,
+    path = "/api/inclusion",
+    tag = "log",
+    params(InclusionQuery),
+    responses(
+        (status = 200, description = "The inclusion proof", body = InclusionResponse),
+        (status = 400, description = "INVALID_LEAF_HASH or INVALID_TREE_SIZE", body = ErrorResponse),
+        (status = 404, description = "LEAF_NOT_FOUND or STH_NOT_FOUND", body = ErrorResponse),
+        (status = 500, description = "DB_ERROR", body = ErrorResponse),
+        (status = 503, description = "TREE_UNAVAILABLE", body = ErrorResponse),
+    )
+)]
+pub async fn get_inclusion_handler(
+    State(data): State<Arc<AppState>>,
+    Query(query): Query<InclusionQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let leaf = Fr::from_hex(&query.leaf_hash).map_err(|_| ApiError::InvalidLeafHash(query.leaf_hash.clone()))?;
+    let tree_size = match query.tree_size {
+        Some(tree_size) => tree_size,
+        None => latest_tree_head(&data.db).await?.ok_or(ApiError::SthNotFound)?.tree_size as usize,
+    };
+
+    // The size check and the proof come from the same tree
+    let proof = data
+        .merkle_tree
+        .read(move |merkle_tree| merkle_tree.inclusion_proof_at(leaf, tree_size))
+        .await??;
+    let proof_json = InclusionResponse {
+        success: true,
+        inclusion_proof: InclusionProofSchema {
+            tree_size,
+            leaf_hash: proof.leaf.to_hex(),
+            position: proof.position,
+            siblings: proof.siblings.iter().map(Codec::to_hex).collect(),
+            root: proof.root.to_hex(),
+        },
+    };
+    Ok(Json(proof_json))
+}
+
+/// Consistency proof between two sizes of the DDID tree, for monitors checking that a new
+/// root only appended to an old one, e.g. between two signed tree heads. Verify it with `merkle_tree_storage::ConsistencyProof`:
+/// fold `leaf` up `siblings` from slot `old_size - 1`, with zeros in place of the right-hand
+/// siblings for `old_root` and as given for `new_root`.
+#[utoipa::path(
+    get,
+    path = "/api/consistency",
+    tag = "log",
+    params(ConsistencyQuery),
+    responses(
+        (status = 200, description = "The consistency proof", body = ConsistencyResponse),
+        (status = 400, description = "INVALID_TREE_SIZE", body = ErrorResponse),
+        (status = 503, description = "TREE_UNAVAILABLE", body = ErrorResponse),
+    )
+)]
+pub async fn get_consistency_handler(
+    State(data): State<Arc<AppState>>,
+    Query(query): Query<ConsistencyQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    // Proof and roots come from the same tree
+    let (old_size, new_size) = (query.old_size, query.new_size);
+    let (proof, old_root, new_root) = data
+        .merkle_tree
+        .read(move |merkle_tree| {
+            let new_size = new_size.unwrap_or(merkle_tree.len());
+            let proof = merkle_tree.consistency_proof(old_size, new_size)?;
+            Ok::<_, TreeError>((proof, merkle_tree.root_at(old_size)?, merkle_tree.root_at(new_size)?))
+        })
+        .await??;
+    let proof_json = ConsistencyResponse {
+        success: true,
+        consistency_proof: ConsistencyProofSchema {
+            old_size: proof.old_size,
+            new_size: proof.new_size,
+            old_root: old_root.to_hex(),
+            new_root: new_root.to_hex(),
+            leaf: proof.leaf.to_hex(),
+            siblings: proof.siblings.iter().map(Codec::to_hex).collect(),
+        },
+    };
+    Ok(Json(proof_json))
+}
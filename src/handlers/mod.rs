@@ -1 +1,2 @@
-pub mod ddid_handlers;
\ No newline at end of file
+pub mod ddid_handlers;
+pub mod log_handlers;
\ No newline at end of file
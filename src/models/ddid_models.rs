@@ -43,19 +43,23 @@
 
 
 use std::collections::BTreeMap;
+use std::str::FromStr;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use sqlx::FromRow;
+use utoipa::ToSchema;
 #[derive(Debug, FromRow, Deserialize, Serialize)]
 #[allow(non_snake_case)]
 pub struct CoreIdModel {
-    pub id: i32,
+    pub id: uuid::Uuid,
     pub embedding_hash: String,
     pub name: String,
     pub breed: String,
     pub date_of_birth: chrono::NaiveDate,
     pub proof_level: i16,
-    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub microchip_id: String,
+    // Rows are replayed into the tree in `created_at` order, see `registry_auditor`
+    pub created_at: Option<chrono::NaiveDateTime>,
 }
 
 
@@ -87,7 +91,7 @@ pub struct MerchantRecordModel {
 #[derive(Debug, FromRow, Deserialize, Serialize)]
 pub struct CoreIdTree {
     pub leaves: Value,
-	pub capacity: i32,
+	pub capacity: i64,
 }
 
 #[derive(Debug, FromRow, Deserialize, Serialize)]
@@ -101,3 +105,71 @@ pub struct MerchantRecordTree {
     pub leaves: Value,
 	pub capacity: i64,
 }
+
+#[derive(Debug, FromRow, Deserialize, Serialize)]
+pub struct ProvingJobModel {
+    pub id: uuid::Uuid,
+    pub circuit: String,
+    pub witness: Value,
+    pub status: String,
+    pub signature: Option<String>,
+    /// Blockhash the submitted transaction was signed with, base58.
+    pub blockhash: Option<String>,
+    pub error: Option<String>,
+    pub attempts: i32,
+    /// Last heartbeat of the worker holding the job, `None` once nobody does.
+    pub locked_at: Option<chrono::NaiveDateTime>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+/// State of a proving job, stored in `ProvingJob.status` as its lowercase name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// Waiting for a worker.
+    Queued,
+    /// A worker is building the proof.
+    Proving,
+    /// The verification transaction was sent, see `signature`.
+    Submitted,
+    /// The proof was verified on chain.
+    Confirmed,
+    /// The job gave up, see `error`.
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Proving => "proving",
+            JobStatus::Submitted => "submitted",
+            JobStatus::Confirmed => "confirmed",
+            JobStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for JobStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        [JobStatus::Queued, JobStatus::Proving, JobStatus::Submitted, JobStatus::Confirmed, JobStatus::Failed]
+            .into_iter()
+            .find(|known| known.as_str() == status)
+            .ok_or_else(|| anyhow::anyhow!("unknown job status {}", status))
+    }
+}
+
+/// A tree head of the DDID tree signed by the server, see `utils::tree_heads`.
+#[derive(Debug, Clone, FromRow, Deserialize, Serialize)]
+pub struct SignedTreeHeadModel {
+    pub tree_size: i64,
+    pub root_hash: String,
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub public_key: String,
+    pub signature: String,
+    pub anchor_signature: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+}
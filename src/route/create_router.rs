@@ -6,13 +6,19 @@ use axum::{
 };
 use crate::{
     AppState,
-    handlers::ddid_handlers::*
+    handlers::{ddid_handlers::*, log_handlers::*},
+    route::openapi::openapi_handler,
 };
 
 
 pub fn create_router(app_state: Arc<AppState>) -> Router {
     Router::new()
         .route("/api/prove_ddid", post(prove_ddid_handler))
+        .route("/api/jobs/:id", get(get_job_handler))
+        .route("/api/sth", get(get_sth_handler))
+        .route("/api/inclusion", get(get_inclusion_handler))
+        .route("/api/consistency", get(get_consistency_handler))
+        .route("/api/openapi.json", get(openapi_handler))
         // .route("/api/is_ddid_member", post(is_ddid_member_handler))
         // .route("/api/add_merchant", post(add_merchant_handler))
         // .route("/api/write_merchant_record", post(write_merchant_record_handler))
@@ -1 +1,2 @@
-pub mod create_router;
\ No newline at end of file
+pub mod create_router;
+pub mod openapi;
\ No newline at end of file
@@ -0,0 +1,51 @@
+//! OpenAPI 3 document of the API, served at `/api/openapi.json` for generating clients.
+//!
+//! Paths come from the `#[utoipa::path]` attributes on the handlers and schemas from the
+//! request and response types, so the document follows the code. A new route needs its
+//! handler listed in `paths` below.
+
+use axum::Json;
+use utoipa::OpenApi;
+
+use crate::{
+    handlers::{ddid_handlers, log_handlers},
+    models::ddid_models::JobStatus,
+    schemas::{ddid_schemas::*, log_schemas::*},
+    utils::errors::ErrorResponse,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "DDID API", description = "Registers CoreIds and serves the DDID tree as a verifiable log. Errors are an `ErrorResponse` whose `error` is a stable code."),
+    paths(
+        ddid_handlers::prove_ddid_handler,
+        ddid_handlers::get_job_handler,
+        log_handlers::get_sth_handler,
+        log_handlers::get_inclusion_handler,
+        log_handlers::get_consistency_handler,
+    ),
+    components(schemas(
+        CoreIdSchema,
+        ProofResponse,
+        ProveDdidResponse,
+        JobSchema,
+        JobStatus,
+        JobResponse,
+        SthSchema,
+        SthResponse,
+        InclusionProofSchema,
+        InclusionResponse,
+        ConsistencyProofSchema,
+        ConsistencyResponse,
+        ErrorResponse,
+    )),
+    tags(
+        (name = "ddid", description = "CoreId registration and proving jobs"),
+        (name = "log", description = "Signed tree heads, inclusion and consistency proofs"),
+    )
+)]
+pub struct ApiDoc;
+
+pub async fn openapi_handler() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
@@ -1,7 +1,19 @@
 use std::collections::{BTreeMap, HashMap};
 
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use utoipa::ToSchema;
+
+use crate::models::ddid_models::JobStatus;
+use crate::utils::errors::ApiError;
+
+/// Hex digits of an `embedding_hash`, a 32-byte hash.
+pub const EMBEDDING_HASH_LEN: usize = 64;
+/// Highest `proof_level`, levels are stored as `u8` in the CoreId leaves.
+pub const MAX_PROOF_LEVEL: i32 = u8::MAX as i32;
+/// Format of `dob`, ISO 8601 calendar dates.
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
 
 #[derive(Deserialize, Debug, Default)]
 pub struct FilterOptions {
@@ -9,17 +21,84 @@ pub struct FilterOptions {
     pub limit: Option<usize>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// `POST /api/prove_ddid`. An unknown `embedding_hash` registers a new CoreId, which needs
+/// `name`, `breed` and `dob`; a known one only updates its `proof_level`.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct CoreIdSchema {
     // pub image_uris: HashMap<String, String>,
+    #[schema(pattern = "^[0-9a-fA-F]{64}$", example = "8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4")]
     pub embedding_hash: String,
+    #[schema(example = "Rex")]
     pub name: String,
+    #[schema(example = "Beagle")]
     pub breed: String,
+    /// Date of birth as `YYYY-MM-DD`, may be empty for a known `embedding_hash`.
+    #[schema(format = Date, example = "2021-04-30")]
     pub dob: String,
+    #[schema(minimum = 0, maximum = 255)]
     pub proof_level: i32,
     pub microchip_id: String
 }
 
+impl CoreIdSchema {
+    /// Checks the fields that have a format, before anything is read or written.
+    pub fn validate(&self) -> Result<(), ApiError> {
+        let hash = &self.embedding_hash;
+        if hash.len() != EMBEDDING_HASH_LEN || !hash.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ApiError::InvalidHash(hash.clone()));
+        }
+        if !(0..=MAX_PROOF_LEVEL).contains(&self.proof_level) {
+            return Err(ApiError::InvalidProofLevel(self.proof_level));
+        }
+        if !self.dob.is_empty() {
+            self.date_of_birth()?;
+        }
+        Ok(())
+    }
+
+    pub fn date_of_birth(&self) -> Result<NaiveDate, ApiError> {
+        NaiveDate::parse_from_str(&self.dob, DATE_FORMAT).map_err(|_| ApiError::InvalidDate(self.dob.clone()))
+    }
+}
+
+/// `proof_response` of a `ProveDdidResponse`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofResponse {
+    /// The CoreId was known, its `proof_level` is updated.
+    ValidProof,
+    /// The CoreId was added to the tree and its proof is being built, see `job_id`.
+    Queued,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ProveDdidResponse {
+    pub success: bool,
+    pub proof_response: ProofResponse,
+    /// Proving job to poll at `/api/jobs/{id}`, set when `proof_response` is `queued`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<uuid::Uuid>,
+}
+
+/// A proving job as `GET /api/jobs/{id}` returns it.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct JobSchema {
+    pub id: uuid::Uuid,
+    pub status: JobStatus,
+    /// Transaction of the on-chain verification, once submitted.
+    pub signature: Option<String>,
+    /// Why the job failed, for `failed` jobs.
+    pub error: Option<String>,
+    pub created_at: chrono::NaiveDateTime,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct JobResponse {
+    pub success: bool,
+    pub job: JobSchema,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct IsDdidMemberSchema {
     pub leaf_hash: String
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// `GET /api/consistency?old_size=..&new_size=..`, `new_size` defaults to the current size.
+#[derive(Serialize, Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ConsistencyQuery {
+    pub old_size: usize,
+    pub new_size: Option<usize>,
+}
+
+/// `GET /api/sth?tree_size=..`, the latest head without `tree_size`.
+#[derive(Serialize, Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SthQuery {
+    pub tree_size: Option<i64>,
+}
+
+/// `GET /api/inclusion?leaf_hash=..&tree_size=..`, `tree_size` defaults to the latest signed
+/// head's size.
+#[derive(Serialize, Deserialize, Debug, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InclusionQuery {
+    /// Leaf as 64 hex digits, big-endian, with or without `0x`.
+    pub leaf_hash: String,
+    pub tree_size: Option<usize>,
+}
+
+/// A signed tree head, see `utils::tree_heads` for the signed message. Hashes are `0x` and 64
+/// hex digits (heads signed before have no `0x`), keys and signatures base58.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct SthSchema {
+    pub tree_size: i64,
+    pub root_hash: String,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: i64,
+    pub public_key: String,
+    pub signature: String,
+    /// Transaction that wrote `root_hash` on chain, once anchored.
+    pub anchor_signature: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct SthResponse {
+    pub success: bool,
+    pub sth: SthSchema,
+}
+
+/// Proof of `leaf_hash` in slot `position` against `root`, the root at `tree_size` leaves.
+/// `siblings` go from the leaf up.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct InclusionProofSchema {
+    pub tree_size: usize,
+    pub leaf_hash: String,
+    pub position: usize,
+    pub siblings: Vec<String>,
+    pub root: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct InclusionResponse {
+    pub success: bool,
+    pub inclusion_proof: InclusionProofSchema,
+}
+
+/// See `merkle_tree_storage::ConsistencyProof`. `leaf` is the one in slot `old_size - 1`.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ConsistencyProofSchema {
+    pub old_size: usize,
+    pub new_size: usize,
+    pub old_root: String,
+    pub new_root: String,
+    pub leaf: String,
+    pub siblings: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ConsistencyResponse {
+    pub success: bool,
+    pub consistency_proof: ConsistencyProofSchema,
+}
@@ -1 +1,2 @@
-pub mod ddid_schemas;
\ No newline at end of file
+pub mod ddid_schemas;
+pub mod log_schemas;
\ No newline at end of file
@@ -1,5 +1,6 @@
 use ark_ff::PrimeField;
 use ark_serialize::SerializationError;
+use groth16_verifier::codec::convert_endianness;
 
 // Helper function to convert a field element to bytes
 pub fn field_to_bytes<F: PrimeField>(field: F) -> [u8; 32] {
@@ -13,26 +14,23 @@ pub fn bytes_to_field<F: PrimeField>(bytes: &[u8]) -> Result<F, SerializationErr
     F::deserialize_uncompressed(bytes)
 }
 
+// Swap between the big-endian `alt_bn128` layout and the little-endian one, word by word.
+// Inputs shorter than the output are zero padded, longer ones truncated.
 pub fn convert_endianness_64(input: &[u8]) -> [u8; 64] {
-    let mut output = [0u8; 64];
-    for (i, &byte) in input.iter().enumerate().take(64) {
-        output[i] = byte.swap_bytes(); // This swaps endianness for each byte
-    }
-    output
+    convert_endianness(&fit(input))
 }
 
 pub fn convert_endianness_32(input: &[u8]) -> [u8; 32] {
-    let mut output = [0u8; 32];
-    for (i, &byte) in input.iter().enumerate().take(32) {
-        output[i] = byte.swap_bytes(); // This swaps endianness for each byte
-    }
-    output
+    convert_endianness(&fit(input))
 }
 
 pub fn convert_endianness_128(input: &[u8]) -> [u8; 128] {
-    let mut output = [0u8; 128];
-    for (i, &byte) in input.iter().enumerate().take(128) {
-        output[i] = byte.swap_bytes(); // This swaps endianness for each byte
-    }
+    convert_endianness(&fit(input))
+}
+
+fn fit<const N: usize>(input: &[u8]) -> [u8; N] {
+    let mut output = [0u8; N];
+    let len = input.len().min(N);
+    output[..len].copy_from_slice(&input[..len]);
     output
-}
\ No newline at end of file
+}
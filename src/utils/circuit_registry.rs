@@ -0,0 +1,247 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use ark_bn254::{Bn254, Fr};
+use ark_circom::{circom::{R1CSFile, R1CS}, CircomBuilder, CircomCircuit, CircomConfig};
+use ark_groth16::{prepare_verifying_key, PreparedVerifyingKey, ProvingKey, VerifyingKey};
+use ark_serialize::CanonicalDeserialize;
+use circom_witness::{merkle_hints, read_sym, R1csFile, WitnessCalculator};
+use merkle_tree_storage::{fr_to_bigint, CIRCUIT_MAX_DEPTH};
+use num_bigint::BigInt;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use super::errors::CircuitRegistryError;
+
+pub const INSERT_LEAF: &str = "InsertLeaf";
+pub const MERKLE_TREE_UPDATER: &str = "MerkleTreeUpdater";
+
+const MANIFEST_FILE: &str = "manifest.json";
+const DEFAULT_CIRCUITS_DIR: &str = "build/circuits";
+
+// SHA-256 (hex) of every artifact of one circuit, as listed in manifest.json.
+// Either `sym` or `wasm` is needed to calculate witnesses; `sym` is preferred.
+#[derive(Deserialize, Debug)]
+struct ManifestEntry {
+    wasm: Option<String>,
+    sym: Option<String>,
+    r1cs: String,
+    proving_key: String,
+    verifying_key: String,
+}
+
+/// Inputs of one witness, arrays flattened in order as with `CircomBuilder::push_input`.
+#[derive(Default, Debug)]
+pub struct CircuitInputs {
+    inputs: HashMap<String, Vec<BigInt>>,
+}
+
+impl CircuitInputs {
+    pub fn push_input<T: Into<BigInt>>(&mut self, name: impl ToString, value: T) {
+        self.inputs.entry(name.to_string()).or_default().push(value.into());
+    }
+
+    /// Pushes a tree node or other field element as is, without going through its hex form.
+    pub fn push_field(&mut self, name: impl ToString, value: &Fr) {
+        self.push_input(name, fr_to_bigint(value));
+    }
+}
+
+enum WitnessGenerator {
+    // Solved in Rust from the r1cs and sym files
+    Native {
+        calculator: WitnessCalculator,
+        r1cs: R1CS<Fr>,
+    },
+    // circom's wasm generator; the instance is shared between clones of `config`,
+    // so witnesses are built one at a time
+    Wasm {
+        config: CircomConfig<Fr>,
+        lock: Mutex<()>,
+    },
+}
+
+/// Compiled circuit and Groth16 keys, loaded once and shared by every proof.
+pub struct CircuitArtifacts {
+    name: String,
+    generator: WitnessGenerator,
+    pub proving_key: ProvingKey<Bn254>,
+    pub verifying_key: VerifyingKey<Bn254>,
+    pub prepared_verifying_key: PreparedVerifyingKey<Bn254>,
+}
+
+impl CircuitArtifacts {
+    /// Returns an empty set of inputs for this circuit.
+    pub fn builder(&self) -> CircuitInputs {
+        CircuitInputs::default()
+    }
+
+    /// Calculates the witness for `inputs`.
+    pub fn build(&self, inputs: CircuitInputs) -> Result<CircomCircuit<Fr>, CircuitRegistryError> {
+        let failed = |e: String| CircuitRegistryError::WitnessCalculationFailed(self.name.clone(), e);
+        match &self.generator {
+            WitnessGenerator::Native { calculator, r1cs } => {
+                let witness = calculator
+                    .calculate_witness(&inputs.inputs)
+                    .map_err(|e| failed(e.to_string()))?;
+                Ok(CircomCircuit {
+                    r1cs: r1cs.clone(),
+                    witness: Some(witness),
+                })
+            }
+            WitnessGenerator::Wasm { config, lock } => {
+                let _guard = lock.lock().unwrap();
+                let mut builder = CircomBuilder::new(config.clone());
+                builder.inputs = inputs.inputs;
+                builder.build().map_err(|e| failed(e.to_string()))
+            }
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// True when witnesses are calculated without the wasm runtime.
+    pub fn is_native(&self) -> bool {
+        matches!(self.generator, WitnessGenerator::Native { .. })
+    }
+}
+
+/// Circuits loaded from a build directory laid out the way circom writes it:
+/// `<name>.r1cs`, `<name>.sym` and/or `<name>_js/<name>.wasm`, plus `<name>.pk` and
+/// `<name>.vk` holding the uncompressed arkworks Groth16 keys. `manifest.json` maps each
+/// circuit name to the SHA-256 of those files.
+pub struct CircuitRegistry {
+    dir: PathBuf,
+    circuits: HashMap<String, CircuitArtifacts>,
+}
+
+impl CircuitRegistry {
+    /// Loads the circuits the server proves with from `CIRCUITS_DIR` (default `build/circuits`).
+    /// `Startup::load` calls it once, so a missing or stale artifact stops the server before it
+    /// accepts requests. `build/circuits/README.md` describes how the artifacts are built.
+    pub fn from_env() -> Result<Self, CircuitRegistryError> {
+        let dir = std::env::var("CIRCUITS_DIR").unwrap_or_else(|_| DEFAULT_CIRCUITS_DIR.to_string());
+        Self::load(dir, &[INSERT_LEAF, MERKLE_TREE_UPDATER])
+    }
+
+    /// Loads and checksums every circuit in `names`, failing on the first bad artifact.
+    pub fn load(dir: impl AsRef<Path>, names: &[&str]) -> Result<Self, CircuitRegistryError> {
+        let dir = dir.as_ref().to_path_buf();
+        let manifest = read_manifest(&dir)?;
+
+        let mut circuits = HashMap::new();
+        for name in names {
+            let entry = manifest
+                .get(*name)
+                .ok_or_else(|| CircuitRegistryError::NotInManifest(name.to_string()))?;
+            let circuit = load_circuit(&dir, name, entry)?;
+            let generator = if circuit.is_native() { "native" } else { "wasm" };
+            info!("Loaded circuit {} from {} ({} witness)", name, dir.display(), generator);
+            circuits.insert(name.to_string(), circuit);
+        }
+
+        Ok(Self { dir, circuits })
+    }
+
+    pub fn get(&self, name: &str) -> Result<&CircuitArtifacts, CircuitRegistryError> {
+        self.circuits
+            .get(name)
+            .ok_or_else(|| CircuitRegistryError::UnknownCircuit(name.to_string()))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+fn read_manifest(dir: &Path) -> Result<HashMap<String, ManifestEntry>, CircuitRegistryError> {
+    let path = dir.join(MANIFEST_FILE);
+    let contents = fs::read_to_string(&path)
+        .map_err(|e| CircuitRegistryError::InvalidManifest(path.display().to_string(), e.to_string()))?;
+    serde_json::from_str(&contents)
+        .map_err(|e| CircuitRegistryError::InvalidManifest(path.display().to_string(), e.to_string()))
+}
+
+fn load_circuit(dir: &Path, name: &str, entry: &ManifestEntry) -> Result<CircuitArtifacts, CircuitRegistryError> {
+    let r1cs_path = dir.join(format!("{name}.r1cs"));
+    let pk_path = dir.join(format!("{name}.pk"));
+    let vk_path = dir.join(format!("{name}.vk"));
+
+    let r1cs_bytes = verify_checksum(&r1cs_path, &entry.r1cs)?;
+    let pk_bytes = verify_checksum(&pk_path, &entry.proving_key)?;
+    let vk_bytes = verify_checksum(&vk_path, &entry.verifying_key)?;
+
+    let generator = match (&entry.sym, &entry.wasm) {
+        (Some(sym_hash), _) => {
+            let sym_path = dir.join(format!("{name}.sym"));
+            let sym_bytes = verify_checksum(&sym_path, sym_hash)?;
+            load_native(&r1cs_path, &r1cs_bytes, &sym_path, &sym_bytes)?
+        }
+        (None, Some(wasm_hash)) => {
+            let wasm_path = dir.join(format!("{name}_js")).join(format!("{name}.wasm"));
+            verify_checksum(&wasm_path, wasm_hash)?;
+            let config = CircomConfig::<Fr>::new(&wasm_path, &r1cs_path)
+                .map_err(|e| CircuitRegistryError::InvalidArtifact(wasm_path.display().to_string(), e.to_string()))?;
+            WitnessGenerator::Wasm {
+                config,
+                lock: Mutex::new(()),
+            }
+        }
+        (None, None) => return Err(CircuitRegistryError::NoWitnessGenerator(name.to_string())),
+    };
+
+    let proving_key = ProvingKey::<Bn254>::deserialize_uncompressed(pk_bytes.as_slice())
+        .map_err(|e| CircuitRegistryError::InvalidArtifact(pk_path.display().to_string(), e.to_string()))?;
+    let verifying_key = VerifyingKey::<Bn254>::deserialize_uncompressed(vk_bytes.as_slice())
+        .map_err(|e| CircuitRegistryError::InvalidArtifact(vk_path.display().to_string(), e.to_string()))?;
+    let prepared_verifying_key = prepare_verifying_key(&verifying_key);
+
+    Ok(CircuitArtifacts {
+        name: name.to_string(),
+        generator,
+        proving_key,
+        verifying_key,
+        prepared_verifying_key,
+    })
+}
+
+// Both circuits share RawMerkleTree and the `indexBits` decomposition, so one set of hints fits
+fn load_native(r1cs_path: &Path, r1cs_bytes: &[u8], sym_path: &Path, sym_bytes: &[u8]) -> Result<WitnessGenerator, CircuitRegistryError> {
+    let invalid = |path: &Path, e: String| CircuitRegistryError::InvalidArtifact(path.display().to_string(), e);
+
+    let solver_r1cs = R1csFile::read(r1cs_bytes).map_err(|e| invalid(r1cs_path, e.to_string()))?;
+    let signals = read_sym(sym_bytes).map_err(|e| invalid(sym_path, e.to_string()))?;
+    let calculator = WitnessCalculator::new(solver_r1cs, signals, &merkle_hints(CIRCUIT_MAX_DEPTH))
+        .map_err(|e| invalid(sym_path, e.to_string()))?;
+
+    // The native witness is in wire order, like circom 2's wasm output
+    let mut r1cs: R1CS<Fr> = R1CSFile::<Fr>::new(Cursor::new(r1cs_bytes))
+        .map_err(|e| invalid(r1cs_path, e.to_string()))?
+        .into();
+    r1cs.wire_mapping = None;
+
+    Ok(WitnessGenerator::Native { calculator, r1cs })
+}
+
+// Reads an artifact and checks it against the manifest, returning its bytes
+fn verify_checksum(path: &Path, expected: &str) -> Result<Vec<u8>, CircuitRegistryError> {
+    let bytes = fs::read(path)
+        .map_err(|_| CircuitRegistryError::MissingArtifact(path.display().to_string()))?;
+    let found = hex::encode(Sha256::digest(&bytes));
+    if !found.eq_ignore_ascii_case(expected) {
+        return Err(CircuitRegistryError::ChecksumMismatch {
+            path: path.display().to_string(),
+            expected: expected.to_string(),
+            found,
+        });
+    }
+    Ok(bytes)
+}
@@ -1,4 +1,13 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use merkle_tree_storage::TreeError;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use tracing::error;
+use utoipa::ToSchema;
 
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
 pub enum Groth16Error {
@@ -24,4 +33,255 @@ pub enum Groth16Error {
     DecompressingG2Failed,
     #[error("PublicInputGreaterThenFieldSize")]
     PublicInputGreaterThenFieldSize,
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Error)]
+pub enum CircuitRegistryError {
+    #[error("Failed to read circuit manifest {0}: {1}")]
+    InvalidManifest(String, String),
+    #[error("Circuit {0} is not listed in the manifest")]
+    NotInManifest(String),
+    #[error("Missing circuit artifact {0}")]
+    MissingArtifact(String),
+    #[error("Checksum mismatch for {path}: manifest has {expected}, file has {found}")]
+    ChecksumMismatch {
+        path: String,
+        expected: String,
+        found: String,
+    },
+    #[error("Failed to load circuit artifact {0}: {1}")]
+    InvalidArtifact(String, String),
+    #[error("Circuit {0} lists neither a sym nor a wasm witness generator")]
+    NoWitnessGenerator(String),
+    #[error("Circuit {0} is not registered")]
+    UnknownCircuit(String),
+    #[error("Witness calculation failed for {0}: {1}")]
+    WitnessCalculationFailed(String, String),
+}
+
+/// Building or checking a Groth16 proof failed.
+#[derive(Debug, Error)]
+pub enum ProofError {
+    #[error(transparent)]
+    Circuit(#[from] CircuitRegistryError),
+    #[error("Invalid witness: {0}")]
+    InvalidWitness(String),
+    #[error("Failed to create the proof: {0}")]
+    ProvingFailed(String),
+    #[error("Proof for {0} failed offline verification")]
+    VerificationFailed(String),
+    #[error("Tree depth {depth} is deeper than the {max} levels the circuits are compiled with")]
+    TreeTooDeep { depth: u32, max: usize },
+}
+
+/// Reading from or writing to Solana failed.
+#[derive(Debug, Error)]
+pub enum ChainError {
+    #[error("Failed to read keypair {path}: {reason}")]
+    Keypair { path: String, reason: String },
+    #[error("Account {0} does not exist")]
+    AccountNotFound(String),
+    #[error("Account {0} does not hold the expected data")]
+    InvalidAccount(String),
+    #[error("RPC request failed: {0}")]
+    Rpc(#[from] solana_client::client_error::ClientError),
+    #[error("Log subscription failed: {0}")]
+    Subscription(String),
+    #[error("Transaction failed: {0}")]
+    TransactionFailed(String),
+    #[error("Transaction {0} was not confirmed")]
+    NotConfirmed(String),
+    #[error("Transaction {0} expired without landing")]
+    Expired(String),
+}
+
+/// A database query failed, or returned a row the backend cannot read.
+#[derive(Debug, Error)]
+pub enum DbError {
+    #[error("Query failed: {0}")]
+    Query(#[from] sqlx::Error),
+    #[error("Invalid row in {table}: {reason}")]
+    InvalidRow { table: String, reason: String },
+}
+
+/// Every error a handler returns. The response is
+/// `{"success": false, "error": CODE, "message": ...}` with the status and code below, and
+/// `INVALID_TREE_SIZE` also carries the current `tree_size`. Codes are stable, messages are
+/// for people and may change.
+///
+/// | Code                 | Status | Meaning                                                     |
+/// |----------------------|--------|-------------------------------------------------------------|
+/// | `MISSING_PARAMS`     | 451    | A new embedding needs `name`, `breed` and `dob`             |
+/// | `INVALID_DATE`       | 400    | `dob` is not a `YYYY-MM-DD` date                            |
+/// | `INVALID_HASH`       | 400    | `embedding_hash` is not 64 hex digits                       |
+/// | `INVALID_PROOF_LEVEL`| 400    | `proof_level` is not between 0 and 255                      |
+/// | `INVALID_LEAF_HASH`  | 400    | `leaf_hash` is not a hex field element                      |
+/// | `INVALID_TREE_SIZE`  | 400    | A size is larger than the tree, or sizes are out of order   |
+/// | `LEAF_NOT_FOUND`     | 404    | The leaf is not in the tree, or not at the requested size   |
+/// | `STH_NOT_FOUND`      | 404    | No signed tree head at that size, or none yet               |
+/// | `JOB_NOT_FOUND`      | 404    | No proving job with that id                                 |
+/// | `DUPLICATE_LEAF`     | 409    | The leaf is already in the tree                             |
+/// | `TREE_FULL`          | 507    | Every slot of the tree is filled                            |
+/// | `INVALID_DEPTH`      | 500    | Tree depth is unsupported, or deeper than the circuits      |
+/// | `TREE_CORRUPT`       | 500    | A tree file holds a node that is not one                    |
+/// | `TREE_UNAVAILABLE`   | 503    | The tree service has stopped                                |
+/// | `CIRCUIT_UNAVAILABLE`| 503    | The circuit's artifacts are missing or do not load          |
+/// | `PROVING_FAILED`     | 500    | The witness or the proof could not be built                 |
+/// | `VERIFICATION_FAILED`| 422    | The proof did not verify, off chain or on chain             |
+/// | `CHAIN_UNAVAILABLE`  | 502    | Solana could not be reached or did not confirm in time      |
+/// | `CHAIN_ERROR`        | 500    | The payer keypair or an on-chain account is missing or bad  |
+/// | `QUEUE_FAILED`       | 500    | The proving job could not be stored                         |
+/// | `DB_ERROR`           | 500    | Any other database failure                                  |
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("A new embedding needs name, breed and dob")]
+    MissingParams,
+    #[error("{0:?} is not a YYYY-MM-DD date")]
+    InvalidDate(String),
+    #[error("{0:?} is not a 64 digit hex hash")]
+    InvalidHash(String),
+    #[error("Proof level {0} is not between 0 and 255")]
+    InvalidProofLevel(i32),
+    #[error("{0:?} is not a hex field element")]
+    InvalidLeafHash(String),
+    #[error("No signed tree head found")]
+    SthNotFound,
+    #[error("No proving job {0}")]
+    JobNotFound(uuid::Uuid),
+    #[error("Tree service has stopped")]
+    TreeUnavailable,
+    #[error("Failed to queue the proving job: {0}")]
+    QueueFailed(DbError),
+    #[error(transparent)]
+    Tree(#[from] TreeError),
+    #[error(transparent)]
+    Proof(#[from] ProofError),
+    #[error(transparent)]
+    Chain(#[from] ChainError),
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self {
+        ApiError::Db(DbError::Query(err))
+    }
+}
+
+impl ApiError {
+    /// Code in the `error` field of the response, see the table above.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::MissingParams => "MISSING_PARAMS",
+            ApiError::InvalidDate(_) => "INVALID_DATE",
+            ApiError::InvalidHash(_) => "INVALID_HASH",
+            ApiError::InvalidProofLevel(_) => "INVALID_PROOF_LEVEL",
+            ApiError::InvalidLeafHash(_) => "INVALID_LEAF_HASH",
+            ApiError::SthNotFound => "STH_NOT_FOUND",
+            ApiError::JobNotFound(_) => "JOB_NOT_FOUND",
+            ApiError::TreeUnavailable => "TREE_UNAVAILABLE",
+            ApiError::QueueFailed(_) => "QUEUE_FAILED",
+            ApiError::Tree(err) => match err {
+                TreeError::Full => "TREE_FULL",
+                TreeError::LeafNotFound | TreeError::LeafAfterSize { .. } => "LEAF_NOT_FOUND",
+                TreeError::InvalidDepth(_) => "INVALID_DEPTH",
+                TreeError::DuplicateLeaf { .. } => "DUPLICATE_LEAF",
+                TreeError::InvalidSize { .. } | TreeError::InvalidRange { .. } => "INVALID_TREE_SIZE",
+                TreeError::CorruptNode { .. } => "TREE_CORRUPT",
+            },
+            ApiError::Proof(err) => match err {
+                ProofError::Circuit(CircuitRegistryError::WitnessCalculationFailed(..)) => "PROVING_FAILED",
+                ProofError::Circuit(_) => "CIRCUIT_UNAVAILABLE",
+                ProofError::InvalidWitness(_) | ProofError::ProvingFailed(_) => "PROVING_FAILED",
+                ProofError::VerificationFailed(_) => "VERIFICATION_FAILED",
+                ProofError::TreeTooDeep { .. } => "INVALID_DEPTH",
+            },
+            ApiError::Chain(err) => match err {
+                ChainError::Rpc(_)
+                | ChainError::Subscription(_)
+                | ChainError::NotConfirmed(_)
+                | ChainError::Expired(_) => "CHAIN_UNAVAILABLE",
+                ChainError::TransactionFailed(_) => "VERIFICATION_FAILED",
+                ChainError::Keypair { .. } | ChainError::AccountNotFound(_) | ChainError::InvalidAccount(_) => {
+                    "CHAIN_ERROR"
+                }
+            },
+            ApiError::Db(_) => "DB_ERROR",
+        }
+    }
+
+    /// HTTP status of the response, see the table above.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::MissingParams => StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            ApiError::InvalidDate(_)
+            | ApiError::InvalidHash(_)
+            | ApiError::InvalidProofLevel(_)
+            | ApiError::InvalidLeafHash(_) => StatusCode::BAD_REQUEST,
+            ApiError::SthNotFound | ApiError::JobNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::TreeUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::QueueFailed(_) | ApiError::Db(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Tree(err) => match err {
+                TreeError::Full => StatusCode::INSUFFICIENT_STORAGE,
+                TreeError::LeafNotFound | TreeError::LeafAfterSize { .. } => StatusCode::NOT_FOUND,
+                TreeError::InvalidDepth(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                TreeError::DuplicateLeaf { .. } => StatusCode::CONFLICT,
+                TreeError::InvalidSize { .. } | TreeError::InvalidRange { .. } => StatusCode::BAD_REQUEST,
+                TreeError::CorruptNode { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            ApiError::Proof(err) => match err {
+                ProofError::Circuit(CircuitRegistryError::WitnessCalculationFailed(..)) => {
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+                ProofError::Circuit(_) => StatusCode::SERVICE_UNAVAILABLE,
+                ProofError::InvalidWitness(_) | ProofError::ProvingFailed(_) | ProofError::TreeTooDeep { .. } => {
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+                ProofError::VerificationFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            },
+            ApiError::Chain(err) => match err {
+                ChainError::Rpc(_)
+                | ChainError::Subscription(_)
+                | ChainError::NotConfirmed(_)
+                | ChainError::Expired(_) => StatusCode::BAD_GATEWAY,
+                ChainError::TransactionFailed(_) => StatusCode::UNPROCESSABLE_ENTITY,
+                ChainError::Keypair { .. } | ChainError::AccountNotFound(_) | ChainError::InvalidAccount(_) => {
+                    StatusCode::INTERNAL_SERVER_ERROR
+                }
+            },
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        if self.status().is_server_error() {
+            error!("{}: {}", self.code(), self);
+        }
+        let tree_size = match self {
+            ApiError::Tree(TreeError::InvalidSize { len, .. }) => Some(len),
+            _ => None,
+        };
+        let error_json = ErrorResponse {
+            success: false,
+            error: self.code().to_string(),
+            message: self.to_string(),
+            tree_size,
+        };
+        (self.status(), Json(error_json)).into_response()
+    }
+}
+
+/// Body of every error response, see `ApiError` for the codes.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ErrorResponse {
+    pub success: bool,
+    /// Stable code from the catalog, e.g. `LEAF_NOT_FOUND`.
+    #[schema(example = "LEAF_NOT_FOUND")]
+    pub error: String,
+    /// Human readable details, may change between releases.
+    pub message: String,
+    /// Current size of the tree, for `INVALID_TREE_SIZE`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree_size: Option<usize>,
+}
@@ -1,34 +1,43 @@
-use ark_circom::{CircomConfig, CircomBuilder};
 use ark_bn254::{Bn254, Fr, G1Projective};
-use ark_std::log2;
 use rand::SeedableRng;
 use rand::rngs::StdRng;
-use ark_groth16::{prepare_verifying_key, Groth16};
+use ark_groth16::Groth16;
 use ark_snark::SNARK;
-use ark_serialize::{CanonicalSerialize, Compress};
-use num_bigint::BigInt;
 use borsh::{to_vec, BorshDeserialize, BorshSerialize};
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_program::instruction::{AccountMeta, Instruction};
 use solana_program::pubkey::Pubkey;
 use solana_sdk::commitment_config::CommitmentConfig;
-use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::hash::Hash;
+use solana_sdk::signature::{Keypair, Signature, Signer};
 use solana_sdk::signer::EncodableKey;
 use solana_sdk::transaction::Transaction;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 
 
 use futures_util::{SinkExt, StreamExt};
 use tokio_tungstenite::connect_async;
+use tracing::{debug, info, warn};
 use url::Url;
 use serde_json::Value;
 
 use tokio::{sync::oneshot, task};
 
-use super::prove::setup;
+use super::circuit_registry::{CircuitArtifacts, CircuitInputs, CircuitRegistry, INSERT_LEAF, MERKLE_TREE_UPDATER};
+use super::errors::{ApiError, ChainError, ProofError};
 use super::verify_lite::{build_verifier, Groth16VerifierPrepared};
-use super::gen_merkle::MerkleProof;
+use groth16_verifier::verify_with_public_inputs;
+use super::gen_merkle::UpdateWitness;
+use merkle_tree_storage::{MerkleProof, CIRCUIT_MAX_DEPTH};
+
+const DEVNET_RPC_URL: &str = "https://api.devnet.solana.com";
+const VERIFIER_PROGRAM_ID: &str = "EjmMQEjv222Mz7u8jUQPC5aJ1pGDEh7xTFTupkELYV3v";
+const CONFIRMATION_ATTEMPTS: usize = 60;
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const PAYER_KEYPAIR_PATH: &str = "src/wallet-keypair.json";
 
 #[derive(BorshSerialize, BorshDeserialize)]
 pub enum ProgramInstruction {
@@ -56,15 +65,15 @@ pub enum ProgramInstruction {
 //     hex_out
 // }
 
-async fn event_listener(rx: oneshot::Receiver<()>) -> String {
+async fn event_listener(rx: oneshot::Receiver<()>) -> Result<String, ChainError> {
     let ws_url = "wss://api.devnet.solana.com";
-    let (ws_stream, _) = connect_async(Url::parse(ws_url).unwrap().to_string())
+    let (ws_stream, _) = connect_async(Url::parse(ws_url).map_err(|e| ChainError::Subscription(e.to_string()))?.to_string())
         .await
-        .expect("Failed to connect");
+        .map_err(|e| ChainError::Subscription(e.to_string()))?;
 
     let (mut write, mut read) = ws_stream.split();
 
-    let program_pubkey = "EjmMQEjv222Mz7u8jUQPC5aJ1pGDEh7xTFTupkELYV3v";
+    let program_pubkey = VERIFIER_PROGRAM_ID;
     let subscription_msg = serde_json::json!({
         "jsonrpc": "2.0",
         "id": 1,
@@ -75,11 +84,16 @@ async fn event_listener(rx: oneshot::Receiver<()>) -> String {
         ]
     });
 
-    write.send(subscription_msg.to_string().into()).await.unwrap();
-    println!("Subscribed to logs for program: {}", program_pubkey);
+    write
+        .send(subscription_msg.to_string().into())
+        .await
+        .map_err(|e| ChainError::Subscription(e.to_string()))?;
+    info!("Subscribed to logs for program: {}", program_pubkey);
 
-    // Wait for the signal before processing logs
-    rx.await.expect("Failed to receive signal");
+    // Wait for the signal before processing logs. The sender is dropped if the transaction failed
+    if rx.await.is_err() {
+        return Ok("Transaction failed".to_string());
+    }
 
     while let Some(msg) = read.next().await {
         match msg {
@@ -90,8 +104,8 @@ async fn event_listener(rx: oneshot::Receiver<()>) -> String {
                             if logs.len() > 3 {
                                 if let Some(log) = logs[3].as_str() {
                                     if let Some(stripped_log) = log.strip_prefix("Program log: ") {
-                                        println!("Extracted Log: {}", stripped_log);
-                                        return stripped_log.to_string(); // Return extracted log
+                                        debug!("Extracted Log: {}", stripped_log);
+                                        return Ok(stripped_log.to_string()); // Return extracted log
                                     }
                                 }
                             }
@@ -99,128 +113,216 @@ async fn event_listener(rx: oneshot::Receiver<()>) -> String {
                     }
                 }
             }
-            Err(e) => eprintln!("Error: {:?}", e),
+            Err(e) => warn!("Log subscription error: {:?}", e),
         }
     }
-    "No log found".to_string() // Default return value
+    Ok("No log found".to_string()) // Default return value
 
 }
 
 
 
 
-async fn zkp_verification(tx: oneshot::Sender<()>, target_leaf: [u8; 64], merkle_proof: MerkleProof) {
-    // Load the WASM and R1CS for witness and proof generation
-    let cfg = CircomConfig::<Fr>::new(
-        "../../build/circuits/InsertLeaf_js/InsertLeaf.wasm",
-        "../../build/circuits/InsertLeaf.r1cs",
-    ).unwrap();
-    
-    let mut builder = CircomBuilder::new(cfg);
-
-    // Use Fr::from_str for large field elements
-    let new_root = merkle_proof.siblings[merkle_proof.siblings.len() - 1];
-    let new_leaf = target_leaf;
-    let path_indices = merkle_proof.indice; // 11101 in binary
-    let depth = log2(path_indices as usize);
-    // Push inputs to the builder
-    builder.push_input("newLeaf", BigInt::parse_bytes(&new_leaf, 16).unwrap());
-    builder.push_input("newRoot", BigInt::parse_bytes(&new_root, 16).unwrap());
-    builder.push_input("pathIndices", path_indices);
-    builder.push_input("depth", depth);
-
-    // Push the Poseidon hash values for pathElements
-    for i in 0..merkle_proof.siblings.len()-1 {
-        let hash_bytes = merkle_proof.siblings[i];
-        builder.push_input("pathElements", BigInt::parse_bytes(&hash_bytes, 16).unwrap());
+async fn zkp_verification(tx: oneshot::Sender<()>, circuits: Arc<CircuitRegistry>, merkle_proof: MerkleProof<Fr>) -> Result<(), ApiError> {
+    let circuit = circuits.get(INSERT_LEAF).map_err(ProofError::from)?;
+    let mut builder = circuit.builder();
+
+    let path = merkle_proof
+        .to_circuit_path(CIRCUIT_MAX_DEPTH)
+        .map_err(|e| ProofError::InvalidWitness(e.to_string()))?;
+    builder.push_field("newLeaf", &path.leaf);
+    builder.push_field("newRoot", &path.root);
+    builder.push_input("pathIndices", path.path_indices);
+    builder.push_input("depth", path.depth);
+    // Padded with zeros above `depth`, which RawMerkleTree ignores
+    for path_element in path.path_elements.iter() {
+        builder.push_field("pathElements", path_element);
+    }
+
+    prove_and_submit(tx, circuit, builder).await
+}
+
+fn update_builder(circuit: &CircuitArtifacts, witness: &UpdateWitness) -> CircuitInputs {
+    let mut builder = circuit.builder();
+
+    builder.push_field("current_root", &witness.current_root);
+    builder.push_field("new_root", &witness.new_root);
+    builder.push_field("new_leaf", &witness.new_leaf);
+    builder.push_input("pathIndices", witness.path_indices);
+    builder.push_input("depth", witness.depth);
+
+    // Levels above `depth` are ignored by RawMerkleTree, pad them with zero
+    for path_element in witness.path_elements.iter() {
+        builder.push_field("pathElements", path_element);
     }
-    for _i in 0..(21-merkle_proof.siblings.len()) {
-        builder.push_input("pathElements", BigInt::parse_bytes(b"0000000000000000000000000000000000000000000000000000000000000000", 16).unwrap());
+    for _i in witness.path_elements.len()..CIRCUIT_MAX_DEPTH {
+        builder.push_input("pathElements", 0);
     }
+    builder
+}
+
+async fn update_zkp_verification(tx: oneshot::Sender<()>, circuits: Arc<CircuitRegistry>, witness: UpdateWitness) -> Result<(), ApiError> {
+    let circuit = circuits.get(MERKLE_TREE_UPDATER).map_err(ProofError::from)?;
+    let builder = update_builder(circuit, &witness);
+
+    prove_and_submit(tx, circuit, builder).await
+}
 
-    // Create an empty instance for setup
-    let circom = builder.setup();
+/// Proves the `MerkleTreeUpdater` transition in `witness`. CPU bound, run it off the async runtime.
+pub fn prove_update(circuit: &CircuitArtifacts, witness: &UpdateWitness) -> Result<Groth16VerifierPrepared, ProofError> {
+    generate_verifier(circuit, update_builder(circuit, witness))
+}
 
-    // Run the trusted setup
+// Proves the witness in `builder` and packs it in the layout the verifier program expects
+fn generate_verifier(circuit: &CircuitArtifacts, builder: CircuitInputs) -> Result<Groth16VerifierPrepared, ProofError> {
     let mut rng = StdRng::from_entropy();
-    let (proving_key, verifying_key) = setup(false, circom.clone());
-    let params = Groth16::<Bn254>::generate_random_parameters_with_reduction(circom, &mut rng).unwrap();
-    println!("Verifying Key: {:?}", params.vk);
     // Build the witness
-    let circom = builder.build().unwrap();
+    let circom = circuit.build(builder)?;
 
-    let public_inputs_fr  = circom.get_public_inputs().unwrap();
+    let public_inputs_fr = circom
+        .get_public_inputs()
+        .ok_or_else(|| ProofError::InvalidWitness(format!("Circuit {} has no witness", circuit.name())))?;
 
     // Create a proof
-    let proof = Groth16::<Bn254>::prove(&proving_key, circom.clone(), &mut rng).unwrap();
-    let mut proof_bytes = Vec::with_capacity(proof.serialized_size(Compress::No));
-    proof
-        .serialize_uncompressed(&mut proof_bytes)
-        .expect("Error serializing proof");
+    let proof = Groth16::<Bn254>::prove(&circuit.proving_key, circom, &mut rng)
+        .map_err(|e| ProofError::ProvingFailed(format!("{:?}", e)))?;
 
-    let prepared_verifying_key = prepare_verifying_key(&verifying_key);
+    let prepared_verifying_key = circuit.prepared_verifying_key.clone();
 
     let public_inputs: G1Projective =
         Groth16::<Bn254>::prepare_inputs(&prepared_verifying_key, &public_inputs_fr)
-            .expect("Error preparing inputs with public inputs and prepared verifying key");
+            .map_err(|e| ProofError::ProvingFailed(format!("Error preparing inputs with public inputs and prepared verifying key: {:?}", e)))?;
 
     let verifier_prepared = build_verifier(super::prove::ProofPackage{
         proof,
         public_inputs,
         prepared_verifying_key
     });
-    let rpc_url = "https://api.devnet.solana.com".to_string();
-    let client = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
 
+    // Check the packed proof off chain first, a bad one would only fail after paying the fee
+    let failed = || ProofError::VerificationFailed(circuit.name().to_string());
+    let packed = to_vec(&verifier_prepared).map_err(|e| ProofError::ProvingFailed(e.to_string()))?;
+    let offline = groth16_verifier::Groth16VerifierPrepared::try_from_slice(&packed).map_err(|_| failed())?;
+    if !verify_with_public_inputs(&offline, &circuit.verifying_key, &public_inputs_fr).map_err(|_| failed())? {
+        return Err(failed());
+    }
+    Ok(verifier_prepared)
+}
+
+async fn verify_transaction(client: &RpcClient, verifier_prepared: Groth16VerifierPrepared) -> Result<Transaction, ChainError> {
     // Load or create a keypair for the payer
-    let payer = Keypair::read_from_file("src/wallet-keypair.json").unwrap();
-    let program_id = Pubkey::from_str("EjmMQEjv222Mz7u8jUQPC5aJ1pGDEh7xTFTupkELYV3v").unwrap(); // Replace with your actual program ID
-    let instruction_data = to_vec(&ProgramInstruction::VerifyProof(verifier_prepared)).unwrap();
+    let payer = Keypair::read_from_file(PAYER_KEYPAIR_PATH)
+        .map_err(|e| ChainError::Keypair { path: PAYER_KEYPAIR_PATH.to_string(), reason: e.to_string() })?;
+    let program_id = Pubkey::from_str(VERIFIER_PROGRAM_ID).expect("VERIFIER_PROGRAM_ID is a valid pubkey");
+    let instruction_data = to_vec(&ProgramInstruction::VerifyProof(verifier_prepared))
+        .map_err(|e| ChainError::TransactionFailed(e.to_string()))?;
     let instruction = Instruction::new_with_bytes(
         program_id,
         instruction_data.as_slice(),
         vec![AccountMeta::new(payer.pubkey(), true)],
     );
-    // Create and send the transaction
-    let recent_blockhash = client.get_latest_blockhash().await.unwrap();
-        
-    let transaction = Transaction::new_signed_with_payer(
+    let recent_blockhash = client.get_latest_blockhash().await?;
+
+    Ok(Transaction::new_signed_with_payer(
         &[instruction],
         Some(&payer.pubkey()),
         &[&payer],
         recent_blockhash,
-    );
+    ))
+}
+
+/// Sends the verify instruction without waiting for it to land. Returns its signature and the
+/// blockhash it was signed with, which `confirm_signature` needs to tell when it expired.
+pub async fn submit_verifier(verifier_prepared: Groth16VerifierPrepared) -> Result<(Signature, Hash), ChainError> {
+    let client = RpcClient::new_with_commitment(DEVNET_RPC_URL.to_string(), CommitmentConfig::confirmed());
+    let transaction = verify_transaction(&client, verifier_prepared).await?;
+    let signature = client.send_transaction(&transaction).await?;
+    Ok((signature, transaction.message.recent_blockhash))
+}
+
+/// Waits until `signature` is confirmed. The verifier program fails the transaction on an
+/// invalid proof, so a confirmed signature means the proof was accepted.
+///
+/// The status is looked up in the transaction history too, so a transaction that landed long
+/// ago is still found. Fails with `ChainError::Expired` once `recent_blockhash` has expired
+/// and the transaction is nowhere to be found: it can no longer land and is safe to send
+/// again. Without a blockhash, or while it is valid, a transaction not found in time fails
+/// with `ChainError::NotConfirmed`, as it may still land.
+pub async fn confirm_signature(signature: &Signature, recent_blockhash: Option<&Hash>) -> Result<(), ChainError> {
+    let client = RpcClient::new_with_commitment(DEVNET_RPC_URL.to_string(), CommitmentConfig::confirmed());
+    for _ in 0..CONFIRMATION_ATTEMPTS {
+        // Checked before the status, so a transaction that landed just before its blockhash
+        // expired is still found below
+        let expired = match recent_blockhash {
+            Some(blockhash) => !client.is_blockhash_valid(blockhash, CommitmentConfig::processed()).await?,
+            None => false,
+        };
+        match client
+            .get_signature_status_with_commitment_and_history(signature, CommitmentConfig::confirmed(), true)
+            .await?
+        {
+            Some(Ok(())) => return Ok(()),
+            Some(Err(err)) => return Err(ChainError::TransactionFailed(format!("{:?}", err))),
+            None if expired => return Err(ChainError::Expired(signature.to_string())),
+            None => tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await,
+        }
+    }
+    Err(ChainError::NotConfirmed(signature.to_string()))
+}
+
+// Dropping `tx` without sending tells the listener there is no log to wait for
+async fn prove_and_submit(tx: oneshot::Sender<()>, circuit: &CircuitArtifacts, builder: CircuitInputs) -> Result<(), ApiError> {
+    let verifier_prepared = generate_verifier(circuit, builder)?;
+
+    let client = RpcClient::new_with_commitment(DEVNET_RPC_URL.to_string(), CommitmentConfig::confirmed());
+    let transaction = verify_transaction(&client, verifier_prepared).await?;
     // Send and confirm transaction
-    match client
+    let signature = client
         .send_and_confirm_transaction_with_spinner(&transaction)
         .await
-    {
-        Ok(signature) => {
-            println!("Transaction succeeded! Signature: {}", signature);
-            tx.send(()).expect("Failed to send signal");
-        },
-        Err(err) => println!("Transaction failed: {:?}", err),
-    }
+        .map_err(|err| ChainError::TransactionFailed(err.to_string()))?;
+    info!("Transaction succeeded! Signature: {}", signature);
+    // The listener only stops early if it failed, which it reports itself
+    let _ = tx.send(());
+    Ok(())
+}
 
 
+fn spawn_listener(rx: oneshot::Receiver<()>) -> task::JoinHandle<Result<bool, ChainError>> {
+    task::spawn(async move {
+        let result = event_listener(rx).await?;
+        debug!("Verifier log: {result}");
+        Ok(result.as_str() == "true")
+    })
 }
 
+// Waits for the prover and the listener. A panic in either is reported, not propagated
+async fn join_verification(
+    zkp_handle: task::JoinHandle<Result<(), ApiError>>,
+    listener_handle: task::JoinHandle<Result<bool, ChainError>>,
+) -> Result<bool, ApiError> {
+    let (zkp_result, listener_result) = tokio::join!(zkp_handle, listener_handle);
+    let panicked = |e: task::JoinError| ProofError::ProvingFailed(e.to_string());
+    zkp_result.map_err(panicked)??;
+    Ok(listener_result.map_err(panicked)??)
+}
 
-pub async fn insert_leaf_zkp(target_leaf: [u8; 64], merkle_proof: MerkleProof) -> bool {
-     
+pub async fn insert_leaf_zkp(circuits: Arc<CircuitRegistry>, merkle_proof: MerkleProof<Fr>) -> Result<bool, ApiError> {
     let (tx, rx) = oneshot::channel();
 
-    let zkp_handle = task::spawn(async move {
-        zkp_verification(tx, target_leaf, merkle_proof).await;
-        true // Return true after verification
-    });
-    let listener_handle = task::spawn(async move {
-        let result = event_listener(rx).await;
-        println!("{result}");
-        result.as_str() == "true"
-    });
+    let zkp_handle = task::spawn(zkp_verification(tx, circuits, merkle_proof));
+    let listener_handle = spawn_listener(rx);
 
     // Wait for both tasks to complete
-    let (zkp_result, listener_result) = tokio::join!(zkp_handle, listener_handle);
-    zkp_result.unwrap() && listener_result.unwrap()
-}
\ No newline at end of file
+    join_verification(zkp_handle, listener_handle).await
+}
+
+/// Proves the root transition of appending a leaf and waits for the on-chain verifier.
+pub async fn update_leaf_zkp(circuits: Arc<CircuitRegistry>, witness: UpdateWitness) -> Result<bool, ApiError> {
+    let (tx, rx) = oneshot::channel();
+
+    let zkp_handle = task::spawn(update_zkp_verification(tx, circuits, witness));
+    let listener_handle = spawn_listener(rx);
+
+    join_verification(zkp_handle, listener_handle).await
+}
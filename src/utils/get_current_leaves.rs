@@ -1,32 +1,27 @@
-use std::{collections::HashMap, sync::Arc};
+use std::sync::Arc;
 
 use axum::extract::State;
 
 use crate::{models::ddid_models::CoreIdTree, AppState};
 
+use super::errors::DbError;
 
+
+/// Leaf hexes of the tree in `table_name`, in slot order.
 pub async fn get_current_leaves(
     table_name: String,
     State(data): State<Arc<AppState>>
-) -> HashMap<String, usize> {
+) -> Result<Vec<String>, DbError> {
     let query = format!(r#"SELECT * FROM {} LIMIT 1"#, table_name);
     let row = sqlx::query_as::<_, CoreIdTree>(
         &query
     )
-    .bind(table_name)
-    .fetch_one(&data.db)
-    .await;
-    let retrieved_map: HashMap<String, usize>;
-    match row {
-        Ok(row) => {
-            let data = row.leaves;
-            retrieved_map = serde_json::from_value(data).unwrap();
-            println!("core id tree data: {:?}", retrieved_map);
-
-        }
-        Err(_) => {
-            retrieved_map = HashMap::new();
-        }
-    }
-    return retrieved_map;
-}
\ No newline at end of file
+    .fetch_optional(&data.db)
+    .await?;
+    let leaves: Vec<String> = match row {
+        Some(row) => serde_json::from_value(row.leaves)
+            .map_err(|e| DbError::InvalidRow { table: table_name, reason: e.to_string() })?,
+        None => Vec::new(),
+    };
+    Ok(leaves)
+}
@@ -6,16 +6,21 @@ use solana_sdk::signature::Signature;
 use std::str::FromStr;
 use borsh::{BorshDeserialize, BorshSerialize, to_vec};
 
-pub fn get_current_root() -> Result<Vec<u8>, String> {
+use super::errors::ChainError;
+
+const PAYER_KEYPAIR_PATH: &str = "src/wallet-keypair.json";
+
+pub fn get_current_root() -> Result<Vec<u8>, ChainError> {
     // Program ID (replace with your actual program ID)
-    let program_id = Pubkey::from_str("9guwSzLJSkomxdbTM6TfKTF3KYSDxLNeSsCRdPaBGVpU").unwrap();
+    let program_id = Pubkey::from_str("9guwSzLJSkomxdbTM6TfKTF3KYSDxLNeSsCRdPaBGVpU").expect("Program ID is a valid pubkey");
      
     // Connect to the Solana devnet
     let rpc_url = String::from("https://api.devnet.solana.com");
     let client = RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed());
     
     // Generate a new keypair for the payer
-    let payer = Keypair::read_from_file("src/wallet-keypair.json").unwrap();
+    let payer = Keypair::read_from_file(PAYER_KEYPAIR_PATH)
+        .map_err(|e| ChainError::Keypair { path: PAYER_KEYPAIR_PATH.to_string(), reason: e.to_string() })?;
     let seed_text = "root_hashes";
     // Derive PDA
     let (pda, _) = Pubkey::find_program_address(
@@ -24,6 +29,6 @@ pub fn get_current_root() -> Result<Vec<u8>, String> {
     );
     match client.get_account(&pda) {
         Ok(account) => Ok(account.data),
-        Err(_) => Err("PDA account does not exist!".to_string()), // Return an error message here
+        Err(_) => Err(ChainError::AccountNotFound(pda.to_string())),
     }
 }
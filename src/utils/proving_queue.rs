@@ -0,0 +1,281 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use groth16_verifier::codec::Codec;
+use merkle_tree_storage::Fr;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{hash::Hash, signature::Signature};
+use sqlx::{PgExecutor, PgPool};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::models::ddid_models::{JobStatus, ProvingJobModel};
+
+use super::circuit_registry::{CircuitRegistry, MERKLE_TREE_UPDATER};
+use super::errors::ChainError;
+use super::gen_merkle::UpdateWitness;
+use super::gen_zkp::{confirm_signature, prove_update, submit_verifier};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+// A lease not refreshed for this long belongs to a worker that stopped
+const LEASE_TIMEOUT: Duration = Duration::from_secs(60);
+// Proving and submitting attempts before a job is failed
+const MAX_ATTEMPTS: i32 = 3;
+
+// JSONB form of an `UpdateWitness`, field elements as `Codec::to_hex`
+#[derive(Serialize, Deserialize)]
+struct StoredUpdateWitness {
+    current_root: String,
+    new_root: String,
+    new_leaf: String,
+    path_indices: u32,
+    depth: u32,
+    path_elements: Vec<String>,
+}
+
+impl From<&UpdateWitness> for StoredUpdateWitness {
+    fn from(witness: &UpdateWitness) -> Self {
+        Self {
+            current_root: witness.current_root.to_hex(),
+            new_root: witness.new_root.to_hex(),
+            new_leaf: witness.new_leaf.to_hex(),
+            path_indices: witness.path_indices,
+            depth: witness.depth,
+            path_elements: witness.path_elements.iter().map(Codec::to_hex).collect(),
+        }
+    }
+}
+
+impl TryFrom<StoredUpdateWitness> for UpdateWitness {
+    type Error = anyhow::Error;
+
+    fn try_from(stored: StoredUpdateWitness) -> Result<Self, Self::Error> {
+        Ok(Self {
+            current_root: Fr::from_hex(&stored.current_root)?,
+            new_root: Fr::from_hex(&stored.new_root)?,
+            new_leaf: Fr::from_hex(&stored.new_leaf)?,
+            path_indices: stored.path_indices,
+            depth: stored.depth,
+            path_elements: stored
+                .path_elements
+                .iter()
+                .map(|element| Fr::from_hex(element))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Stores a `MerkleTreeUpdater` proof request and returns its job id. The proof is built by
+/// the workers started with `spawn_proving_workers`. Takes a transaction so the job can be
+/// stored together with the row it proves.
+pub async fn enqueue_update_proof(db: impl PgExecutor<'_>, witness: &UpdateWitness) -> Result<Uuid, sqlx::Error> {
+    let id = Uuid::new_v4();
+    sqlx::query(
+        r#"INSERT INTO ProvingJob (id, circuit, witness, status) VALUES ($1, $2, $3, $4)"#
+    )
+    .bind(id)
+    .bind(MERKLE_TREE_UPDATER)
+    .bind(sqlx::types::Json(StoredUpdateWitness::from(witness)))
+    .bind(JobStatus::Queued.as_str())
+    .execute(db)
+    .await?;
+    Ok(id)
+}
+
+pub async fn fetch_job(db: &PgPool, id: Uuid) -> Result<Option<ProvingJobModel>, sqlx::Error> {
+    sqlx::query_as::<_, ProvingJobModel>(r#"SELECT * FROM ProvingJob WHERE id = $1"#)
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+/// Starts `workers` tasks that take queued jobs, prove them and submit them on chain.
+/// A worker holds its job through a lease it refreshes every `HEARTBEAT_INTERVAL`. Jobs whose
+/// lease went stale, e.g. because the server stopped, are taken over: a job left in `proving`
+/// is proven again, and one left in `submitted` is confirmed, or submitted again once its
+/// transaction can no longer land.
+pub fn spawn_proving_workers(db: PgPool, circuits: Arc<CircuitRegistry>, workers: usize) {
+    for worker in 0..workers {
+        let db = db.clone();
+        let circuits = circuits.clone();
+        tokio::spawn(async move {
+            info!("Proving worker {} started", worker);
+            loop {
+                match claim_job(&db).await {
+                    Ok(Some(job)) => run_job(&db, circuits.clone(), job).await,
+                    Ok(None) => tokio::time::sleep(POLL_INTERVAL).await,
+                    Err(err) => {
+                        warn!("Proving worker {} failed to claim a job: {:?}", worker, err);
+                        tokio::time::sleep(POLL_INTERVAL).await;
+                    }
+                }
+            }
+        });
+    }
+}
+
+// Leases the oldest job that is queued or whose worker stopped; SKIP LOCKED keeps workers off
+// each other's jobs. A submitted job stays `submitted` and does not count as another attempt.
+async fn claim_job(db: &PgPool) -> Result<Option<ProvingJobModel>, sqlx::Error> {
+    sqlx::query_as::<_, ProvingJobModel>(
+        r#"UPDATE ProvingJob SET
+            status = CASE WHEN status = $3 THEN status ELSE $1 END,
+            attempts = CASE WHEN status = $3 THEN attempts ELSE attempts + 1 END,
+            locked_at = NOW(),
+            updated_at = NOW()
+        WHERE id = (
+            SELECT id FROM ProvingJob
+            WHERE status = $2 OR (status IN ($1, $3) AND locked_at < NOW() - make_interval(secs => $4))
+            ORDER BY created_at
+            FOR UPDATE SKIP LOCKED
+            LIMIT 1
+        )
+        RETURNING *"#
+    )
+    .bind(JobStatus::Proving.as_str())
+    .bind(JobStatus::Queued.as_str())
+    .bind(JobStatus::Submitted.as_str())
+    .bind(LEASE_TIMEOUT.as_secs_f64())
+    .fetch_optional(db)
+    .await
+}
+
+async fn run_job(db: &PgPool, circuits: Arc<CircuitRegistry>, job: ProvingJobModel) {
+    let heartbeat = tokio::spawn(heartbeat(db.clone(), job.id));
+    let result = if job.status == JobStatus::Submitted.as_str() {
+        reconcile_submitted(db, &job).await
+    } else if job.attempts > MAX_ATTEMPTS {
+        Err(anyhow::anyhow!("Gave up after {} attempts", MAX_ATTEMPTS))
+    } else {
+        prove_submit_confirm(db, circuits, &job).await
+    };
+    heartbeat.abort();
+
+    if let Err(err) = result {
+        error!("Proving job {} failed: {:?}", job.id, err);
+        let _ = retry_or_fail(db, &job, err.to_string()).await;
+    }
+}
+
+// Keeps the lease of job `id` fresh until the task is aborted
+async fn heartbeat(db: PgPool, id: Uuid) {
+    let mut ticker = tokio::time::interval(HEARTBEAT_INTERVAL);
+    loop {
+        ticker.tick().await;
+        if let Err(err) = sqlx::query(r#"UPDATE ProvingJob SET locked_at = NOW() WHERE id = $1"#)
+            .bind(id)
+            .execute(&db)
+            .await
+        {
+            warn!("Failed to refresh the lease of proving job {}: {:?}", id, err);
+        }
+    }
+}
+
+// Queues the job for another attempt, or fails it once it used `MAX_ATTEMPTS`
+async fn retry_or_fail(db: &PgPool, job: &ProvingJobModel, error: String) -> Result<(), sqlx::Error> {
+    let status = if job.attempts < MAX_ATTEMPTS { JobStatus::Queued } else { JobStatus::Failed };
+    set_status(db, job.id, status, None, Some(error)).await
+}
+
+async fn prove_submit_confirm(db: &PgPool, circuits: Arc<CircuitRegistry>, job: &ProvingJobModel) -> anyhow::Result<()> {
+    let stored: StoredUpdateWitness = serde_json::from_value(job.witness.clone())?;
+    let witness = UpdateWitness::try_from(stored)?;
+
+    let verifier_prepared = tokio::task::spawn_blocking(move || {
+        let circuit = circuits.get(MERKLE_TREE_UPDATER)?;
+        prove_update(circuit, &witness)
+    })
+    .await??;
+
+    let (signature, blockhash) = submit_verifier(verifier_prepared).await?;
+    set_submitted(db, job.id, &signature, &blockhash).await?;
+    let confirmed = confirm_signature(&signature, Some(&blockhash)).await;
+    finish_submitted(db, job, &signature, confirmed).await
+}
+
+// A job left in `submitted` may have landed after its worker stopped, see `finish_submitted`.
+// Jobs submitted before blockhashes were stored cannot be told to have expired, so one that
+// is not found is failed rather than submitted again.
+async fn reconcile_submitted(db: &PgPool, job: &ProvingJobModel) -> anyhow::Result<()> {
+    let signature = job
+        .signature
+        .as_deref()
+        .ok_or_else(|| anyhow::anyhow!("Submitted job has no signature"))?;
+    let signature = Signature::from_str(signature)?;
+    let blockhash = job.blockhash.as_deref().map(Hash::from_str).transpose()?;
+    match (confirm_signature(&signature, blockhash.as_ref()).await, blockhash) {
+        (Err(ChainError::NotConfirmed(_)), None) => {
+            let error = format!("{} was not found and has no blockhash, check it before resubmitting", signature);
+            set_status(db, job.id, JobStatus::Failed, None, Some(error)).await?;
+            Ok(())
+        }
+        (confirmed, _) => finish_submitted(db, job, &signature, confirmed).await,
+    }
+}
+
+// Finishes a submitted job. A confirmed signature confirms it, and one whose blockhash expired
+// without it landing is queued again: it can no longer land, so proving and submitting again
+// cannot apply the update twice. While it may still land the job stays `submitted` and its
+// lease runs out, so it is checked again later. A transaction that failed on chain is retried
+// like a failed proof.
+async fn finish_submitted(
+    db: &PgPool,
+    job: &ProvingJobModel,
+    signature: &Signature,
+    confirmed: Result<(), ChainError>,
+) -> anyhow::Result<()> {
+    match confirmed {
+        Ok(()) => {
+            set_status(db, job.id, JobStatus::Confirmed, None, None).await?;
+            info!("Proving job {} confirmed: {}", job.id, signature);
+            Ok(())
+        }
+        Err(err @ (ChainError::Rpc(_) | ChainError::NotConfirmed(_))) => {
+            warn!("Proving job {} is not confirmed yet, checking again later: {}", job.id, err);
+            Ok(())
+        }
+        Err(err @ ChainError::Expired(_)) => {
+            info!("Proving job {}: {}, submitting again", job.id, err);
+            retry_or_fail(db, job, err.to_string()).await?;
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn set_submitted(db: &PgPool, id: Uuid, signature: &Signature, blockhash: &Hash) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"UPDATE ProvingJob SET status = $2, signature = $3, blockhash = $4, error = NULL, updated_at = NOW() WHERE id = $1"#
+    )
+    .bind(id)
+    .bind(JobStatus::Submitted.as_str())
+    .bind(signature.to_string())
+    .bind(blockhash.to_string())
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+async fn set_status(
+    db: &PgPool,
+    id: Uuid,
+    status: JobStatus,
+    signature: Option<String>,
+    error: Option<String>,
+) -> Result<(), sqlx::Error> {
+    // Only jobs a worker is on hold a lease
+    let leased = matches!(status, JobStatus::Proving | JobStatus::Submitted);
+    sqlx::query(
+        r#"UPDATE ProvingJob SET status = $2, signature = COALESCE($3, signature), error = $4,
+        locked_at = CASE WHEN $5 THEN locked_at END, updated_at = NOW() WHERE id = $1"#
+    )
+    .bind(id)
+    .bind(status.as_str())
+    .bind(signature)
+    .bind(error)
+    .bind(leased)
+    .execute(db)
+    .await?;
+    Ok(())
+}
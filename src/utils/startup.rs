@@ -0,0 +1,59 @@
+//! What the server loads and starts before it accepts requests.
+//!
+//! The server's entry point is not part of this tree. It is meant to build `AppState` from
+//! `Startup::load` and then call `spawn_background_tasks` before binding its port, so a missing
+//! or stale circuit artifact, a tree the circuits cannot prove or an unreadable keypair stops
+//! the server first.
+//!
+//! Everything here logs through `tracing`, so install a subscriber (e.g.
+//! `tracing_subscriber::fmt().init()`) before calling `Startup::load`.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use merkle_tree_storage::{MerkleTreeStorage, CIRCUIT_MAX_DEPTH};
+use sqlx::PgPool;
+
+use crate::AppState;
+
+use super::circuit_registry::CircuitRegistry;
+use super::proving_queue::spawn_proving_workers;
+use super::tree_heads::{spawn_tree_head_signer, TreeHeadConfig};
+use super::tree_service::TreeService;
+
+const CORE_ID_TREE: &str = "CoreIdTree";
+const DEFAULT_PROVING_WORKERS: usize = 1;
+
+/// The parts of `AppState` that are loaded rather than connected.
+pub struct Startup {
+    pub circuits: Arc<CircuitRegistry>,
+    pub merkle_tree: TreeService,
+}
+
+impl Startup {
+    /// Loads the circuits with `CircuitRegistry::from_env` and the DDID tree from `CoreIdTree`,
+    /// which is as deep as the circuits allow.
+    pub async fn load(db: &PgPool) -> anyhow::Result<Self> {
+        let circuits = CircuitRegistry::from_env().context("Failed to load the circuits, see build/circuits/README.md")?;
+        let tree = MerkleTreeStorage::load(db, CORE_ID_TREE, CIRCUIT_MAX_DEPTH as u32)
+            .await
+            .with_context(|| format!("Failed to load {}", CORE_ID_TREE))?;
+        Ok(Self {
+            circuits: Arc::new(circuits),
+            merkle_tree: TreeService::spawn(tree)?,
+        })
+    }
+}
+
+/// Starts `PROVING_WORKERS` (default 1) proving workers and the tree head signer, configured
+/// with `TreeHeadConfig::from_env`.
+pub fn spawn_background_tasks(state: Arc<AppState>) -> anyhow::Result<()> {
+    let workers = match std::env::var("PROVING_WORKERS") {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| anyhow::anyhow!("PROVING_WORKERS is not a number: {}", value))?,
+        Err(_) => DEFAULT_PROVING_WORKERS,
+    };
+    spawn_proving_workers(state.db.clone(), state.circuits.clone(), workers);
+    spawn_tree_head_signer(state, TreeHeadConfig::from_env()?)
+}
@@ -0,0 +1,249 @@
+//! Signed tree heads (STHs) for the DDID tree, so third parties can audit the backend as a
+//! verifiable log.
+//!
+//! A head commits to the tree size, its root and a timestamp, and is signed with the server's
+//! ed25519 Solana keypair. The signed message is
+//! `"ddid-sth-v1" || tree_size (u64 BE) || timestamp in ms (i64 BE) || root (32 bytes BE)`.
+//! Heads are kept in `SignedTreeHead`. The root of the latest one is periodically written to
+//! `ddid_root` in the root program's `HashAccount` PDA, which anchors it on chain.
+
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use borsh::{to_vec, BorshDeserialize, BorshSerialize};
+use chrono::{DateTime, Utc};
+use groth16_verifier::codec::Codec;
+use merkle_tree_storage::{fr_to_bytes, Fr};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::pubkey::Pubkey;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::signer::EncodableKey;
+use solana_sdk::system_program;
+use solana_sdk::transaction::Transaction;
+use sqlx::PgPool;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::{models::ddid_models::SignedTreeHeadModel, AppState};
+
+use super::errors::ChainError;
+
+const STH_DOMAIN: &[u8] = b"ddid-sth-v1";
+const DEFAULT_KEYPAIR_PATH: &str = "src/wallet-keypair.json";
+const DEFAULT_SIGN_INTERVAL_SECS: u64 = 10;
+const DEFAULT_ANCHOR_INTERVAL_SECS: u64 = 600;
+const DEVNET_RPC_URL: &str = "https://api.devnet.solana.com";
+const ROOT_PROGRAM_ID: &str = "9guwSzLJSkomxdbTM6TfKTF3KYSDxLNeSsCRdPaBGVpU";
+const ROOT_SEED: &[u8] = b"root_hashes";
+
+// Instructions and account of `merkle_root_hash_solana_program`, Borsh compatible with it
+#[derive(BorshSerialize, BorshDeserialize)]
+enum RootProgramInstruction {
+    MerkleRootHash([u8; 32], [u8; 32], [u8; 32]),
+    CreateAccount(bool),
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+struct HashAccount {
+    ddid_root: [u8; 32],
+    merchant_root: [u8; 32],
+    merchant_record_root: [u8; 32],
+}
+
+/// How often heads are signed and anchored.
+pub struct TreeHeadConfig {
+    pub keypair_path: String,
+    /// Inserts made within one interval are covered by a single head.
+    pub sign_interval: Duration,
+    /// Minimum time between two anchors, `None` to never anchor.
+    pub anchor_interval: Option<Duration>,
+}
+
+impl TreeHeadConfig {
+    /// Reads `STH_KEYPAIR_PATH` (default `src/wallet-keypair.json`), `STH_INTERVAL_SECS`
+    /// (default 10) and `STH_ANCHOR_INTERVAL_SECS` (default 600, 0 disables anchoring).
+    pub fn from_env() -> anyhow::Result<Self> {
+        let keypair_path = std::env::var("STH_KEYPAIR_PATH").unwrap_or_else(|_| DEFAULT_KEYPAIR_PATH.to_string());
+        let sign_interval = env_secs("STH_INTERVAL_SECS", DEFAULT_SIGN_INTERVAL_SECS)?;
+        let anchor_interval = env_secs("STH_ANCHOR_INTERVAL_SECS", DEFAULT_ANCHOR_INTERVAL_SECS)?;
+        if sign_interval == 0 {
+            return Err(anyhow::anyhow!("STH_INTERVAL_SECS must be positive"));
+        }
+        Ok(Self {
+            keypair_path,
+            sign_interval: Duration::from_secs(sign_interval),
+            anchor_interval: (anchor_interval > 0).then_some(Duration::from_secs(anchor_interval)),
+        })
+    }
+}
+
+fn env_secs(name: &str, default: u64) -> anyhow::Result<u64> {
+    match std::env::var(name) {
+        Ok(value) => value.parse().map_err(|_| anyhow::anyhow!("{} is not a number of seconds: {}", name, value)),
+        Err(_) => Ok(default),
+    }
+}
+
+/// What a signed tree head commits to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeHead {
+    pub tree_size: u64,
+    pub root: Fr,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl TreeHead {
+    /// The bytes that are signed, see the module docs.
+    pub fn message(&self) -> Vec<u8> {
+        let mut message = STH_DOMAIN.to_vec();
+        message.extend_from_slice(&self.tree_size.to_be_bytes());
+        message.extend_from_slice(&self.timestamp.timestamp_millis().to_be_bytes());
+        message.extend_from_slice(&fr_to_bytes(&self.root));
+        message
+    }
+
+    pub fn sign(&self, keypair: &Keypair) -> Signature {
+        keypair.sign_message(&self.message())
+    }
+
+    pub fn verify(&self, public_key: &Pubkey, signature: &Signature) -> bool {
+        signature.verify(public_key.as_ref(), &self.message())
+    }
+}
+
+impl TryFrom<&SignedTreeHeadModel> for TreeHead {
+    type Error = anyhow::Error;
+
+    fn try_from(head: &SignedTreeHeadModel) -> Result<Self, Self::Error> {
+        Ok(Self {
+            tree_size: u64::try_from(head.tree_size)?,
+            root: Fr::from_hex(&head.root_hash)?,
+            timestamp: head.timestamp,
+        })
+    }
+}
+
+pub async fn latest_tree_head(db: &PgPool) -> Result<Option<SignedTreeHeadModel>, sqlx::Error> {
+    sqlx::query_as::<_, SignedTreeHeadModel>(r#"SELECT * FROM SignedTreeHead ORDER BY tree_size DESC LIMIT 1"#)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn tree_head_at(db: &PgPool, tree_size: i64) -> Result<Option<SignedTreeHeadModel>, sqlx::Error> {
+    sqlx::query_as::<_, SignedTreeHeadModel>(r#"SELECT * FROM SignedTreeHead WHERE tree_size = $1"#)
+        .bind(tree_size)
+        .fetch_optional(db)
+        .await
+}
+
+/// Signs and stores a head for the current tree, unless the latest head already covers it.
+pub async fn sign_tree_head(state: &AppState, keypair: &Keypair) -> anyhow::Result<Option<SignedTreeHeadModel>> {
+    let (tree_size, root) = state.merkle_tree.head().await?;
+    let latest = latest_tree_head(&state.db).await?;
+    if latest.is_some_and(|head| head.tree_size as usize >= tree_size) {
+        return Ok(None);
+    }
+
+    let head = TreeHead {
+        tree_size: tree_size as u64,
+        root,
+        timestamp: Utc::now(),
+    };
+    let signature = head.sign(keypair);
+    // Another instance may have signed the same size first, its head stands
+    let stored = sqlx::query_as::<_, SignedTreeHeadModel>(
+        r#"INSERT INTO SignedTreeHead (tree_size, root_hash, timestamp, public_key, signature)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (tree_size) DO NOTHING
+        RETURNING *"#
+    )
+    .bind(tree_size as i64)
+    .bind(root.to_hex())
+    .bind(head.timestamp)
+    .bind(keypair.pubkey().to_string())
+    .bind(signature.to_string())
+    .fetch_optional(&state.db)
+    .await?;
+    Ok(stored)
+}
+
+/// Writes the root of `head` to `ddid_root` in the `HashAccount` PDA of `keypair`, keeping the
+/// merchant roots, and records the transaction on the head.
+pub async fn anchor_tree_head(db: &PgPool, keypair: &Keypair, head: &SignedTreeHeadModel) -> anyhow::Result<Signature> {
+    let client = RpcClient::new_with_commitment(DEVNET_RPC_URL.to_string(), CommitmentConfig::confirmed());
+    let program_id = Pubkey::from_str(ROOT_PROGRAM_ID).expect("ROOT_PROGRAM_ID is a valid pubkey");
+    let (pda, _) = Pubkey::find_program_address(&[keypair.pubkey().as_ref(), ROOT_SEED], &program_id);
+
+    let current = match client.get_account(&pda).await {
+        Ok(account) => HashAccount::deserialize(&mut account.data.as_slice())?,
+        Err(_) => return Err(ChainError::AccountNotFound(pda.to_string()).into()),
+    };
+    let ddid_root = fr_to_bytes(&Fr::from_hex(&head.root_hash)?);
+    let instruction_data = to_vec(&RootProgramInstruction::MerkleRootHash(
+        ddid_root,
+        current.merchant_root,
+        current.merchant_record_root,
+    ))?;
+    let instruction = Instruction::new_with_bytes(
+        program_id,
+        instruction_data.as_slice(),
+        vec![
+            AccountMeta::new(keypair.pubkey(), true),
+            AccountMeta::new(pda, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    );
+    let recent_blockhash = client.get_latest_blockhash().await?;
+    let transaction = Transaction::new_signed_with_payer(&[instruction], Some(&keypair.pubkey()), &[keypair], recent_blockhash);
+    let signature = client.send_and_confirm_transaction(&transaction).await?;
+
+    sqlx::query(r#"UPDATE SignedTreeHead SET anchor_signature = $2 WHERE tree_size = $1"#)
+        .bind(head.tree_size)
+        .bind(signature.to_string())
+        .execute(db)
+        .await?;
+    Ok(signature)
+}
+
+/// Starts the task that signs a head every `sign_interval` the tree grew in, and anchors the
+/// latest head at most once per `anchor_interval`. Fails if the keypair cannot be read.
+pub fn spawn_tree_head_signer(state: Arc<AppState>, config: TreeHeadConfig) -> anyhow::Result<()> {
+    let keypair = Keypair::read_from_file(&config.keypair_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read STH keypair {}: {}", config.keypair_path, e))?;
+    info!("Signing tree heads as {}", keypair.pubkey());
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(config.sign_interval);
+        let mut last_anchor: Option<Instant> = None;
+        loop {
+            ticker.tick().await;
+            match sign_tree_head(&state, &keypair).await {
+                Ok(Some(head)) => info!("Signed tree head at size {}", head.tree_size),
+                Ok(None) => {}
+                Err(err) => warn!("Failed to sign a tree head: {:?}", err),
+            }
+
+            let Some(anchor_interval) = config.anchor_interval else {
+                continue;
+            };
+            if last_anchor.is_some_and(|at| at.elapsed() < anchor_interval) {
+                continue;
+            }
+            match latest_tree_head(&state.db).await {
+                Ok(Some(head)) if head.anchor_signature.is_none() => {
+                    match anchor_tree_head(&state.db, &keypair, &head).await {
+                        Ok(signature) => {
+                            info!("Anchored tree head at size {}: {}", head.tree_size, signature);
+                            last_anchor = Some(Instant::now());
+                        }
+                        Err(err) => warn!("Failed to anchor tree head at size {}: {:?}", head.tree_size, err),
+                    }
+                }
+                Ok(_) => {}
+                Err(err) => warn!("Failed to read the latest tree head: {:?}", err),
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! The DDID tree, owned by one thread that takes commands over a channel.
+//!
+//! An append is prepared first and applied later: `prepare_append` works out everything a
+//! caller needs (the append proof against the old root, the new root and the new leaf's proof)
+//! without changing the tree, so the caller can store the leaf in its transaction and apply
+//! the append only once that commits. Until then no other append can be prepared.
+//! Reads run a closure against the tree between two appends, so e.g. a proof and the roots
+//! it is checked against always come from the same tree.
+//!
+//! `AppState::merkle_tree` is a `TreeService::spawn(tree)` of the tree loaded at startup, which
+//! fails there if the tree is deeper than the circuits can prove.
+
+use std::{sync::Arc, thread};
+
+use merkle_tree_storage::{Fr, MerkleTreeStorage, PreparedAppend, TreeError, CIRCUIT_MAX_DEPTH};
+use tokio::sync::{mpsc, oneshot, Mutex, OwnedMutexGuard};
+
+use super::errors::{ApiError, ProofError};
+
+// Requests waiting for the tree before senders have to wait themselves
+const QUEUE_LEN: usize = 1024;
+
+type Read = Box<dyn FnOnce(&MerkleTreeStorage) + Send>;
+
+enum Command {
+    Apply { prepared: PreparedAppend<Fr>, reply: oneshot::Sender<Result<usize, TreeError>> },
+    Read(Read),
+}
+
+/// Handle to the thread owning the tree. Cloning it shares the thread.
+#[derive(Clone)]
+pub struct TreeService {
+    commands: mpsc::Sender<Command>,
+    // Held from preparing an append until it is applied or dropped
+    appending: Arc<Mutex<()>>,
+}
+
+/// An append prepared against the tree, see `TreeService::prepare_append`. Dropping it
+/// leaves the tree as it was.
+pub struct PendingAppend {
+    pub prepared: PreparedAppend<Fr>,
+    service: TreeService,
+    _appending: OwnedMutexGuard<()>,
+}
+
+impl TreeService {
+    /// Moves `tree` onto its own thread. Hashing stays off the async runtime's workers, and the
+    /// thread ends once every handle is dropped. Fails with `ProofError::TreeTooDeep` if the
+    /// circuits cannot prove paths of `tree`'s depth.
+    pub fn spawn(mut tree: MerkleTreeStorage) -> Result<Self, ProofError> {
+        if tree.depth() as usize > CIRCUIT_MAX_DEPTH {
+            return Err(ProofError::TreeTooDeep { depth: tree.depth(), max: CIRCUIT_MAX_DEPTH });
+        }
+        let (commands, mut receiver) = mpsc::channel(QUEUE_LEN);
+        thread::Builder::new()
+            .name("tree-service".to_string())
+            .spawn(move || {
+                while let Some(command) = receiver.blocking_recv() {
+                    match command {
+                        Command::Apply { prepared, reply } => {
+                            let _ = reply.send(tree.apply_append(&prepared));
+                        }
+                        Command::Read(read) => read(&tree),
+                    }
+                }
+            })
+            .expect("Failed to start the tree service thread");
+        Ok(Self { commands, appending: Arc::new(Mutex::new(())) })
+    }
+
+    /// Prepares appending `leaf` in the next empty slot, waiting for the append prepared
+    /// before to be applied or dropped. Fails with `ApiError::TreeUnavailable` if the thread
+    /// has stopped, and with `ApiError::Tree` if the tree is full.
+    pub async fn prepare_append(&self, leaf: Fr) -> Result<PendingAppend, ApiError> {
+        let appending = self.appending.clone().lock_owned().await;
+        let prepared = self.read(move |tree| tree.prepare_append(leaf)).await??;
+        Ok(PendingAppend { prepared, service: self.clone(), _appending: appending })
+    }
+
+    /// Runs `read` against the tree as it is between two mutations and returns its result.
+    pub async fn read<T, F>(&self, read: F) -> Result<T, ApiError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&MerkleTreeStorage) -> T + Send + 'static,
+    {
+        let (reply, receiver) = oneshot::channel();
+        self.send(Command::Read(Box::new(move |tree| {
+            let _ = reply.send(read(tree));
+        })))
+        .await?;
+        receiver.await.map_err(|_| ApiError::TreeUnavailable)
+    }
+
+    /// Size and root of the tree, read together.
+    pub async fn head(&self) -> Result<(usize, Fr), ApiError> {
+        self.read(|tree| (tree.len(), tree.root())).await
+    }
+
+    async fn send(&self, command: Command) -> Result<(), ApiError> {
+        self.commands.send(command).await.map_err(|_| ApiError::TreeUnavailable)
+    }
+}
+
+impl PendingAppend {
+    /// Appends the leaf, e.g. once the transaction storing it has committed, and returns its
+    /// position.
+    pub async fn apply(self) -> Result<usize, ApiError> {
+        let (reply, receiver) = oneshot::channel();
+        self.service.send(Command::Apply { prepared: self.prepared, reply }).await?;
+        Ok(receiver.await.map_err(|_| ApiError::TreeUnavailable)??)
+    }
+}